@@ -1,8 +1,12 @@
+#![allow(clippy::redundant_closure)]
 #[macro_use]
 extern crate criterion;
 
 use criterion::Criterion;
 
+extern crate bytes;
+use bytes::IntoBuf;
+
 extern crate uorb_codec;
 use uorb_codec::common::*;
 use uorb_codec::UorbMsgMeta;
@@ -66,4 +70,50 @@ fn criterion_deser_benchmark(c: &mut Criterion) {
 
 
 criterion_group!(benches, criterion_serialization_benchmark, criterion_deser_benchmark);
-criterion_main!(benches);
\ No newline at end of file
+
+/// Serialize one message type into a reused buffer
+fn ser_into_sensor_gyro() {
+    let msg_data = get_sensor_gyro();
+    let mut buf = [0u8; SensorGyroData::ENCODED_LEN];
+    for _i in 0..100 {
+        msg_data.ser_into(&mut (&mut buf[..]).into_buf());
+    }
+}
+
+/// Encode complete frames into a reused buffer
+fn encode_frame_sensor_gyro() {
+    let msg_data = get_sensor_gyro();
+    let mut buf = [0u8; uorb_codec::MAX_FRAME_LEN];
+    for _i in 0..100 {
+        let _len = msg_data.encode_frame_into(0, msg_data.timestamp, &mut buf).unwrap();
+    }
+}
+
+/// Read a couple of fields through a borrowed view
+fn view_sensor_gyro() {
+    let msg_data = get_sensor_gyro();
+    let encoded:Vec<u8> = msg_data.ser();
+    let sliced_encoded = encoded.as_slice();
+    for _i in 0..100 {
+        let view = SensorGyroView::new(sliced_encoded).unwrap();
+        assert_eq!(msg_data.timestamp, view.timestamp());
+        assert_eq!(msg_data.x, view.x());
+    }
+}
+
+fn criterion_ser_into_benchmark(c: &mut Criterion) {
+    c.bench_function("serialization_into", |b| b.iter(ser_into_sensor_gyro));
+}
+fn criterion_encode_frame_benchmark(c: &mut Criterion) {
+    c.bench_function("encode_frame_into", |b| b.iter(encode_frame_sensor_gyro));
+}
+fn criterion_view_benchmark(c: &mut Criterion) {
+    c.bench_function("view", |b| b.iter(view_sensor_gyro));
+}
+
+criterion_group!(buffer_benches,
+    criterion_ser_into_benchmark,
+    criterion_encode_frame_benchmark,
+    criterion_view_benchmark);
+
+criterion_main!(benches, buffer_benches);
\ No newline at end of file
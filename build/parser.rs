@@ -3,22 +3,22 @@ extern crate proc_macro2;
 
 use std::fs::{self, File};
 use std::path::{Path};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use quote::{ ToTokens, TokenStreamExt};
 use proc_macro2::{Ident, Span, TokenStream};
 
-use crc16;
 use heck::{CamelCase};
 
 use std::default::Default;
 use std::io::{Read, Write, BufRead, BufReader};
 
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Default)]
 pub enum UorbFieldType {
     Bool,
     Char,
+    #[default]
     UInt8,
     UInt16,
     UInt32,
@@ -103,6 +103,27 @@ impl UorbFieldType {
     }
 
 
+    /// Emit the FieldType of this type, or of the elements of an array, and the array length
+    pub fn field_info_type(&self) -> (TokenStream, TokenStream) {
+        use self::UorbFieldType::*;
+        let base = match self {
+            Bool => quote!(FieldType::Bool),
+            Char => quote!(FieldType::Char),
+            UInt8 => quote!(FieldType::UInt8),
+            UInt16 => quote!(FieldType::UInt16),
+            UInt32 => quote!(FieldType::UInt32),
+            UInt64 => quote!(FieldType::UInt64),
+            Int8 => quote!(FieldType::Int8),
+            Int16 => quote!(FieldType::Int16),
+            Int32 => quote!(FieldType::Int32),
+            Int64 => quote!(FieldType::Int64),
+            Float32 => quote!(FieldType::Float32),
+            Float64 => quote!(FieldType::Float64),
+            Array(t, size) => return (t.field_info_type().0, quote!(Some(#size))),
+        };
+        (base, quote!(None))
+    }
+
     /// Variation of encoding len used for field sorting rule
     pub fn field_sorting_len(&self) -> usize {
         use self::UorbFieldType::*;
@@ -134,74 +155,229 @@ impl UorbFieldType {
             UInt64 => quote!{#buf.put_u64_le(#val);},
             Int64 => quote!{#buf.put_i64_le(#val);},
             Float64 => quote!{#buf.put_f64_le(#val);},
-            Array(t,_size) => {
-                let w = t.rust_writer("*val".to_string(), buf_name.clone());
-                quote!{
-                    #buf.put_u8(#val.len() as u8);
-                    for val in &#val {
-                        #w
+            Array(t, size) => {
+                // fixed-size arrays are written without a length prefix,
+                // so that the encoded size always matches ENCODED_LEN
+                if size > 32 {
+                    // a vector may not hold exactly `size` elements
+                    let w = t.rust_writer("val".to_string(), buf_name.clone());
+                    quote!{
+                        for idx in 0..#size {
+                            let val = #val.get(idx).cloned().unwrap_or_default();
+                            #w
+                        }
+                    }
+                } else {
+                    let w = t.rust_writer("*val".to_string(), buf_name.clone());
+                    quote!{
+                        for val in &#val {
+                            #w
+                        }
                     }
                 }
             },
         }
     }
 
-    /// Emit reader of a given type
-    pub fn rust_reader(&self, name: String, buf_name: String) -> TokenStream {
+    /// Emit a statement that writes a given type at `offset` within a byte slice
+    pub fn rust_slice_writer(&self, name: String, buf_name: String, offset: TokenStream) -> TokenStream {
         use self::UorbFieldType::*;
         let val:TokenStream = name.parse().unwrap();
         let buf:TokenStream = buf_name.parse().unwrap();
 
         match self.clone() {
-            Bool => quote!{#val = #buf.get_u8() != 0;},
-            Char => quote!{#val = #buf.get_u8() as char;},
-            UInt8 => quote!{#val = #buf.get_u8();},
-            UInt16 => quote!{#val = #buf.get_u16_le();},
-            UInt32 => quote!{#val = #buf.get_u32_le();},
-            UInt64 => quote!{#val = #buf.get_u64_le();},
-            Int8 => quote!{#val = #buf.get_i8();},
-            Int16 => quote!{#val = #buf.get_i16_le();},
-            Int32 => quote!{#val = #buf.get_i32_le();},
-            Int64 => quote!{#val = #buf.get_i64_le();},
-            Float32 => quote!{#val = #buf.get_f32_le();},
-            Float64 => quote!{#val = #buf.get_f64_le();},
-            Array(t, size) => {
-                if size > 32 {
-                    // it is a vector
-                    let r = t.rust_reader("let val".to_string(), buf_name.clone());
-                    quote!{
-                        for _ in 0..#size {
-                            #r
-                            #val.push(val);
-                        }
-                    }
-                } else {
-                    // handle as a slice
-                    let r = t.rust_reader("let val".to_string(), buf_name.clone());
-                    quote!{
-                        for idx in 0..#val.len() {
-                            #r
-                            #val[idx] = val;
-                        }
-                    }
-                }
-            }
+            Bool => quote!{#buf[#offset] = #val as u8;},
+            Char => quote!{#buf[#offset] = #val as u8;},
+            UInt8 => quote!{#buf[#offset] = #val;},
+            Int8 => quote!{#buf[#offset] = #val as u8;},
+            UInt16 => quote!{LittleEndian::write_u16(&mut #buf[#offset..], #val);},
+            UInt32 => quote!{LittleEndian::write_u32(&mut #buf[#offset..], #val);},
+            UInt64 => quote!{LittleEndian::write_u64(&mut #buf[#offset..], #val);},
+            Int16 => quote!{LittleEndian::write_i16(&mut #buf[#offset..], #val);},
+            Int32 => quote!{LittleEndian::write_i32(&mut #buf[#offset..], #val);},
+            Int64 => quote!{LittleEndian::write_i64(&mut #buf[#offset..], #val);},
+            Float32 => quote!{LittleEndian::write_f32(&mut #buf[#offset..], #val);},
+            Float64 => quote!{LittleEndian::write_f64(&mut #buf[#offset..], #val);},
+            Array(..) => panic!("arrays are written one element at a time"),
         }
     }
-}
 
-impl Default for UorbFieldType {
-    fn default() -> UorbFieldType {
-        UorbFieldType::UInt8
+    /// Emit an expression that reads a given type at `offset` within a byte slice
+    pub fn rust_slice_reader(&self, buf_name: String, offset: TokenStream) -> TokenStream {
+        use self::UorbFieldType::*;
+        let buf:TokenStream = buf_name.parse().unwrap();
+
+        match self.clone() {
+            Bool => quote!{#buf[#offset] != 0},
+            Char => quote!{#buf[#offset] as char},
+            UInt8 => quote!{#buf[#offset]},
+            Int8 => quote!{#buf[#offset] as i8},
+            UInt16 => quote!{LittleEndian::read_u16(&#buf[#offset..])},
+            UInt32 => quote!{LittleEndian::read_u32(&#buf[#offset..])},
+            UInt64 => quote!{LittleEndian::read_u64(&#buf[#offset..])},
+            Int16 => quote!{LittleEndian::read_i16(&#buf[#offset..])},
+            Int32 => quote!{LittleEndian::read_i32(&#buf[#offset..])},
+            Int64 => quote!{LittleEndian::read_i64(&#buf[#offset..])},
+            Float32 => quote!{LittleEndian::read_f32(&#buf[#offset..])},
+            Float64 => quote!{LittleEndian::read_f64(&#buf[#offset..])},
+            Array(..) => panic!("arrays are read one element at a time"),
+        }
     }
 }
 
+/// Phrases in field comments that name a unit, with its normalized name and the
+/// factor from the field's value to that unit. Matched regardless of case, and
+/// only as whole words.
+const UNIT_PATTERNS: &[(&str, &str, f64)] = &[
+    ("microseconds", "us", 1.0),
+    ("nanoseconds", "ns", 1.0),
+    ("in seconds", "s", 1.0),
+    ("in min", "min", 1.0),
+    ("in volts", "V", 1.0),
+    ("in amperes", "A", 1.0),
+    ("in mah", "mAh", 1.0),
+    ("in degrees celsius", "degC", 1.0),
+    ("in deg c", "degC", 1.0),
+    ("in rad/s", "rad/s", 1.0),
+    ("in m/s^2", "m/s^2", 1.0),
+    ("in m/s", "m/s", 1.0),
+    ("in gauss", "gauss", 1.0),
+    ("in millibar", "mbar", 1.0),
+    ("(metres/sec)", "m/s", 1.0),
+    ("(millimetres)", "m", 0.001),
+    ("(metres)", "m", 1.0),
+    ("(m)", "m", 1.0),
+    ("(radians)", "rad", 1.0),
+    ("(rad", "rad", 1.0),
+];
+
+/// Unit words after a power of ten, as in `1E-7 degrees`, and in annotations
+const UNIT_ALIASES: &[(&str, &str)] = &[
+    ("degrees", "deg"),
+    ("degree", "deg"),
+    ("deg", "deg"),
+    ("meters", "m"),
+    ("metres", "m"),
+    ("m", "m"),
+    ("radians", "rad"),
+    ("rad", "rad"),
+    ("seconds", "s"),
+    ("microseconds", "us"),
+    ("nanoseconds", "ns"),
+    ("volts", "V"),
+    ("amperes", "A"),
+    ("celsius", "degC"),
+];
+
+/// Units of fields whose comments name none, or the wrong one, as (message, field,
+/// annotation), the annotation written as it would be between `[` and `]` in a comment.
+/// Kept here so the .msg files stay as they are upstream in PX4.
+const UNIT_OVERRIDES: &[(&str, &str, &str)] = &[
+    ("actuator_controls", "timestamp_sample", "us"),
+    ("battery_status", "voltage_cell_v", "V"),
+    ("battery_status", "max_cell_voltage_delta", "V"),
+    ("differential_pressure", "differential_pressure_raw_pa", "Pa"),
+    ("differential_pressure", "differential_pressure_filtered_pa", "Pa"),
+    ("differential_pressure", "temperature", "degC"),
+    // delta angles, though the comments say rad/s
+    ("sensor_gyro", "x_integral", "rad"),
+    ("sensor_gyro", "y_integral", "rad"),
+    ("sensor_gyro", "z_integral", "rad"),
+];
+
+fn unit_alias(word: &str) -> Option<&'static str> {
+    UNIT_ALIASES.iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(word))
+        .map(|(_, unit)| *unit)
+}
+
+/// The unit of a field: its normalized name, and the factor from the field's value to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct UorbUnit {
+    pub name: String,
+    pub scale: f64,
+}
+
+impl UorbUnit {
+    /// The unit of a field and its comment without any unit annotation.
+    ///
+    /// A comment starting with `[unit]` or `[scale unit]`, as in `[1e-7 deg]`,
+    /// names the unit outright, and `[-]` marks the field as having none.
+    /// Otherwise the first phrase of UNIT_PATTERNS, or a power of ten followed by
+    /// a unit word, gives the unit.
+    pub fn parse_comment(comment: &str) -> (Option<UorbUnit>, String) {
+        let trimmed = comment.trim();
+        if trimmed.starts_with('[') && !trimmed.starts_with("[@") {
+            if let Some(end) = trimmed.find(']') {
+                let unit = UorbUnit::from_annotation(&trimmed[1..end]);
+                return (unit, trimmed[end + 1..].trim().to_string());
+            }
+        }
+        let unit = UorbUnit::from_power_of_ten(trimmed).or_else(|| UorbUnit::from_patterns(trimmed));
+        (unit, trimmed.to_string())
+    }
+
+    fn from_annotation(text: &str) -> Option<UorbUnit> {
+        let toks: Vec<&str> = text.split_whitespace().collect();
+        let (scale, name) = match toks[..] {
+            [] | ["-"] => return None,
+            [scale, name] if scale.parse::<f64>().is_ok() => (scale.parse().unwrap(), name),
+            _ => (1.0, text.trim()),
+        };
+        let name = unit_alias(name).unwrap_or(name).to_string();
+        Some(UorbUnit { name, scale })
+    }
+
+    fn from_power_of_ten(comment: &str) -> Option<UorbUnit> {
+        let toks: Vec<&str> = comment.split_whitespace().collect();
+        toks.windows(2).find_map(|pair| {
+            if !pair[0].to_ascii_lowercase().starts_with("1e") {
+                return None;
+            }
+            let scale = pair[0].parse::<f64>().ok()?;
+            let word = pair[1].trim_matches(|c: char| !c.is_ascii_alphanumeric());
+            unit_alias(word).map(|name| UorbUnit { name: name.to_string(), scale })
+        })
+    }
+
+    fn from_patterns(comment: &str) -> Option<UorbUnit> {
+        let lower = comment.to_ascii_lowercase();
+        let is_word = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+        // the earliest phrase wins, then the longest at that position
+        UNIT_PATTERNS.iter()
+            .filter_map(|&(phrase, name, scale)| {
+                let pos = lower.match_indices(phrase).map(|(pos, _)| pos).find(|&pos| {
+                    let before = lower[..pos].chars().next_back();
+                    let after = lower[pos + phrase.len()..].chars().next();
+                    let starts_mid_word = is_word(phrase.chars().next()) && is_word(before);
+                    let ends_mid_word = is_word(phrase.chars().next_back()) && is_word(after);
+                    !starts_mid_word && !ends_mid_word
+                })?;
+                Some((pos, phrase.len(), name, scale))
+            })
+            .min_by_key(|&(pos, len, _, _)| (pos, usize::MAX - len))
+            .map(|(_, _, name, scale)| UorbUnit { name: name.to_string(), scale })
+    }
+
+    /// Suffix of the accessor returning a field in this unit, such as `deg` or `m_s`
+    pub fn accessor_suffix(&self) -> String {
+        let suffix: String = self.name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+            .collect();
+        suffix.split('_').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("_")
+    }
+
+    fn scale_tokens(&self) -> TokenStream {
+        format!("{:?}", self.scale).parse().unwrap()
+    }
+}
 
 #[derive(Debug, Clone, Default)]
 pub struct UorbMsgField {
     pub uorbtype: UorbFieldType,
     pub name: String,
     pub description: Option<String>,
+    pub unit: Option<UorbUnit>,
 }
 
 impl UorbMsgField {
@@ -219,24 +395,32 @@ impl UorbMsgField {
         }
 
         let fname = toks[1];
+        let (unit, description) = match comment {
+            Some(comment) => {
+                let (unit, rest) = UorbUnit::parse_comment(&comment);
+                (unit, if rest.is_empty() { None } else { Some(rest) })
+            }
+            None => (None, None),
+        };
 
         let ftype = UorbFieldType::parse_type(toks[0]);
         match ftype {
             Some(le_type) => {
-                return Some(UorbMsgField {
+                Some(UorbMsgField {
                     uorbtype: le_type,
                     name: fname.to_string(),
-                    description: comment,
+                    description,
+                    unit,
                 })
             },
             _ => {
                 println!("failed to parse: {:?}", toks[0]);
-                return None
+                None
             }
         }
     }
 
-    /// Emit writer that will write this field to Vec<u8>
+    /// Emit writer that will write this field to a BufMut
     fn rust_writer(&self) -> TokenStream {
         let name = "self.".to_string() + &self.name.clone();
         let buf  = "buf".to_string();
@@ -244,12 +428,155 @@ impl UorbMsgField {
         self.uorbtype.rust_writer(name, buf)
     }
 
-    /// Emit reader that will read this field from a buffer
-    fn rust_reader(&self) -> TokenStream {
-        let name =  "msg.".to_string() + &self.name.clone();
+    /// Emit writer that will write this field into a byte slice,
+    /// where the field starts `offset` bytes into the payload
+    fn rust_slice_writer(&self, offset: usize) -> TokenStream {
+        let name =  "self.".to_string() + &self.name.clone();
         let buf  = "buf".to_string();
 
-        self.uorbtype.rust_reader(name, buf)
+        match self.uorbtype {
+            UorbFieldType::Array(ref t, size) => {
+                let elem_len = t.encoded_len();
+                let w = t.rust_slice_writer("*val".to_string(), buf, quote!(offset));
+                let val:TokenStream = name.parse().unwrap();
+                quote!{
+                    for (idx, val) in #val.iter().take(#size).enumerate() {
+                        let offset = #offset + idx * #elem_len;
+                        #w
+                    }
+                }
+            },
+            ref t => t.rust_slice_writer(name, buf, quote!(#offset)),
+        }
+    }
+
+    /// Emit a statement writing this field to a CdrWriter `w`
+    fn rust_cdr_writer(&self) -> TokenStream {
+        let name:Ident = Ident::new(&self.name, Span::call_site());
+        match self.uorbtype {
+            UorbFieldType::Array(_, size) if size > 32 => quote!{
+                for idx in 0..#size {
+                    w.write(self.#name.get(idx).cloned().unwrap_or_default())?;
+                }
+            },
+            UorbFieldType::Array(_, _) => quote!{w.write_array(&self.#name)?;},
+            _ => quote!{w.write(self.#name)?;},
+        }
+    }
+
+    /// Emit a statement reading this field from a CdrReader `r` into `msg`
+    fn rust_cdr_reader(&self) -> TokenStream {
+        let name:Ident = Ident::new(&self.name, Span::call_site());
+        match self.uorbtype {
+            UorbFieldType::Array(_, size) if size > 32 => quote!{
+                msg.#name = vec![Default::default(); #size];
+                r.read_array(&mut msg.#name)?;
+            },
+            UorbFieldType::Array(_, _) => quote!{r.read_array(&mut msg.#name)?;},
+            _ => quote!{msg.#name = r.read()?;},
+        }
+    }
+
+    /// Whether this field is too large to be represented as a Rust array
+    pub fn is_vec(&self) -> bool {
+        match self.uorbtype {
+            UorbFieldType::Array(_, size) => size > 32,
+            _ => false,
+        }
+    }
+
+    /// Emit an accessor method that lazily reads this field from a borrowed buffer,
+    /// where the field starts `offset` bytes into the payload
+    fn rust_view_accessor(&self, offset: usize) -> TokenStream {
+        let name:Ident = Ident::new(&self.name, Span::call_site());
+        let desc = self.description.clone()
+            .unwrap_or_else(|| format!("Read the `{}` field", self.name));
+        let desc = desc.trim();
+
+        match self.uorbtype {
+            UorbFieldType::Array(ref t, size) => {
+                let elem_len = t.encoded_len();
+                let rust_type:TokenStream = t.rust_type().parse().unwrap();
+                let r = t.rust_slice_reader("self.buf".to_string(), quote!(offset));
+                quote!{
+                    #[doc = #desc]
+                    pub fn #name(&self, idx: usize) -> #rust_type {
+                        assert!(idx < #size, "index out of range");
+                        let offset = #offset + idx * #elem_len;
+                        #r
+                    }
+                }
+            },
+            ref t => {
+                let rust_type:TokenStream = t.rust_type().parse().unwrap();
+                let r = t.rust_slice_reader("self.buf".to_string(), quote!(#offset));
+                quote!{
+                    #[doc = #desc]
+                    pub fn #name(&self) -> #rust_type {
+                        #r
+                    }
+                }
+            }
+        }
+    }
+
+    /// Emit an accessor returning this field in its unit, as f64, if it has a unit
+    /// with a scale other than one. `read` reads the field, or one of its elements
+    /// as `idx`, in its own type.
+    fn rust_unit_accessor(&self, read: TokenStream) -> TokenStream {
+        let unit = match self.unit {
+            Some(ref unit) if unit.scale != 1.0 => unit,
+            _ => return TokenStream::new(),
+        };
+        let numeric = match self.uorbtype {
+            UorbFieldType::Array(ref t, _) => **t != UorbFieldType::Bool && **t != UorbFieldType::Char,
+            ref t => *t != UorbFieldType::Bool && *t != UorbFieldType::Char,
+        };
+        if !numeric {
+            return TokenStream::new();
+        }
+        let name = Ident::new(&format!("{}_{}", self.name, unit.accessor_suffix()), Span::call_site());
+        let desc = format!("The `{}` field in {}", self.name, unit.name);
+        let scale = unit.scale_tokens();
+        match self.uorbtype {
+            UorbFieldType::Array(_, _) => quote!{
+                #[doc = #desc]
+                pub fn #name(&self, idx: usize) -> f64 {
+                    #read as f64 * #scale
+                }
+            },
+            _ => quote!{
+                #[doc = #desc]
+                pub fn #name(&self) -> f64 {
+                    #read as f64 * #scale
+                }
+            },
+        }
+    }
+
+    /// Emit a statement copying this field from a view into an owned struct
+    fn rust_view_copier(&self) -> TokenStream {
+        let name:Ident = Ident::new(&self.name, Span::call_site());
+
+        match self.uorbtype {
+            UorbFieldType::Array(_, size) => {
+                if size > 32 {
+                    // it is a vector
+                    quote!{
+                        msg.#name = (0..#size).map(|idx| self.#name(idx)).collect();
+                    }
+                } else {
+                    quote!{
+                        for (idx, val) in msg.#name.iter_mut().enumerate() {
+                            *val = self.#name(idx);
+                        }
+                    }
+                }
+            },
+            _ => quote!{
+                msg.#name = self.#name();
+            }
+        }
     }
 }
 
@@ -262,8 +589,17 @@ impl ToTokens for UorbMsgField {
         let raw_rust_type: String = self.uorbtype.rust_type();
         let rust_type:TokenStream = raw_rust_type.parse().unwrap();
 
-        //TODO description?
+        // Vec-backed arrays must still hold exactly their declared number of elements
+        let serde_attr = match self.uorbtype {
+            UorbFieldType::Array(_, size) if size > 32 => {
+                let with = format!("crate::serde_arrays::exact_len::<_, _, {}>", size);
+                quote!(#[cfg_attr(feature = "serde", serde(deserialize_with = #with))])
+            }
+            _ => TokenStream::new(),
+        };
+
         let toks = quote!(
+        #serde_attr
         pub #name: #rust_type,
         );
         tokens.append_all(toks);
@@ -301,7 +637,7 @@ impl UorbMsgConst {
                 uorbtype: ftype,
                 name: Some(fname.to_string()),
                 description: comment,
-                const_val: const_val
+                const_val
             })
     }
 }
@@ -330,7 +666,10 @@ pub struct UorbMsg {
     pub raw_name: String,
     pub name_hash_val: u16,
     pub description: Option<String>,
+    /// Fields sorted for the packed uORB layout
     pub fields: Vec<UorbMsgField>,
+    /// Fields in declaration order, as CDR serializes them
+    pub decl_fields: Vec<UorbMsgField>,
     pub consts: Vec<UorbMsgConst>,
     pub topics: Vec<String>,
 }
@@ -342,11 +681,12 @@ impl UorbMsg {
         println!("msg raw_name {:?} hash_val: {}", raw_name, hash_val);
 
         let mut msg: UorbMsg = UorbMsg {
-            name: name,
-            raw_name: raw_name,
+            name,
+            raw_name,
             name_hash_val: hash_val,
             description: None,
             fields: vec![],
+            decl_fields: vec![],
             consts: vec![],
             topics: vec![],
         };
@@ -367,7 +707,7 @@ impl UorbMsg {
                         1 => { //all comment, as in a TOPICS line
                             println!("All comment: {:?}", comment_split);
                             let topics_exist: Vec<&str> = comment_split[0].split("TOPICS").collect();
-                            if topics_exist.len() > 0 {
+                            if !topics_exist.is_empty() {
                                 let topics: Vec<&str> = topics_exist[0].split_whitespace().collect();
                                 for topic in topics {
                                     all_topics.push(topic.to_string());
@@ -397,6 +737,7 @@ impl UorbMsg {
         }
 
         msg.topics = all_topics;
+        msg.decl_fields = msg.fields.clone();
         msg.sort_fields_by_desc_encoded_len();
 
         msg
@@ -411,13 +752,18 @@ impl UorbMsg {
                 }
             }
         }
-        else if let Some(field) = UorbMsgField::from_line(field_desc, comment) {
+        else if let Some(mut field) = UorbMsgField::from_line(field_desc, comment) {
+            let overridden = UNIT_OVERRIDES.iter()
+                .find(|&&(msg_name, field_name, _)| msg_name == msg.raw_name && field_name == field.name);
+            if let Some(&(_, _, annotation)) = overridden {
+                field.unit = UorbUnit::from_annotation(annotation);
+            }
             msg.fields.push(field);
         }
     }
 
 
-    /// Support for tokenizing
+    // Support for tokenizing
 
     /// Emit rust consts
     fn emit_constants(&self) -> TokenStream {
@@ -453,26 +799,43 @@ impl UorbMsg {
         format!("{}Data",self.name).parse().unwrap()
     }
 
-    fn emit_deserialize_fields(&self) -> TokenStream {
-        let deser_fields = self.fields.iter()
-            .map(|f| {
-                f.rust_reader()
-            }).collect::<Vec<TokenStream>>();
-
-        //println!("deser_fields  {:?}",deser_fields);
+    fn emit_slice_serialize_fields(&self) -> TokenStream {
+        let mut offset = 0;
+        let mut ser_fields = vec![];
+        for field in &self.fields {
+            ser_fields.push(field.rust_slice_writer(offset));
+            offset += field.uorbtype.encoded_len();
+        }
+        quote!{
+                #(#ser_fields)*
+            }
+    }
 
-        if deser_fields.is_empty() {
-            // struct has no fields
-            quote!{
-                    Some(Self::default())
-                }
-        } else {
-            quote!{
-                    let mut msg = Self::default();
-                    #(#deser_fields)*
-                    Some(msg)
+    /// Emit the FieldInfo layout of every field, in encoded order
+    fn emit_field_infos(&self) -> TokenStream {
+        let mut offset = 0;
+        let mut infos = vec![];
+        for field in &self.fields {
+            let name = field.name.clone();
+            let (field_type, array_len) = field.uorbtype.field_info_type();
+            let unit = match field.unit {
+                Some(ref unit) => {
+                    let unit_name = unit.name.clone();
+                    let scale = unit.scale_tokens();
+                    quote!(Some(FieldUnit { name: #unit_name, scale: #scale }))
                 }
+                None => quote!(None),
+            };
+            infos.push(quote!(FieldInfo {
+                name: #name,
+                field_type: #field_type,
+                array_len: #array_len,
+                offset: #offset,
+                unit: #unit,
+            },));
+            offset += field.uorbtype.encoded_len();
         }
+        quote!(&[#(#infos)*])
     }
 
     fn emit_serialize_fields(&self) -> TokenStream {
@@ -481,28 +844,163 @@ impl UorbMsg {
                 f.rust_writer()
             }).collect::<Vec<TokenStream>>();
         quote!{
-                let mut buf = Vec::new();
                 #(#ser_fields)*
-                buf
             }
     }
 
+    fn emit_cdr_serialize_fields(&self) -> TokenStream {
+        let ser_fields = self.decl_fields.iter()
+            .map(|f| f.rust_cdr_writer())
+            .collect::<Vec<TokenStream>>();
+        quote!{
+                #(#ser_fields)*
+            }
+    }
+
+    fn emit_cdr_deserialize_fields(&self) -> TokenStream {
+        let deser_fields = self.decl_fields.iter()
+            .map(|f| f.rust_cdr_reader())
+            .collect::<Vec<TokenStream>>();
+        quote!{
+                #(#deser_fields)*
+            }
+    }
+
+    /// Size of the CDR encoding, with each primitive aligned to its own size
+    pub fn cdr_encoded_len(&self) -> usize {
+        self.decl_fields.iter().fold(0, |len, field| {
+            let elem_len = field.uorbtype.field_sorting_len();
+            let aligned = len.div_ceil(elem_len) * elem_len;
+            aligned + field.uorbtype.encoded_len()
+        })
+    }
+
+    /// Emit a borrowed view type with lazy per-field accessors
+    fn emit_view(&self) -> TokenStream {
+        let view_name:TokenStream = format!("{}View", self.name).parse().unwrap();
+        let inner_struct_name = self.emit_inner_struct_name();
+        let view_doc = format!("Borrowed view of an encoded `{}` payload, whose fields are read on demand",
+                               self.raw_name);
+
+        let mut offset = 0;
+        let mut accessors = vec![];
+        for field in &self.fields {
+            accessors.push(field.rust_view_accessor(offset));
+            offset += field.uorbtype.encoded_len();
+        }
+        let copiers = self.fields.iter()
+            .map(|f| f.rust_view_copier())
+            .collect::<Vec<TokenStream>>();
+        let unit_accessors = self.fields.iter()
+            .map(|f| {
+                let name = Ident::new(&f.name, Span::call_site());
+                match f.uorbtype {
+                    UorbFieldType::Array(_, _) => f.rust_unit_accessor(quote!(self.#name(idx))),
+                    _ => f.rust_unit_accessor(quote!(self.#name())),
+                }
+            })
+            .collect::<Vec<TokenStream>>();
+
+        let cfg = self.emit_cfg();
+
+        quote!{
+            #cfg
+            #[doc = #view_doc]
+            #[derive(Debug, Clone, Copy, PartialEq)]
+            pub struct #view_name<'a> {
+                buf: &'a [u8],
+            }
+
+            #cfg
+            impl<'a> #view_name<'a> {
+                /// Wrap an encoded payload, which must hold at least ENCODED_LEN bytes
+                pub fn new(input: &'a [u8]) -> Option<Self> {
+                    if input.len() < #inner_struct_name::ENCODED_LEN {
+                        None
+                    } else {
+                        Some(Self { buf: input })
+                    }
+                }
+
+                /// The underlying encoded payload
+                pub fn as_bytes(&self) -> &'a [u8] {
+                    self.buf
+                }
+
+                #(#accessors)*
+
+                #(#unit_accessors)*
+
+                /// Copy every field out into the owned data type
+                pub fn to_owned(&self) -> #inner_struct_name {
+                    let mut msg = #inner_struct_name::default();
+                    #(#copiers)*
+                    msg
+                }
+            }
+        }
+    }
+
+    /// Emit accessors returning scaled fields in their units
+    fn emit_unit_accessors(&self) -> TokenStream {
+        let accessors = self.fields.iter()
+            .map(|f| {
+                let name = Ident::new(&f.name, Span::call_site());
+                match f.uorbtype {
+                    UorbFieldType::Array(_, _) => f.rust_unit_accessor(quote!(self.#name[idx])),
+                    _ => f.rust_unit_accessor(quote!(self.#name)),
+                }
+            })
+            .collect::<Vec<TokenStream>>();
+        quote!(#(#accessors)*)
+    }
+
+    /// Messages with Vec-backed arrays are only available with the `std` feature
+    pub fn needs_std(&self) -> bool {
+        self.fields.iter().any(|f| f.is_vec())
+    }
+
+    /// Emit the cfg attribute that gates this message's generated items
+    pub fn emit_cfg(&self) -> TokenStream {
+        if self.needs_std() {
+            quote!(#[cfg(feature = "std")])
+        } else {
+            TokenStream::new()
+        }
+    }
+
+    /// Total encoded size of all fields, in bytes
+    pub fn encoded_len(&self) -> usize {
+        self.fields.iter()
+            .map(|f| f.uorbtype.encoded_len())
+            .sum()
+    }
+
 }
 
 impl ToTokens for UorbMsg {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         let const_defs = self.emit_constants();
+        let unit_accessors = self.emit_unit_accessors();
         let field_defs = self.emit_field_defs();
 //        println!("field_defs: {:?}", field_defs);
-        let deser_fields = self.emit_deserialize_fields();
         let ser_fields = self.emit_serialize_fields();
-        //println!("deser_fields: {:?}",deser_fields);
+        let slice_ser_fields = self.emit_slice_serialize_fields();
+        let field_infos = self.emit_field_infos();
+        let cdr_ser_fields = self.emit_cdr_serialize_fields();
+        let cdr_deser_fields = self.emit_cdr_deserialize_fields();
+        let cdr_encoded_len = self.cdr_encoded_len();
+        let view = self.emit_view();
+        let view_name:TokenStream = format!("{}View", self.name).parse().unwrap();
+        let cfg = self.emit_cfg();
+        // Vec-backed arrays prevent deriving Copy
+        let (derives, wrapped) = if self.needs_std() {
+            (quote!(#[derive(Debug, Clone, PartialEq, Default)]), quote!(self.clone()))
+        } else {
+            (quote!(#[derive(Debug, Clone, Copy, PartialEq, Default)]), quote!(*self))
+        };
 
-        let mut encoded_msg_len = 0;
-        for field in self.fields.clone() {
-            encoded_msg_len += field.uorbtype.encoded_len();
-        }
-        let encoded_msg_len:TokenStream = format!("{:?}",encoded_msg_len).parse().unwrap();
+        let encoded_msg_len:TokenStream = format!("{:?}",self.encoded_len()).parse().unwrap();
 
         let raw_name = self.raw_name.clone();
         let hash_val = crc16::State::<crc16::MCRF4XX>::calculate(raw_name.as_bytes());
@@ -512,41 +1010,75 @@ impl ToTokens for UorbMsg {
 
         let inner_struct_name = self.emit_inner_struct_name();
         let enum_cast_name:TokenStream = self.name.clone().parse().unwrap();
+        let struct_doc = self.description.clone()
+            .unwrap_or_else(|| format!("Payload of the `{}` uORB message", self.raw_name));
         let toks = quote!(
 
-        #[derive(Debug, Clone, Copy, PartialEq, Default)]
+        #cfg
+        #[doc = #struct_doc]
+        #derives
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
         pub struct #inner_struct_name {
             #field_defs
         }
 
+        #cfg
         impl UorbMsgMeta for #inner_struct_name {
             const ENCODED_LEN: usize = #encoded_msg_len;
             const MSG_HASH_CODE: u16 = #hash_val;
             const MSG_RAW_NAME: &'static str = #raw_name;
+            const FIELDS: &'static [FieldInfo] = #field_infos;
+            const CDR_ENCODED_LEN: usize = #cdr_encoded_len;
 
-            fn ser(&self) -> Vec<u8> {
+            #[cfg(feature = "std")]
+            fn ser_into<B: BufMut>(&self, buf: &mut B) {
                 #ser_fields
             }
 
+            fn ser_into_slice(&self, buf: &mut [u8]) -> Option<usize> {
+                if buf.len() < Self::ENCODED_LEN {
+                    return None;
+                }
+                #slice_ser_fields
+                Some(Self::ENCODED_LEN)
+            }
+
+            fn deser(input: &[u8]) -> Option<Self> {
+                #view_name::new(input).map(|view| view.to_owned())
+            }
+
+            fn ser_cdr_body(&self, w: &mut CdrWriter) -> Option<()> {
+                #cdr_ser_fields
+                Some(())
+            }
+
+            fn deser_cdr_body(r: &mut CdrReader) -> Option<Self> {
+                let mut msg = Self::default();
+                #cdr_deser_fields
+                Some(msg)
+            }
+
             fn wrap(&self) -> UorbMessage {
-                UorbMessage::#enum_cast_name(*self)
+                UorbMessage::#enum_cast_name(#wrapped)
+            }
+
+            #[allow(unreachable_patterns)]
+            fn unwrap_message(msg: UorbMessage) -> Option<Self> {
+                match msg {
+                    UorbMessage::#enum_cast_name(body) => Some(body),
+                    _ => None,
+                }
             }
         }
 
+        #cfg
         impl #inner_struct_name {
             #const_defs
-            pub fn deser(input: &[u8]) -> Option<Self> {
-                if input.len() < Self::ENCODED_LEN {
-                    None
-                }
-                else {
-                    //fast zero copy
-                    let mut buf = Bytes::from(input).into_buf();
-                    #deser_fields
-                }
-            }
 
+            #unit_accessors
         }
+
+        #view
         );
 
         tokens.append_all(toks);
@@ -559,6 +1091,10 @@ impl ToTokens for UorbMsg {
 pub struct Parser {
     msg_list: Vec<String>,
     msg_map: HashMap<u16, String>,
+    raw_names: HashMap<String, String>,
+    std_only_msgs: HashSet<String>,
+    timestamped_msgs: HashSet<String>,
+    max_encoded_len: usize,
 }
 
 impl Parser {
@@ -566,9 +1102,33 @@ impl Parser {
         Parser {
             msg_list: vec![],
             msg_map: HashMap::with_capacity(255),
+            raw_names: HashMap::new(),
+            std_only_msgs: HashSet::new(),
+            timestamped_msgs: HashSet::new(),
+            max_encoded_len: 0,
         }
     }
 
+    /// The cfg attribute that gates the generated items for the named message
+    fn msg_cfg(&self, msg_name: &str) -> TokenStream {
+        if self.std_only_msgs.contains(msg_name) {
+            quote!(#[cfg(feature = "std")])
+        } else {
+            TokenStream::new()
+        }
+    }
+
+    /// Emit one (cfg-gated) enum variant or match arm per known message
+    fn msg_arms<F: Fn(&str) -> TokenStream>(&self, arm: F) -> Vec<TokenStream> {
+        self.msg_list.iter()
+            .map(|msg_name| {
+                let cfg = self.msg_cfg(msg_name);
+                let arm = arm(msg_name);
+                quote!(#cfg #arm)
+            })
+            .collect()
+    }
+
     fn write_rust_to_output<W: Write>(&self, tokens: &TokenStream, output_rust: &mut W) {
         let rust_src = tokens.to_string();
         //println!("rust_src: {:?}", rust_src);
@@ -588,9 +1148,17 @@ impl Parser {
         let name = raw_name.to_camel_case();
         println!("msg name: {:?} converted: {:?}", raw_name, name);
         self.msg_list.push(name.clone());
+        self.raw_names.insert(name.clone(), raw_name.clone());
 
         let msg: UorbMsg = UorbMsg::from_lines(raw_name, name.clone(), input);
         self.msg_map.insert(msg.name_hash_val, name.clone());
+        self.max_encoded_len = self.max_encoded_len.max(msg.encoded_len());
+        if msg.needs_std() {
+            self.std_only_msgs.insert(name.clone());
+        }
+        if msg.fields.iter().any(|f| f.name == "timestamp" && f.uorbtype == UorbFieldType::UInt64) {
+            self.timestamped_msgs.insert(name.clone());
+        }
 
         let mut top_tokens = TokenStream::new();
         msg.to_tokens(&mut top_tokens);
@@ -625,44 +1193,176 @@ impl Parser {
 
 
         //we now have msg_list with a list of all uORB messages
-        let msg_enum_names = self.msg_list.iter()
-            .map(|msg_name| {
-                let line = format!("{}({}Data),",msg_name,msg_name);
-                line.parse().unwrap()
+        let msg_enum_names = self.msg_arms(|msg_name| {
+            let raw_name = &self.raw_names[msg_name];
+            let variant: TokenStream = format!("{}({}Data),",msg_name,msg_name).parse().unwrap();
+            quote!(#[cfg_attr(feature = "serde", serde(rename = #raw_name))] #variant)
+        });
+
+        let msg_data_deser_toks: Vec<TokenStream> = self.msg_map.iter()
+            .map(|(hash_val, msg_name)| {
+                let cfg = self.msg_cfg(msg_name);
+                let name_ident:TokenStream = msg_name.parse().unwrap();
+                let data_ident:TokenStream = format!("{}Data",msg_name).parse().unwrap();
+
+                quote!(
+                #cfg
+                #hash_val => #data_ident ::deser(payload).map(UorbMessage::#name_ident),
+                )
             })
             .collect::<Vec<TokenStream>>();
 
 
-        let msg_data_deser_toks: Vec<TokenStream> = self.msg_map.iter()
+        let msg_encoded_len_for_hash_toks: Vec<TokenStream> = self.msg_map.iter()
+            .map(|(hash_val, msg_name)| {
+                let cfg = self.msg_cfg(msg_name);
+                let data_ident:TokenStream = format!("{}Data",msg_name).parse().unwrap();
+
+                quote!(
+                #cfg
+                #hash_val => Some(#data_ident ::ENCODED_LEN),
+                )
+            })
+            .collect::<Vec<TokenStream>>();
+
+        let msg_fields_for_hash_toks: Vec<TokenStream> = self.msg_map.iter()
+            .map(|(hash_val, msg_name)| {
+                let cfg = self.msg_cfg(msg_name);
+                let data_ident:TokenStream = format!("{}Data",msg_name).parse().unwrap();
+
+                quote!(
+                #cfg
+                #hash_val => Some(#data_ident ::FIELDS),
+                )
+            })
+            .collect::<Vec<TokenStream>>();
+
+        let msg_raw_name_for_hash_toks: Vec<TokenStream> = self.msg_map.iter()
+            .map(|(hash_val, msg_name)| {
+                let cfg = self.msg_cfg(msg_name);
+                let data_ident:TokenStream = format!("{}Data",msg_name).parse().unwrap();
+
+                quote!(
+                #cfg
+                #hash_val => Some(#data_ident ::MSG_RAW_NAME),
+                )
+            })
+            .collect::<Vec<TokenStream>>();
+
+        let msg_hash_for_raw_name_toks = self.msg_arms(|msg_name| {
+            let data_ident:TokenStream = format!("{}Data",msg_name).parse().unwrap();
+            quote!(#data_ident ::MSG_RAW_NAME => Some(#data_ident ::MSG_HASH_CODE),)
+        });
+
+        let msg_cdr_encoded_len_for_hash_toks: Vec<TokenStream> = self.msg_map.iter()
             .map(|(hash_val, msg_name)| {
+                let cfg = self.msg_cfg(msg_name);
+                let data_ident:TokenStream = format!("{}Data",msg_name).parse().unwrap();
+
+                quote!(
+                #cfg
+                #hash_val => Some(#data_ident ::CDR_ENCODED_LEN),
+                )
+            })
+            .collect::<Vec<TokenStream>>();
+
+        let msg_deser_cdr_body_toks: Vec<TokenStream> = self.msg_map.iter()
+            .map(|(hash_val, msg_name)| {
+                let cfg = self.msg_cfg(msg_name);
                 let name_ident:TokenStream = msg_name.parse().unwrap();
                 let data_ident:TokenStream = format!("{}Data",msg_name).parse().unwrap();
 
                 quote!(
-                #hash_val => Some(UorbMessage::#name_ident (
-                    #data_ident ::deser(payload).unwrap()
-                )),
+                #cfg
+                #hash_val => #data_ident ::deser_cdr_body(r).map(UorbMessage::#name_ident),
                 )
             })
             .collect::<Vec<TokenStream>>();
 
+        let msg_ser_cdr_body_toks = self.msg_arms(|msg_name| {
+            let name_ident:TokenStream = msg_name.parse().unwrap();
+            quote!(UorbMessage::#name_ident(body) => body.ser_cdr_body(w),)
+        });
 
-        let msg_map_deser_names = self.msg_list.iter()
-            .map(|msg_name| {
-                msg_name.parse().unwrap()
+        let msg_known_hashes_toks = self.msg_arms(|msg_name| {
+            let data_ident:TokenStream = format!("{}Data",msg_name).parse().unwrap();
+            quote!(#data_ident ::MSG_HASH_CODE,)
+        });
+
+        let msg_view_parse_toks: Vec<TokenStream> = self.msg_map.iter()
+            .map(|(hash_val, msg_name)| {
+                let cfg = self.msg_cfg(msg_name);
+                let name_ident:TokenStream = msg_name.parse().unwrap();
+                let view_ident:TokenStream = format!("{}View",msg_name).parse().unwrap();
+
+                quote!(
+                #cfg
+                #hash_val => #view_ident ::new(payload).map(UorbMessageView::#name_ident),
+                )
             })
             .collect::<Vec<TokenStream>>();
 
+        let msg_view_enum_names = self.msg_arms(|msg_name| {
+            format!("{}({}View<'a>),",msg_name,msg_name).parse().unwrap()
+        });
+
+        let msg_view_to_owned_toks = self.msg_arms(|msg_name| {
+            let name_ident:TokenStream = msg_name.parse().unwrap();
+            quote!(UorbMessageView::#name_ident(view) => UorbMessage::#name_ident(view.to_owned()),)
+        });
+
+        let msg_ser_toks = self.msg_arms(|msg_name| {
+            let name_ident:TokenStream = msg_name.parse().unwrap();
+            quote!(UorbMessage::#name_ident(body) => body.ser(),)
+        });
+
+        let msg_ser_into_toks = self.msg_arms(|msg_name| {
+            let name_ident:TokenStream = msg_name.parse().unwrap();
+            quote!(UorbMessage::#name_ident(body) => body.ser_into(buf),)
+        });
+
+        let msg_ser_into_slice_toks = self.msg_arms(|msg_name| {
+            let name_ident:TokenStream = msg_name.parse().unwrap();
+            quote!(UorbMessage::#name_ident(body) => body.ser_into_slice(buf),)
+        });
+
+        let msg_encoded_len_toks = self.msg_arms(|msg_name| {
+            let name_ident:TokenStream = msg_name.parse().unwrap();
+            quote!(UorbMessage::#name_ident(body) => body.encoded_len(),)
+        });
+
+        let msg_timestamp_toks = self.msg_arms(|msg_name| {
+            let name_ident:TokenStream = msg_name.parse().unwrap();
+            if self.timestamped_msgs.contains(msg_name) {
+                quote!(UorbMessage::#name_ident(body) => Some(body.timestamp),)
+            } else {
+                quote!(UorbMessage::#name_ident(_) => None,)
+            }
+        });
+
+
+        let max_encoded_len: TokenStream = format!("{}", self.max_encoded_len).parse().unwrap();
 
         let enum_toks = quote!(
 
+        /// Largest ENCODED_LEN of all the known message definitions
+        pub const MAX_ENCODED_LEN: usize = #max_encoded_len;
+
 
+        /// A message of any known type. With the `serde` feature, it is externally
+        /// tagged by MSG_RAW_NAME.
         #[derive(Clone, PartialEq, Debug)]
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
         pub enum UorbMessage {
             #(#msg_enum_names)*
         }
 
         impl UorbMessage {
+            /// The MSG_HASH_CODE of every known message
+            pub const KNOWN_HASHES: &'static [u16] = &[
+                #(#msg_known_hashes_toks)*
+            ];
+
             pub fn parse(hash_val: u16, payload: &[u8]) -> Option<UorbMessage> {
                 match hash_val {
                 #(#msg_data_deser_toks)*
@@ -670,13 +1370,121 @@ impl Parser {
                 }
             }
 
+            /// The ENCODED_LEN of the message with the given hash, if it is known
+            pub fn encoded_len_for_hash(hash_val: u16) -> Option<usize> {
+                match hash_val {
+                #(#msg_encoded_len_for_hash_toks)*
+                _ => None
+                }
+            }
+
+            /// The field layout of the message with the given hash, if it is known
+            pub fn fields_for_hash(hash_val: u16) -> Option<&'static [FieldInfo]> {
+                match hash_val {
+                #(#msg_fields_for_hash_toks)*
+                _ => None
+                }
+            }
+
+            /// The MSG_RAW_NAME of the message with the given hash, if it is known
+            pub fn raw_name_for_hash(hash_val: u16) -> Option<&'static str> {
+                match hash_val {
+                #(#msg_raw_name_for_hash_toks)*
+                _ => None
+                }
+            }
+
+            /// The MSG_HASH_CODE of the message with the given raw name, such as `sensor_gyro`
+            pub fn hash_for_raw_name(raw_name: &str) -> Option<u16> {
+                match raw_name {
+                #(#msg_hash_for_raw_name_toks)*
+                _ => None
+                }
+            }
+
+            /// The CDR_ENCODED_LEN of the message with the given hash, if it is known
+            pub fn cdr_encoded_len_for_hash(hash_val: u16) -> Option<usize> {
+                match hash_val {
+                #(#msg_cdr_encoded_len_for_hash_toks)*
+                _ => None
+                }
+            }
+
+            /// Read the CDR fields of the message with the given hash
+            pub fn deser_cdr_body(hash_val: u16, r: &mut CdrReader) -> Option<UorbMessage> {
+                match hash_val {
+                #(#msg_deser_cdr_body_toks)*
+                _ => None
+                }
+            }
+
+            /// Write the inner data as CDR, without an encapsulation header
+            pub fn ser_cdr_body(&self, w: &mut CdrWriter) -> Option<()> {
+                match self {
+                 #(#msg_ser_cdr_body_toks)*
+                }
+            }
+
+            #[cfg(feature = "std")]
             pub fn ser(&self) -> Vec<u8> {
                 match self {
-                 #(&UorbMessage::#msg_map_deser_names(ref body) => body.ser(),)*
+                 #(#msg_ser_toks)*
+                }
+            }
+
+            /// serialize the inner data into the given buffer, without allocating
+            #[cfg(feature = "std")]
+            pub fn ser_into<B: BufMut>(&self, buf: &mut B) {
+                match self {
+                 #(#msg_ser_into_toks)*
+                }
+            }
+
+            /// serialize the inner data into the start of a byte slice,
+            /// returning the number of bytes written, or None if the slice is too short
+            pub fn ser_into_slice(&self, buf: &mut [u8]) -> Option<usize> {
+                match self {
+                 #(#msg_ser_into_slice_toks)*
                 }
             }
 
+            /// Encoded size of the inner data, in bytes
+            pub fn encoded_len(&self) -> usize {
+                match self {
+                 #(#msg_encoded_len_toks)*
+                }
+            }
+
+            /// The message's `timestamp` field, if its type has one
+            pub fn timestamp(&self) -> Option<u64> {
+                match self {
+                 #(#msg_timestamp_toks)*
+                }
+            }
+
+
+        }
 
+        /// Borrowed view of any known message payload, for dispatch without copying
+        #[derive(Clone, Copy, PartialEq, Debug)]
+        pub enum UorbMessageView<'a> {
+            #(#msg_view_enum_names)*
+        }
+
+        impl<'a> UorbMessageView<'a> {
+            pub fn parse(hash_val: u16, payload: &'a [u8]) -> Option<UorbMessageView<'a>> {
+                match hash_val {
+                #(#msg_view_parse_toks)*
+                _ => None
+                }
+            }
+
+            /// Copy the viewed payload out into the owned message type
+            pub fn to_owned(&self) -> UorbMessage {
+                match self {
+                 #(#msg_view_to_owned_toks)*
+                }
+            }
         }
         );
 
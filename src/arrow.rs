@@ -0,0 +1,337 @@
+//! Apache Arrow record batches and Parquet files, for querying with DuckDB or Polars
+//!
+//! Each message type has a schema of its own, with a column per field in encoded
+//! order and `timestamp` first, as in the `csv` module. Array fields become
+//! FixedSizeList columns, and chars become UInt8. Every column is non-nullable.
+//!
+//! `ParquetExporter` writes a directory partitioned Hive style, with one file per
+//! topic instance at `topic=<name>/instance=<id>/data.parquet`, so that
+//! `read_parquet('<dir>/**/*.parquet', hive_partitioning = true)` in DuckDB, or
+//! `scan_parquet` in Polars, sees the topic and instance as columns.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Read, Result, Seek};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, BooleanArray, FixedSizeListArray, PrimitiveArray, RecordBatch};
+use arrow_array::types::{Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type};
+use arrow_array::types::{UInt16Type, UInt32Type, UInt64Type, UInt8Type};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use crate::field::column_order;
+use crate::recording::{for_each_frame, for_each_recorded_frame};
+use crate::{FieldInfo, FieldType, FieldValue, UorbHeader, UorbMessage};
+
+
+/// Rows gathered for each topic instance before they are handed to Parquet
+pub const DEFAULT_BATCH_ROWS: usize = 65_536;
+
+fn invalid_input(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, msg.to_string())
+}
+
+/// The Arrow type of each element of a field
+pub fn element_type(field_type: FieldType) -> DataType {
+    use crate::FieldType::*;
+    match field_type {
+        Bool => DataType::Boolean,
+        Char | UInt8 => DataType::UInt8,
+        UInt16 => DataType::UInt16,
+        UInt32 => DataType::UInt32,
+        UInt64 => DataType::UInt64,
+        Int8 => DataType::Int8,
+        Int16 => DataType::Int16,
+        Int32 => DataType::Int32,
+        Int64 => DataType::Int64,
+        Float32 => DataType::Float32,
+        Float64 => DataType::Float64,
+    }
+}
+
+/// The Arrow field for a message field, with arrays as FixedSizeList
+pub fn arrow_field(field: &FieldInfo) -> Field {
+    let data_type = element_type(field.field_type);
+    match field.array_len {
+        Some(len) => Field::new(field.name, DataType::FixedSizeList(Arc::new(Field::new("item", data_type, false)), len as i32), false),
+        None => Field::new(field.name, data_type, false),
+    }
+}
+
+/// The schema of the message with this hash, or None if it is unknown. Its metadata
+/// holds the message's `uorb.name` and `uorb.hash`.
+pub fn schema(hash: u16) -> Option<SchemaRef> {
+    let fields = UorbMessage::fields_for_hash(hash)?;
+    let raw_name = UorbMessage::raw_name_for_hash(hash)?;
+    let metadata: HashMap<String, String> = vec![
+        ("uorb.name".to_string(), raw_name.to_string()),
+        ("uorb.hash".to_string(), hash.to_string()),
+    ].into_iter().collect();
+    let arrow_fields: Vec<Field> = column_order(fields).map(arrow_field).collect();
+    Some(Arc::new(Schema::new_with_metadata(arrow_fields, metadata)))
+}
+
+
+/// The values of one column gathered so far, flattened for array fields
+#[derive(Debug)]
+enum ColumnValues {
+    Bool(Vec<bool>),
+    UInt8(Vec<u8>),
+    UInt16(Vec<u16>),
+    UInt32(Vec<u32>),
+    UInt64(Vec<u64>),
+    Int8(Vec<i8>),
+    Int16(Vec<i16>),
+    Int32(Vec<i32>),
+    Int64(Vec<i64>),
+    Float32(Vec<f32>),
+    Float64(Vec<f64>),
+}
+
+impl ColumnValues {
+    fn new(field_type: FieldType) -> ColumnValues {
+        use crate::FieldType::*;
+        match field_type {
+            Bool => ColumnValues::Bool(Vec::new()),
+            Char | UInt8 => ColumnValues::UInt8(Vec::new()),
+            UInt16 => ColumnValues::UInt16(Vec::new()),
+            UInt32 => ColumnValues::UInt32(Vec::new()),
+            UInt64 => ColumnValues::UInt64(Vec::new()),
+            Int8 => ColumnValues::Int8(Vec::new()),
+            Int16 => ColumnValues::Int16(Vec::new()),
+            Int32 => ColumnValues::Int32(Vec::new()),
+            Int64 => ColumnValues::Int64(Vec::new()),
+            Float32 => ColumnValues::Float32(Vec::new()),
+            Float64 => ColumnValues::Float64(Vec::new()),
+        }
+    }
+
+    /// Add a value decoded for this column's field type, which always fits
+    fn push(&mut self, value: FieldValue) {
+        match (self, value) {
+            (ColumnValues::Bool(values), FieldValue::Bool(value)) => values.push(value),
+            (ColumnValues::UInt8(values), FieldValue::Char(value)) => values.push(value as u32 as u8),
+            (ColumnValues::UInt8(values), FieldValue::UInt(value)) => values.push(value as u8),
+            (ColumnValues::UInt16(values), FieldValue::UInt(value)) => values.push(value as u16),
+            (ColumnValues::UInt32(values), FieldValue::UInt(value)) => values.push(value as u32),
+            (ColumnValues::UInt64(values), FieldValue::UInt(value)) => values.push(value),
+            (ColumnValues::Int8(values), FieldValue::Int(value)) => values.push(value as i8),
+            (ColumnValues::Int16(values), FieldValue::Int(value)) => values.push(value as i16),
+            (ColumnValues::Int32(values), FieldValue::Int(value)) => values.push(value as i32),
+            (ColumnValues::Int64(values), FieldValue::Int(value)) => values.push(value),
+            (ColumnValues::Float32(values), FieldValue::Float32(value)) => values.push(value),
+            (ColumnValues::Float64(values), FieldValue::Float64(value)) => values.push(value),
+            (_, value) => unreachable!("{:?} does not match its column", value),
+        }
+    }
+
+    /// Take the values gathered so far as an array
+    fn take(&mut self) -> ArrayRef {
+        match self {
+            ColumnValues::Bool(values) => Arc::new(BooleanArray::from(mem::take(values))),
+            ColumnValues::UInt8(values) => Arc::new(PrimitiveArray::<UInt8Type>::from(mem::take(values))),
+            ColumnValues::UInt16(values) => Arc::new(PrimitiveArray::<UInt16Type>::from(mem::take(values))),
+            ColumnValues::UInt32(values) => Arc::new(PrimitiveArray::<UInt32Type>::from(mem::take(values))),
+            ColumnValues::UInt64(values) => Arc::new(PrimitiveArray::<UInt64Type>::from(mem::take(values))),
+            ColumnValues::Int8(values) => Arc::new(PrimitiveArray::<Int8Type>::from(mem::take(values))),
+            ColumnValues::Int16(values) => Arc::new(PrimitiveArray::<Int16Type>::from(mem::take(values))),
+            ColumnValues::Int32(values) => Arc::new(PrimitiveArray::<Int32Type>::from(mem::take(values))),
+            ColumnValues::Int64(values) => Arc::new(PrimitiveArray::<Int64Type>::from(mem::take(values))),
+            ColumnValues::Float32(values) => Arc::new(PrimitiveArray::<Float32Type>::from(mem::take(values))),
+            ColumnValues::Float64(values) => Arc::new(PrimitiveArray::<Float64Type>::from(mem::take(values))),
+        }
+    }
+}
+
+
+/// Gathers frames of one message type into record batches
+#[derive(Debug)]
+pub struct BatchBuilder {
+    hash: u16,
+    schema: SchemaRef,
+    columns: Vec<(FieldInfo, ColumnValues)>,
+    payload: Vec<u8>,
+    rows: usize,
+}
+
+impl BatchBuilder {
+    /// Gather frames of the message with this hash
+    pub fn new(hash: u16) -> Result<Self> {
+        let schema = schema(hash).ok_or_else(|| invalid_input("unknown message hash"))?;
+        let columns = column_order(UorbMessage::fields_for_hash(hash).unwrap_or(&[]))
+            .map(|field| (*field, ColumnValues::new(field.field_type)))
+            .collect();
+        Ok(BatchBuilder {
+            hash,
+            schema,
+            columns,
+            payload: vec![0; UorbMessage::encoded_len_for_hash(hash).unwrap_or(0)],
+            rows: 0,
+        })
+    }
+
+    pub fn hash(&self) -> u16 {
+        self.hash
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Number of rows gathered since the last batch
+    pub fn len(&self) -> usize {
+        self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    /// Add one frame as a row. The header must carry this builder's message.
+    pub fn append(&mut self, header: &UorbHeader, msg: &UorbMessage) -> Result<()> {
+        if header.hash != self.hash || msg.encoded_len() != self.payload.len() {
+            return Err(invalid_input("message does not match the record batch"));
+        }
+        msg.ser_into_slice(&mut self.payload);
+        for (field, values) in &mut self.columns {
+            for value in field.values(&self.payload) {
+                values.push(value);
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Take the rows gathered so far as a record batch
+    pub fn finish(&mut self) -> Result<RecordBatch> {
+        let arrays = self.columns.iter_mut()
+            .map(|(field, values)| {
+                let array = values.take();
+                match field.array_len {
+                    Some(len) => {
+                        let item = Arc::new(Field::new("item", element_type(field.field_type), false));
+                        let list = FixedSizeListArray::try_new(item, len as i32, array, None).map_err(Error::other)?;
+                        Ok(Arc::new(list) as ArrayRef)
+                    }
+                    None => Ok(array),
+                }
+            })
+            .collect::<Result<Vec<ArrayRef>>>()?;
+        self.rows = 0;
+        RecordBatch::try_new(self.schema.clone(), arrays).map_err(Error::other)
+    }
+}
+
+
+/// A Parquet file being written for one topic instance
+#[derive(Debug)]
+struct TopicFile {
+    path: PathBuf,
+    builder: BatchBuilder,
+    writer: ArrowWriter<File>,
+}
+
+impl TopicFile {
+    fn write_batch(&mut self) -> Result<()> {
+        let batch = self.builder.finish()?;
+        self.writer.write(&batch).map_err(Error::other)
+    }
+}
+
+/// Writes frames from any source to a directory of Parquet files, partitioned by
+/// topic and instance.
+///
+/// Rows are written out in batches of `batch_rows`. Files are created as their
+/// first frame arrives, replacing any at the same path, and are only complete
+/// once `finish` has written their footers.
+#[derive(Debug)]
+pub struct ParquetExporter {
+    dir: PathBuf,
+    batch_rows: usize,
+    properties: WriterProperties,
+    topics: BTreeMap<(u16, u8), TopicFile>,
+}
+
+impl ParquetExporter {
+    /// Export to `dir`, which is created if it does not exist. Files are compressed
+    /// with Snappy.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(ParquetExporter {
+            dir: dir.as_ref().to_path_buf(),
+            batch_rows: DEFAULT_BATCH_ROWS,
+            properties: WriterProperties::builder().set_compression(Compression::SNAPPY).build(),
+            topics: BTreeMap::new(),
+        })
+    }
+
+    pub fn set_batch_rows(&mut self, batch_rows: usize) {
+        self.batch_rows = batch_rows.max(1);
+    }
+
+    /// Writer properties for the files created from now on
+    pub fn set_properties(&mut self, properties: WriterProperties) {
+        self.properties = properties;
+    }
+
+    /// The path of the file for one topic instance, relative to the export directory
+    pub fn partition_path(raw_name: &str, instance_id: u8) -> PathBuf {
+        Path::new(&format!("topic={}", raw_name)).join(format!("instance={}", instance_id)).join("data.parquet")
+    }
+
+    /// Add one frame to the file for its topic and instance
+    pub fn write_msg(&mut self, header: &UorbHeader, msg: &UorbMessage) -> Result<()> {
+        let key = (header.hash, header.instance_id);
+        if !self.topics.contains_key(&key) {
+            let raw_name = UorbMessage::raw_name_for_hash(header.hash)
+                .ok_or_else(|| invalid_input("unknown message hash"))?;
+            let path = self.dir.join(Self::partition_path(raw_name, header.instance_id));
+            fs::create_dir_all(path.parent().unwrap_or(&self.dir))?;
+            let builder = BatchBuilder::new(header.hash)?;
+            let writer = ArrowWriter::try_new(File::create(&path)?, builder.schema(), Some(self.properties.clone()))
+                .map_err(Error::other)?;
+            self.topics.insert(key, TopicFile { path, builder, writer });
+        }
+        let topic = self.topics.get_mut(&key).unwrap();
+        topic.builder.append(header, msg)?;
+        if topic.builder.len() >= self.batch_rows {
+            topic.write_batch()?;
+        }
+        Ok(())
+    }
+
+    /// Export frames read with `read_msg` until the source ends. Returns the
+    /// number of frames exported.
+    pub fn export_stream<R: Read>(&mut self, r: &mut R) -> Result<u64> {
+        for_each_frame(r, |header, msg| self.write_msg(header, msg))
+    }
+
+    /// Export every frame of a recording, without waiting out its timing. Returns
+    /// the number of frames exported.
+    pub fn export_recording<R: Read + Seek>(&mut self, reader: R) -> Result<u64> {
+        for_each_recorded_frame(reader, |header, msg| self.write_msg(header, msg))
+    }
+
+    /// Paths of the files created so far, by message hash and instance
+    pub fn files(&self) -> Vec<&Path> {
+        self.topics.values().map(|topic| topic.path.as_path()).collect()
+    }
+
+    /// Write the remaining rows and the footer of every file, and return their paths
+    pub fn finish(self) -> Result<Vec<PathBuf>> {
+        self.topics.into_values()
+            .map(|mut topic| {
+                if !topic.builder.is_empty() {
+                    topic.write_batch()?;
+                }
+                topic.writer.close().map_err(Error::other)?;
+                Ok(topic.path)
+            })
+            .collect()
+    }
+}
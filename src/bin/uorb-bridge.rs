@@ -0,0 +1,74 @@
+//! Relay uORB frames from an upstream source to every connected TCP client.
+//!
+//! usage: uorb-bridge <listen-addr> [<upstream-addr> | -] [--queue-len N] [--drop-oldest]
+//!
+//! Frames are read from a TCP connection to `upstream-addr`, or from stdin when
+//! it is `-` or omitted. Clients may send big-endian MSG_HASH_CODE values to
+//! receive only those messages.
+
+extern crate uorb_codec;
+
+use std::io::Result;
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::thread;
+
+use uorb_codec::bridge::{Bridge, DropPolicy};
+
+const DEFAULT_QUEUE_LEN: usize = 64;
+
+fn usage() -> ! {
+    eprintln!("usage: uorb-bridge <listen-addr> [<upstream-addr> | -] [--queue-len N] [--drop-oldest]");
+    process::exit(2)
+}
+
+fn run() -> Result<()> {
+    let mut positional = Vec::new();
+    let mut queue_len = DEFAULT_QUEUE_LEN;
+    let mut drop_policy = DropPolicy::DropNewest;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--queue-len" => {
+                queue_len = args.next().and_then(|len| len.parse().ok()).unwrap_or_else(|| usage());
+            }
+            "--drop-oldest" => drop_policy = DropPolicy::DropOldest,
+            "-h" | "--help" => usage(),
+            _ => positional.push(arg),
+        }
+    }
+    if positional.is_empty() || positional.len() > 2 {
+        usage();
+    }
+
+    let bridge = Bridge::new(queue_len, drop_policy);
+    let listener = TcpListener::bind(positional[0].as_str())?;
+    eprintln!("uorb-bridge: listening on {}", listener.local_addr()?);
+    let server = bridge.serve_with(listener, |err| eprintln!("uorb-bridge: client not served: {}", err));
+    // a bridge that can no longer accept clients is of no use
+    thread::spawn(move || {
+        match server.join() {
+            Ok(Err(err)) => eprintln!("uorb-bridge: listener failed: {}", err),
+            _ => eprintln!("uorb-bridge: listener stopped"),
+        }
+        process::exit(1);
+    });
+
+    match positional.get(1).map(String::as_str) {
+        None | Some("-") => bridge.run(std::io::stdin().lock())?,
+        Some(addr) => bridge.run(TcpStream::connect(addr)?)?,
+    }
+    eprintln!("uorb-bridge: upstream closed");
+    bridge.shutdown();
+    if bridge.dropped() > 0 {
+        eprintln!("uorb-bridge: dropped {} frames for slow clients", bridge.dropped());
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("uorb-bridge: {}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,300 @@
+//! Print uORB frames from a file, stdin, or a TCP or UDP endpoint.
+//!
+//! usage: uorb-dump [<source>] [--json] [--stats] [--name NAME]... [--hash HASH]... [--instance N]...
+//!
+//! The source is a file path, `-` for stdin (the default), `tcp:<addr>` to connect
+//! to a TCP server, or `udp:<addr>` to receive datagrams on a local address.
+//! Each frame is printed as text, or as a JSON line with `--json`. With `--stats`,
+//! a table of frame counts and rates per topic instance is printed instead, once
+//! a second and at the end of the input. Filters of one kind match any of their
+//! values, and filters of different kinds must all match.
+
+extern crate uorb_codec;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Result, Write};
+use std::net::TcpStream;
+use std::process;
+use std::time::{Duration, Instant};
+
+use uorb_codec::{read_msg, FieldValue, UdpTransport, UorbHeader, UorbMessage};
+
+const STATS_PERIOD: Duration = Duration::from_secs(1);
+
+fn usage() -> ! {
+    eprintln!("usage: uorb-dump [<file> | - | tcp:<addr> | udp:<addr>] [--json] [--stats] \
+               [--name NAME]... [--hash HASH]... [--instance N]...");
+    process::exit(2)
+}
+
+
+enum Source {
+    Stream(Box<dyn Read>),
+    Udp(UdpTransport),
+}
+
+impl Source {
+    fn open(spec: &str) -> Result<Source> {
+        let source = if spec == "-" {
+            Source::Stream(Box::new(io::stdin()))
+        } else if let Some(addr) = spec.strip_prefix("tcp:") {
+            Source::Stream(Box::new(BufReader::new(TcpStream::connect(addr)?)))
+        } else if let Some(addr) = spec.strip_prefix("udp:") {
+            Source::Udp(UdpTransport::bind(addr)?)
+        } else {
+            Source::Stream(Box::new(BufReader::new(File::open(spec)?)))
+        };
+        Ok(source)
+    }
+
+    /// The next frame, or None at the end of the input. Frames that cannot be
+    /// decoded are counted in `errors` and skipped.
+    fn next(&mut self, errors: &mut u64) -> Result<Option<(UorbHeader, UorbMessage)>> {
+        loop {
+            let res = match self {
+                Source::Stream(r) => match read_msg(r) {
+                    Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                    res => res,
+                },
+                Source::Udp(udp) => udp.recv_msg().map(|(_from, header, msg)| (header, msg)),
+            };
+            match res {
+                Ok(frame) => return Ok(Some(frame)),
+                Err(err) if is_frame_error(&err) => *errors += 1,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+fn is_frame_error(err: &io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::InvalidData | ErrorKind::InvalidInput | ErrorKind::UnexpectedEof)
+}
+
+
+#[derive(Default)]
+struct Filter {
+    names: Vec<String>,
+    hashes: Vec<u16>,
+    instances: Vec<u8>,
+}
+
+impl Filter {
+    fn matches(&self, header: &UorbHeader) -> bool {
+        let name = UorbMessage::raw_name_for_hash(header.hash).unwrap_or("");
+        (self.names.is_empty() || self.names.iter().any(|filter| filter == name))
+            && (self.hashes.is_empty() || self.hashes.contains(&header.hash))
+            && (self.instances.is_empty() || self.instances.contains(&header.instance_id))
+    }
+}
+
+fn parse_hash(arg: &str) -> Option<u16> {
+    match arg.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => arg.parse().ok(),
+    }
+}
+
+
+/// The elements of each field of a message, in encoded order
+fn field_values(header: &UorbHeader, msg: &UorbMessage) -> Vec<(&'static str, bool, Vec<FieldValue>)> {
+    let mut payload = vec![0; msg.encoded_len()];
+    msg.ser_into_slice(&mut payload);
+    UorbMessage::fields_for_hash(header.hash).unwrap_or(&[]).iter()
+        .map(|field| (field.name, field.array_len.is_some(), field.values(&payload).collect()))
+        .collect()
+}
+
+/// A char array as the string it holds, up to the first NUL
+fn char_string(values: &[FieldValue]) -> Option<String> {
+    values.iter()
+        .map(|value| match value {
+            FieldValue::Char(c) => Some(*c),
+            _ => None,
+        })
+        .take_while(|c| *c != Some('\0'))
+        .collect()
+}
+
+fn print_text<W: Write>(out: &mut W, header: &UorbHeader, msg: &UorbMessage) -> Result<()> {
+    let name = UorbMessage::raw_name_for_hash(header.hash).unwrap_or("?");
+    writeln!(out, "{} {} (hash 0x{:04x}) timestamp {}", name, header.instance_id, header.hash, header.timestamp)?;
+    for (field, is_array, values) in field_values(header, msg) {
+        write!(out, "    {}: ", field)?;
+        if let (true, Some(text)) = (is_array, char_string(&values)) {
+            writeln!(out, "{:?}", text)?;
+        } else if is_array {
+            let elems: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+            writeln!(out, "[{}]", elems.join(", "))?;
+        } else if let Some(value) = values.first() {
+            writeln!(out, "{}", value)?;
+        } else {
+            writeln!(out)?;
+        }
+    }
+    writeln!(out)
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 || c as u32 > 0x7e => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A value as JSON, with non-finite floats as null
+fn json_value(value: &FieldValue) -> String {
+    match value {
+        FieldValue::Char(c) => json_string(&c.to_string()),
+        FieldValue::Float32(v) if !v.is_finite() => "null".to_string(),
+        FieldValue::Float64(v) if !v.is_finite() => "null".to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn print_json<W: Write>(out: &mut W, header: &UorbHeader, msg: &UorbMessage) -> Result<()> {
+    let name = UorbMessage::raw_name_for_hash(header.hash).unwrap_or("");
+    let fields: Vec<String> = field_values(header, msg).into_iter()
+        .map(|(field, is_array, values)| {
+            let value = match (is_array, char_string(&values)) {
+                (true, Some(text)) => json_string(&text),
+                (true, None) => format!("[{}]", values.iter().map(json_value).collect::<Vec<_>>().join(",")),
+                (false, _) => values.first().map_or("null".to_string(), json_value),
+            };
+            format!("{}:{}", json_string(field), value)
+        })
+        .collect();
+    writeln!(out, "{{\"name\":{},\"instance\":{},\"hash\":{},\"timestamp\":{},\"fields\":{{{}}}}}",
+             json_string(name), header.instance_id, header.hash, header.timestamp, fields.join(","))
+}
+
+
+#[derive(Default)]
+struct TopicStats {
+    hash: u16,
+    frames: u64,
+    bytes: u64,
+    first_timestamp: u64,
+    last_timestamp: u64,
+}
+
+impl TopicStats {
+    /// Frames per second by header timestamps
+    fn rate(&self) -> f64 {
+        let span = self.last_timestamp.saturating_sub(self.first_timestamp);
+        if self.frames < 2 || span == 0 {
+            return 0.0;
+        }
+        (self.frames - 1) as f64 * 1e6 / span as f64
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    topics: BTreeMap<(&'static str, u8), TopicStats>,
+    errors: u64,
+}
+
+impl Stats {
+    fn add(&mut self, header: &UorbHeader) {
+        let name = UorbMessage::raw_name_for_hash(header.hash).unwrap_or("?");
+        let topic = self.topics.entry((name, header.instance_id)).or_default();
+        if topic.frames == 0 {
+            topic.hash = header.hash;
+            topic.first_timestamp = header.timestamp;
+        }
+        topic.frames += 1;
+        topic.bytes += (uorb_codec::UORB_HEADER_LEN + header.payload_len) as u64;
+        topic.last_timestamp = header.timestamp;
+    }
+
+    fn print<W: Write>(&self, out: &mut W, json: bool) -> Result<()> {
+        if json {
+            for ((name, instance), topic) in &self.topics {
+                writeln!(out, "{{\"name\":{},\"instance\":{},\"hash\":{},\"frames\":{},\"bytes\":{},\"rate_hz\":{:.2}}}",
+                         json_string(name), instance, topic.hash, topic.frames, topic.bytes, topic.rate())?;
+            }
+            writeln!(out, "{{\"errors\":{}}}", self.errors)?;
+        } else {
+            writeln!(out, "{:<28} {:>4} {:>6} {:>10} {:>12} {:>10}", "topic", "inst", "hash", "frames", "bytes", "rate (Hz)")?;
+            for ((name, instance), topic) in &self.topics {
+                writeln!(out, "{:<28} {:>4} {:>6} {:>10} {:>12} {:>10.2}",
+                         name, instance, format!("0x{:04x}", topic.hash), topic.frames, topic.bytes, topic.rate())?;
+            }
+            writeln!(out, "{} frames could not be decoded", self.errors)?;
+            writeln!(out)?;
+        }
+        out.flush()
+    }
+}
+
+
+fn run() -> Result<()> {
+    let mut source_spec = None;
+    let mut json = false;
+    let mut stats_mode = false;
+    let mut filter = Filter::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--stats" => stats_mode = true,
+            "--name" => filter.names.push(args.next().unwrap_or_else(|| usage())),
+            "--hash" => filter.hashes.push(args.next().and_then(|hash| parse_hash(&hash)).unwrap_or_else(|| usage())),
+            "--instance" => {
+                filter.instances.push(args.next().and_then(|instance| instance.parse().ok()).unwrap_or_else(|| usage()));
+            }
+            "-h" | "--help" => usage(),
+            _ if source_spec.is_none() && (arg == "-" || !arg.starts_with('-')) => source_spec = Some(arg),
+            _ => usage(),
+        }
+    }
+
+    let mut source = Source::open(source_spec.as_deref().unwrap_or("-"))?;
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut stats = Stats::default();
+    let mut last_stats = Instant::now();
+
+    while let Some((header, msg)) = source.next(&mut stats.errors)? {
+        if !filter.matches(&header) {
+            continue;
+        }
+        if stats_mode {
+            stats.add(&header);
+            if last_stats.elapsed() >= STATS_PERIOD {
+                stats.print(&mut out, json)?;
+                last_stats = Instant::now();
+            }
+        } else if json {
+            print_json(&mut out, &header, &msg)?;
+        } else {
+            print_text(&mut out, &header, &msg)?;
+        }
+    }
+    if stats_mode {
+        stats.print(&mut out, json)?;
+    }
+    out.flush()
+}
+
+fn main() {
+    match run() {
+        Ok(()) => {}
+        // the reader went away, as with `uorb-dump log.bin | head`
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => {}
+        Err(err) => {
+            eprintln!("uorb-dump: {}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! Fanning out uORB frames from one upstream source to many TCP clients
+
+use std::collections::{HashSet, VecDeque};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{read_msg, UorbHeader, UorbMessage, MAX_FRAME_LEN};
+
+
+/// How long `shutdown` waits for clients to be sent their queued frames
+pub const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(1);
+
+/// Longest pause after repeated accept errors, such as running out of file descriptors
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+
+/// Which messages a client receives, by MSG_HASH_CODE
+#[derive(Debug, Clone, PartialEq)]
+pub enum TopicFilter {
+    All,
+    Only(HashSet<u16>),
+}
+
+impl TopicFilter {
+    pub fn matches(&self, hash: u16) -> bool {
+        match self {
+            TopicFilter::All => true,
+            TopicFilter::Only(hashes) => hashes.contains(&hash),
+        }
+    }
+
+    /// Add `hash` to the messages received, narrowing `All` to just that message
+    pub fn subscribe(&mut self, hash: u16) {
+        match self {
+            TopicFilter::All => *self = TopicFilter::Only(Some(hash).into_iter().collect()),
+            TopicFilter::Only(hashes) => { hashes.insert(hash); }
+        }
+    }
+}
+
+
+/// Which frame to discard when a client's queue is full
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DropPolicy {
+    /// Keep the queued frames and discard the new one
+    DropNewest,
+    /// Discard the oldest queued frame to make room for the new one
+    DropOldest,
+}
+
+
+#[derive(Debug, Default)]
+struct QueueState {
+    frames: VecDeque<Arc<Vec<u8>>>,
+    dropped: u64,
+    closed: bool,
+}
+
+/// Bounded queue of encoded frames waiting to be written to one client
+#[derive(Debug)]
+struct ClientQueue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+}
+
+impl ClientQueue {
+    fn push(&self, frame: &Arc<Vec<u8>>, capacity: usize, policy: DropPolicy) {
+        let mut state = self.state.lock().unwrap();
+        if state.frames.len() >= capacity {
+            state.dropped += 1;
+            match policy {
+                DropPolicy::DropNewest => return,
+                DropPolicy::DropOldest => { state.frames.pop_front(); }
+            }
+        }
+        state.frames.push_back(frame.clone());
+        self.ready.notify_one();
+    }
+
+    /// Wait for the next frame, or None once the queue is closed and drained
+    fn pop(&self) -> Option<Arc<Vec<u8>>> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(frame) = state.frames.pop_front() {
+                return Some(frame);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.ready.wait(state).unwrap();
+        }
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.ready.notify_all();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+}
+
+#[derive(Debug)]
+struct Client {
+    queue: Arc<ClientQueue>,
+    filter: Arc<Mutex<TopicFilter>>,
+    writer: Option<JoinHandle<()>>,
+    /// Shut down to disconnect a TCP client, unblocking its writer and request reader
+    stream: Option<Arc<TcpStream>>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    queue_len: usize,
+    drop_policy: DropPolicy,
+    clients: Mutex<Vec<Client>>,
+    dropped_total: Mutex<u64>,
+}
+
+
+/// Forwards every frame it is given to all connected clients.
+///
+/// Each client is served by its own writer thread from a bounded queue, so a
+/// slow client only loses its own frames, according to the drop policy,
+/// and never stalls the others. Clones share the same set of clients.
+#[derive(Debug, Clone)]
+pub struct Bridge {
+    shared: Arc<Shared>,
+}
+
+impl Bridge {
+    /// Queue up to `queue_len` frames per client before dropping according to `drop_policy`
+    pub fn new(queue_len: usize, drop_policy: DropPolicy) -> Self {
+        Bridge {
+            shared: Arc::new(Shared {
+                queue_len: queue_len.max(1),
+                drop_policy,
+                clients: Mutex::new(Vec::new()),
+                dropped_total: Mutex::new(0),
+            }),
+        }
+    }
+
+    /// Start forwarding frames matching `filter` to `writer`
+    pub fn add_client<W: Write + Send + 'static>(&self, writer: W, filter: TopicFilter) {
+        self.add_client_with_filter(writer, Arc::new(Mutex::new(filter)), None);
+    }
+
+    fn add_client_with_filter<W: Write + Send + 'static>(&self, mut writer: W,
+                                                          filter: Arc<Mutex<TopicFilter>>,
+                                                          stream: Option<Arc<TcpStream>>) {
+        let queue = Arc::new(ClientQueue {
+            state: Mutex::new(QueueState::default()),
+            ready: Condvar::new(),
+        });
+        let writer_queue = queue.clone();
+        let writer_stream = stream.clone();
+        let handle = thread::spawn(move || {
+            while let Some(frame) = writer_queue.pop() {
+                if writer.write_all(&frame).is_err() {
+                    break;
+                }
+            }
+            // disconnected, or closed and drained
+            writer_queue.close();
+            if let Some(stream) = writer_stream {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        });
+        self.shared.clients.lock().unwrap().push(Client { queue, filter, writer: Some(handle), stream });
+    }
+
+    /// Serve a TCP client. With no requests from the client, it receives every message;
+    /// each big-endian MSG_HASH_CODE it sends subscribes it to just the messages requested.
+    pub fn add_tcp_client(&self, stream: TcpStream) -> Result<()> {
+        stream.set_nodelay(true)?;
+        let mut requests = stream.try_clone()?;
+        let control = stream.try_clone()?;
+        let filter = Arc::new(Mutex::new(TopicFilter::All));
+        let request_filter = filter.clone();
+        thread::spawn(move || {
+            while let Ok(hash) = requests.read_u16::<BigEndian>() {
+                request_filter.lock().unwrap().subscribe(hash);
+            }
+        });
+        self.add_client_with_filter(stream, filter, Some(Arc::new(control)));
+        Ok(())
+    }
+
+    /// Accept TCP clients on a background thread, ignoring failed connections.
+    /// See `serve_with`.
+    pub fn serve(&self, listener: TcpListener) -> JoinHandle<Result<()>> {
+        self.serve_with(listener, |_| {})
+    }
+
+    /// Accept TCP clients on a background thread, passing each error that only affects
+    /// one connection to `on_error` and carrying on. Accept errors such as running out of
+    /// file descriptors are retried after a pause. The thread only ends, with the error,
+    /// when the listener itself is unusable.
+    pub fn serve_with<F>(&self, listener: TcpListener, mut on_error: F) -> JoinHandle<Result<()>>
+        where F: FnMut(&Error) + Send + 'static {
+        let bridge = self.clone();
+        thread::spawn(move || {
+            let mut backoff = Duration::from_millis(0);
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        backoff = Duration::from_millis(0);
+                        if let Err(err) = bridge.add_tcp_client(stream) {
+                            on_error(&err);
+                        }
+                    }
+                    Err(err) if is_listener_error(&err) => return Err(err),
+                    Err(err) => {
+                        on_error(&err);
+                        if !is_connection_error(&err) {
+                            backoff = (backoff * 2).clamp(Duration::from_millis(10), MAX_ACCEPT_BACKOFF);
+                            thread::sleep(backoff);
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Queue a frame for every client whose filter matches it
+    pub fn forward(&self, header: &UorbHeader, data: &UorbMessage) -> Result<()> {
+        let mut frame = [0; MAX_FRAME_LEN];
+        let frame_len = data.encode_frame_into(header, &mut frame)?;
+        let frame = Arc::new(frame[..frame_len].to_vec());
+
+        let mut clients = self.shared.clients.lock().unwrap();
+        let mut dropped_total = self.shared.dropped_total.lock().unwrap();
+        clients.retain(|client| {
+            // keep the drop count of disconnected clients
+            let state = client.queue.state.lock().unwrap();
+            if state.closed {
+                *dropped_total += state.dropped;
+            }
+            !state.closed
+        });
+        for client in clients.iter() {
+            if client.filter.lock().unwrap().matches(header.hash) {
+                client.queue.push(&frame, self.shared.queue_len, self.shared.drop_policy);
+            }
+        }
+        Ok(())
+    }
+
+    /// Forward every frame read from `upstream` until it ends.
+    /// Frames that cannot be decoded are skipped.
+    pub fn run<R: Read>(&self, mut upstream: R) -> Result<()> {
+        loop {
+            match read_msg(&mut upstream) {
+                Ok((header, msg)) => self.forward(&header, &msg)?,
+                Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                Err(ref err) if err.kind() == ErrorKind::InvalidData
+                    || err.kind() == ErrorKind::InvalidInput => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Number of clients still connected
+    pub fn client_count(&self) -> usize {
+        self.shared.clients.lock().unwrap().iter()
+            .filter(|client| !client.queue.is_closed())
+            .count()
+    }
+
+    /// Number of frames dropped from full queues, across all clients past and present
+    pub fn dropped(&self) -> u64 {
+        let clients = self.shared.clients.lock().unwrap();
+        let current: u64 = clients.iter().map(|client| client.queue.state.lock().unwrap().dropped).sum();
+        *self.shared.dropped_total.lock().unwrap() + current
+    }
+
+    /// Stop accepting frames, and wait up to `DEFAULT_SHUTDOWN_GRACE` for every client
+    /// to be sent the frames already queued. See `shutdown_within`.
+    pub fn shutdown(&self) {
+        self.shutdown_within(DEFAULT_SHUTDOWN_GRACE);
+    }
+
+    /// Stop accepting frames, and wait up to `grace` for every client to be sent the
+    /// frames already queued. All TCP clients are then disconnected, including any
+    /// still being written to; other writers still blocked are left to finish on their own.
+    pub fn shutdown_within(&self, grace: Duration) {
+        let clients: Vec<Client> = self.shared.clients.lock().unwrap().drain(..).collect();
+        for client in &clients {
+            client.queue.close();
+        }
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline && clients.iter().any(|client| client.is_writing()) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        for mut client in clients {
+            if let Some(stream) = &client.stream {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+            if let Some(writer) = client.writer.take() {
+                // a blocked writer that is not a TCP stream cannot be interrupted
+                if client.stream.is_some() || writer.is_finished() {
+                    let _ = writer.join();
+                }
+            }
+            *self.shared.dropped_total.lock().unwrap() += client.queue.state.lock().unwrap().dropped;
+        }
+    }
+}
+
+impl Client {
+    fn is_writing(&self) -> bool {
+        self.writer.as_ref().is_some_and(|writer| !writer.is_finished())
+    }
+}
+
+/// Accept errors that come from one failed connection rather than the listener
+fn is_connection_error(err: &Error) -> bool {
+    matches!(err.kind(), ErrorKind::ConnectionAborted | ErrorKind::ConnectionReset
+        | ErrorKind::ConnectionRefused | ErrorKind::Interrupted | ErrorKind::WouldBlock
+        | ErrorKind::TimedOut)
+}
+
+/// Accept errors that mean the listener cannot be used again
+fn is_listener_error(err: &Error) -> bool {
+    matches!(err.kind(), ErrorKind::InvalidInput | ErrorKind::NotConnected | ErrorKind::Unsupported)
+}
@@ -0,0 +1,333 @@
+//! An in-process publish/subscribe bus modeled on uORB.
+//!
+//! Topics are keyed by MSG_HASH_CODE and instance ID, and hold only the latest
+//! publication, as on the vehicle. Publications are stored as encoded payloads,
+//! so they can be written with `write_msg` to bridge the bus to a vehicle.
+//! A subscriber with an interval set is instead fed every publication through an
+//! interval filter, so that it can be given the first or the latest of each interval.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::interval::{Interval, IntervalMode};
+use crate::{UorbHeader, UorbMessage, UorbMsgMeta};
+
+
+#[derive(Debug, Default)]
+struct Topic {
+    /// Header and payload of the latest publication, if any
+    latest: Option<(UorbHeader, Vec<u8>)>,
+    /// Number of publications so far
+    generation: u64,
+    /// Bus-wide sequence number of the latest publication
+    seq: u64,
+    advertised: bool,
+    /// Interval filters of subscribers with an interval set, by subscription key
+    throttles: HashMap<u64, Throttle>,
+}
+
+/// The publications delivered to one subscriber with an interval set
+#[derive(Debug, Clone)]
+struct Throttle {
+    interval: Interval<Vec<u8>>,
+    /// Header and payload of the publication delivered last, if any
+    delivered: Option<(UorbHeader, Vec<u8>)>,
+    /// Whether it has been delivered since the subscriber last copied
+    fresh: bool,
+}
+
+#[derive(Debug, Default)]
+struct BusState {
+    topics: HashMap<(u16, u8), Topic>,
+    seq: u64,
+    /// Last subscription key handed out
+    throttle_key: u64,
+}
+
+impl BusState {
+    fn is_advertised(&self, hash: u16, instance: u8) -> bool {
+        self.topics.get(&(hash, instance)).is_some_and(|topic| topic.advertised)
+    }
+
+    fn publish(&mut self, header: UorbHeader, payload: Vec<u8>) {
+        self.seq += 1;
+        let seq = self.seq;
+        let topic = self.topics.entry((header.hash, header.instance_id)).or_default();
+        for throttle in topic.throttles.values_mut() {
+            if let Some(delivered) = throttle.interval.push(header.clone(), payload.clone()) {
+                throttle.delivered = Some(delivered);
+                throttle.fresh = true;
+            }
+        }
+        topic.advertised = true;
+        topic.latest = Some((header, payload));
+        topic.generation += 1;
+        topic.seq = seq;
+    }
+
+    fn next_throttle_key(&mut self) -> u64 {
+        self.throttle_key += 1;
+        self.throttle_key
+    }
+
+    /// Remove the interval filter of a subscription, wherever it is
+    fn remove_throttle(&mut self, key: u64) -> Option<Throttle> {
+        self.topics.values_mut().find_map(|topic| topic.throttles.remove(&key))
+    }
+}
+
+
+/// Shared handle to a bus; clones refer to the same topics
+#[derive(Debug, Clone)]
+pub struct Bus {
+    state: Arc<Mutex<BusState>>,
+    start: Instant,
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        Bus { state: Arc::new(Mutex::new(BusState::default())), start: Instant::now() }
+    }
+
+    /// Microseconds since the bus was created, used to timestamp publications
+    pub fn now(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+
+    /// Advertise instance `instance` of topic `T`
+    pub fn advertise<T: UorbMsgMeta>(&self, instance: u8) -> Publisher<T> {
+        let mut state = self.state.lock().unwrap();
+        state.topics.entry((T::MSG_HASH_CODE, instance)).or_default().advertised = true;
+        Publisher { bus: self.clone(), instance, _msg: PhantomData }
+    }
+
+    /// Advertise the lowest instance of topic `T` not yet advertised,
+    /// as `orb_advertise_multi` does
+    pub fn advertise_next<T: UorbMsgMeta>(&self) -> Option<Publisher<T>> {
+        let instance = {
+            let state = self.state.lock().unwrap();
+            (0..=u8::MAX).find(|instance| !state.is_advertised(T::MSG_HASH_CODE, *instance))?
+        };
+        Some(self.advertise(instance))
+    }
+
+    /// Subscribe to instance `instance` of topic `T`, which need not be advertised yet
+    pub fn subscribe<T: UorbMsgMeta>(&self, instance: u8) -> Subscriber<T> {
+        Subscriber { bus: self.clone(), instance, generation: 0, throttle: None, _msg: PhantomData }
+    }
+
+    /// Receive the latest publication of every topic
+    pub fn subscribe_all(&self) -> BusSubscriber {
+        BusSubscriber { bus: self.clone(), seq: 0 }
+    }
+
+    /// Whether instance `instance` of topic `T` has been advertised
+    pub fn exists<T: UorbMsgMeta>(&self, instance: u8) -> bool {
+        self.state.lock().unwrap().is_advertised(T::MSG_HASH_CODE, instance)
+    }
+
+    /// Number of advertised instances of topic `T`, as `orb_group_count` reports
+    pub fn group_count<T: UorbMsgMeta>(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.topics.iter()
+            .filter(|((hash, _), topic)| *hash == T::MSG_HASH_CODE && topic.advertised)
+            .count()
+    }
+
+    /// Publish a message received from elsewhere, such as with `read_msg`,
+    /// on the topic instance named by its header
+    pub fn publish_msg(&self, header: &UorbHeader, data: &UorbMessage) {
+        let mut payload = vec![0; data.encoded_len()];
+        data.ser_into_slice(&mut payload);
+        let mut header = header.clone();
+        header.payload_len = payload.len();
+        self.state.lock().unwrap().publish(header, payload);
+    }
+}
+
+
+/// Publishes to one instance of topic `T`
+#[derive(Debug, Clone)]
+pub struct Publisher<T> {
+    bus: Bus,
+    instance: u8,
+    _msg: PhantomData<T>,
+}
+
+impl<T: UorbMsgMeta> Publisher<T> {
+    pub fn instance(&self) -> u8 {
+        self.instance
+    }
+
+    /// Publish `data` stamped with the bus clock, returning the header and message
+    /// ready to be passed to `write_msg`
+    pub fn publish(&self, data: &T) -> (UorbHeader, UorbMessage) {
+        self.publish_at(data, self.bus.now())
+    }
+
+    /// Publish `data` with the given header timestamp
+    pub fn publish_at(&self, data: &T, timestamp: u64) -> (UorbHeader, UorbMessage) {
+        let mut payload = vec![0; T::ENCODED_LEN];
+        data.ser_into_slice(&mut payload);
+        let header = data.header_for_instance(self.instance, timestamp);
+        self.bus.state.lock().unwrap().publish(header.clone(), payload);
+        (header, data.wrap())
+    }
+}
+
+
+/// Reads the latest publication on one instance of topic `T`
+#[derive(Debug)]
+pub struct Subscriber<T> {
+    bus: Bus,
+    instance: u8,
+    /// Generation of the last publication copied
+    generation: u64,
+    /// Key of this subscriber's interval filter on its topic, once an interval is set
+    throttle: Option<u64>,
+    _msg: PhantomData<T>,
+}
+
+impl<T: UorbMsgMeta> Subscriber<T> {
+    pub fn instance(&self) -> u8 {
+        self.instance
+    }
+
+    /// Whether the topic instance has been advertised
+    pub fn exists(&self) -> bool {
+        self.bus.exists::<T>(self.instance)
+    }
+
+    /// Report updates at most once every `interval` microseconds of publication
+    /// timestamps, as `orb_set_interval` does, copying the first or the latest
+    /// publication of each interval according to `mode`. Only publications from
+    /// now on are delivered.
+    pub fn set_interval(&mut self, interval: u64, mode: IntervalMode) {
+        let mut state = self.bus.state.lock().unwrap();
+        let key = match self.throttle {
+            Some(key) => key,
+            None => state.next_throttle_key(),
+        };
+        self.throttle = Some(key);
+        let topic = state.topics.entry((T::MSG_HASH_CODE, self.instance)).or_default();
+        topic.throttles.insert(key, Throttle { interval: Interval::new(interval, mode), delivered: None, fresh: false });
+    }
+
+    /// Report every publication again
+    pub fn clear_interval(&mut self) {
+        if let Some(key) = self.throttle.take() {
+            self.bus.state.lock().unwrap().remove_throttle(key);
+        }
+    }
+
+    /// Whether there has been a publication since the last copy,
+    /// or with an interval set, whether one has been delivered
+    pub fn updated(&self) -> bool {
+        let state = self.bus.state.lock().unwrap();
+        let topic = match state.topics.get(&(T::MSG_HASH_CODE, self.instance)) {
+            Some(topic) => topic,
+            None => return false,
+        };
+        match self.throttle {
+            Some(key) => topic.throttles.get(&key).is_some_and(|throttle| throttle.fresh),
+            None => topic.generation > self.generation,
+        }
+    }
+
+    /// Copy the latest publication, or with an interval set the one delivered last,
+    /// whether or not it has been copied before
+    pub fn copy(&mut self) -> Option<T> {
+        self.copy_with_header().map(|(_header, data)| data)
+    }
+
+    /// Copy as `copy` does, along with the header
+    pub fn copy_with_header(&mut self) -> Option<(UorbHeader, T)> {
+        let mut state = self.bus.state.lock().unwrap();
+        let topic = state.topics.get_mut(&(T::MSG_HASH_CODE, self.instance))?;
+        let throttle = match self.throttle {
+            Some(key) => topic.throttles.get_mut(&key),
+            None => None,
+        };
+        let (header, payload) = match throttle {
+            Some(throttle) => {
+                throttle.fresh = false;
+                throttle.delivered.as_ref()?
+            }
+            None => topic.latest.as_ref()?,
+        };
+        let data = T::deser(payload)?;
+        self.generation = topic.generation;
+        Some((header.clone(), data))
+    }
+
+    /// Copy the latest publication only if it is new since the last copy
+    pub fn update(&mut self) -> Option<T> {
+        if self.updated() {
+            self.copy()
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Clone for Subscriber<T> {
+    /// The clone has an interval filter of its own, in the same state
+    fn clone(&self) -> Self {
+        let throttle = self.throttle.map(|key| {
+            let mut state = self.bus.state.lock().unwrap();
+            let clone_key = state.next_throttle_key();
+            for topic in state.topics.values_mut() {
+                if let Some(throttle) = topic.throttles.get(&key).cloned() {
+                    topic.throttles.insert(clone_key, throttle);
+                }
+            }
+            clone_key
+        });
+        Subscriber { bus: self.bus.clone(), instance: self.instance, generation: self.generation,
+                     throttle, _msg: PhantomData }
+    }
+}
+
+impl<T> Drop for Subscriber<T> {
+    fn drop(&mut self) {
+        if let (Some(key), Ok(mut state)) = (self.throttle, self.bus.state.lock()) {
+            state.remove_throttle(key);
+        }
+    }
+}
+
+
+/// Reads the latest publication of every topic, such as to bridge the bus with `write_msg`
+#[derive(Debug, Clone)]
+pub struct BusSubscriber {
+    bus: Bus,
+    /// Bus sequence number of the last publication taken
+    seq: u64,
+}
+
+impl BusSubscriber {
+    /// The latest publication of each topic instance published since the last call,
+    /// in publication order. Earlier publications replaced in the meantime are not returned.
+    pub fn poll(&mut self) -> Vec<(UorbHeader, UorbMessage)> {
+        let state = self.bus.state.lock().unwrap();
+        let mut updated: Vec<(u64, &UorbHeader, &Vec<u8>)> = state.topics.values()
+            .filter(|topic| topic.seq > self.seq)
+            .filter_map(|topic| topic.latest.as_ref().map(|(header, payload)| (topic.seq, header, payload)))
+            .collect();
+        updated.sort_by_key(|(seq, _, _)| *seq);
+        self.seq = state.seq;
+        updated.into_iter()
+            .filter_map(|(_, header, payload)| {
+                UorbMessage::parse(header.hash, payload).map(|msg| (header.clone(), msg))
+            })
+            .collect()
+    }
+}
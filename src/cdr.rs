@@ -0,0 +1,180 @@
+//! OMG CDR (XCDR1) serialization primitives, as used by DDS and the microRTPS bridge
+//!
+//! Each primitive is aligned to its own size, relative to the start of the buffer,
+//! which follows the encapsulation header when there is one.
+
+/// Size of the encapsulation header that precedes a serialized DDS sample
+pub const CDR_HEADER_LEN: usize = 4;
+
+/// Byte order of a CDR stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CdrEndian {
+    Big,
+    Little,
+}
+
+/// The encapsulation header of plain CDR in the given byte order, with no options
+pub fn encapsulation_header(endian: CdrEndian) -> [u8; CDR_HEADER_LEN] {
+    match endian {
+        CdrEndian::Big => [0x00, 0x00, 0x00, 0x00],
+        CdrEndian::Little => [0x00, 0x01, 0x00, 0x00],
+    }
+}
+
+/// The byte order given by an encapsulation header, or None if it is not plain CDR
+pub fn decode_encapsulation_header(input: &[u8]) -> Option<CdrEndian> {
+    match input.get(..2)? {
+        [0x00, 0x00] => Some(CdrEndian::Big),
+        [0x00, 0x01] => Some(CdrEndian::Little),
+        _ => None,
+    }
+}
+
+/// A type that CDR encodes as a single aligned primitive
+pub trait CdrPrimitive: Copy {
+    /// Encoded size and alignment, in bytes
+    const SIZE: usize;
+
+    fn write_to(self, endian: CdrEndian, buf: &mut [u8]);
+
+    fn read_from(endian: CdrEndian, buf: &[u8]) -> Self;
+}
+
+macro_rules! cdr_primitive {
+    ($($ty:ty),*) => {$(
+        impl CdrPrimitive for $ty {
+            const SIZE: usize = core::mem::size_of::<$ty>();
+
+            fn write_to(self, endian: CdrEndian, buf: &mut [u8]) {
+                let bytes = match endian {
+                    CdrEndian::Big => self.to_be_bytes(),
+                    CdrEndian::Little => self.to_le_bytes(),
+                };
+                buf[..Self::SIZE].copy_from_slice(&bytes);
+            }
+
+            fn read_from(endian: CdrEndian, buf: &[u8]) -> Self {
+                let mut bytes = [0; core::mem::size_of::<$ty>()];
+                bytes.copy_from_slice(&buf[..Self::SIZE]);
+                match endian {
+                    CdrEndian::Big => <$ty>::from_be_bytes(bytes),
+                    CdrEndian::Little => <$ty>::from_le_bytes(bytes),
+                }
+            }
+        }
+    )*};
+}
+
+cdr_primitive!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+impl CdrPrimitive for bool {
+    const SIZE: usize = 1;
+
+    fn write_to(self, _endian: CdrEndian, buf: &mut [u8]) {
+        buf[0] = self as u8;
+    }
+
+    fn read_from(_endian: CdrEndian, buf: &[u8]) -> Self {
+        buf[0] != 0
+    }
+}
+
+/// A uORB `char` is a single byte
+impl CdrPrimitive for char {
+    const SIZE: usize = 1;
+
+    fn write_to(self, _endian: CdrEndian, buf: &mut [u8]) {
+        buf[0] = self as u8;
+    }
+
+    fn read_from(_endian: CdrEndian, buf: &[u8]) -> Self {
+        buf[0] as char
+    }
+}
+
+
+/// Writes CDR primitives into the start of a byte slice
+#[derive(Debug)]
+pub struct CdrWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+    endian: CdrEndian,
+}
+
+impl<'a> CdrWriter<'a> {
+    pub fn new(buf: &'a mut [u8], endian: CdrEndian) -> Self {
+        CdrWriter { buf, pos: 0, endian }
+    }
+
+    /// Number of bytes written so far
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Zero-fill up to the next multiple of `align`, or None if the slice is too short
+    pub fn align(&mut self, align: usize) -> Option<()> {
+        let padding = (align - self.pos % align) % align;
+        self.buf.get_mut(self.pos..self.pos + padding)?.iter_mut().for_each(|byte| *byte = 0);
+        self.pos += padding;
+        Some(())
+    }
+
+    /// Write an aligned primitive, or None if the slice is too short
+    pub fn write<T: CdrPrimitive>(&mut self, value: T) -> Option<()> {
+        self.align(T::SIZE)?;
+        value.write_to(self.endian, self.buf.get_mut(self.pos..self.pos + T::SIZE)?);
+        self.pos += T::SIZE;
+        Some(())
+    }
+
+    /// Write the elements of a fixed-size array
+    pub fn write_array<T: CdrPrimitive>(&mut self, values: &[T]) -> Option<()> {
+        values.iter().try_for_each(|value| self.write(*value))
+    }
+}
+
+
+/// Reads CDR primitives from a byte slice
+#[derive(Debug)]
+pub struct CdrReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    endian: CdrEndian,
+}
+
+impl<'a> CdrReader<'a> {
+    pub fn new(buf: &'a [u8], endian: CdrEndian) -> Self {
+        CdrReader { buf, pos: 0, endian }
+    }
+
+    /// Number of bytes read so far
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Skip up to the next multiple of `align`, or None past the end of the slice
+    pub fn align(&mut self, align: usize) -> Option<()> {
+        let padding = (align - self.pos % align) % align;
+        if self.pos + padding > self.buf.len() {
+            return None;
+        }
+        self.pos += padding;
+        Some(())
+    }
+
+    /// Read an aligned primitive, or None past the end of the slice
+    pub fn read<T: CdrPrimitive>(&mut self) -> Option<T> {
+        self.align(T::SIZE)?;
+        let value = T::read_from(self.endian, self.buf.get(self.pos..self.pos + T::SIZE)?);
+        self.pos += T::SIZE;
+        Some(value)
+    }
+
+    /// Fill a fixed-size array
+    pub fn read_array<T: CdrPrimitive>(&mut self, values: &mut [T]) -> Option<()> {
+        values.iter_mut().try_for_each(|value| {
+            *value = self.read()?;
+            Some(())
+        })
+    }
+}
@@ -0,0 +1,81 @@
+//! Sources of time in microseconds, so timing code can run against a virtual clock
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+
+/// A monotonic clock counting microseconds
+pub trait Clock {
+    fn now(&self) -> u64;
+
+    /// Block until `now()` reaches `timestamp`
+    fn sleep_until(&self, timestamp: u64);
+}
+
+
+/// Wall-clock time since the clock was created
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+
+    fn sleep_until(&self, timestamp: u64) {
+        let now = self.now();
+        if timestamp > now {
+            thread::sleep(Duration::from_micros(timestamp - now));
+        }
+    }
+}
+
+
+/// A clock that only moves when told to, or when slept on, for deterministic tests.
+/// Clones share the same time.
+#[derive(Debug, Clone, Default)]
+pub struct VirtualClock {
+    now: Arc<AtomicU64>,
+}
+
+impl VirtualClock {
+    pub fn new(start: u64) -> Self {
+        VirtualClock { now: Arc::new(AtomicU64::new(start)) }
+    }
+
+    pub fn advance(&self, micros: u64) {
+        self.now.fetch_add(micros, Ordering::SeqCst);
+    }
+
+    /// Set the time, which must not go backwards
+    pub fn set(&self, timestamp: u64) {
+        self.now.fetch_max(timestamp, Ordering::SeqCst);
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+
+    /// Jump straight to `timestamp`
+    fn sleep_until(&self, timestamp: u64) {
+        self.set(timestamp);
+    }
+}
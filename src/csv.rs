@@ -0,0 +1,370 @@
+//! CSV files in the layout of PX4's ulog2csv, for analysis with pandas and the like
+//!
+//! Each (message, instance) gets a file of its own, named `<prefix>_<name>_<instance>.csv`.
+//! The header row names the fields in encoded order, with `timestamp` moved to the front,
+//! and each array element is a column of its own, such as `q[0]`. Each frame is one row.
+//! As in ulog2csv, bools and chars are written as numbers.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Result, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use crate::field::column_order;
+use crate::recording::{for_each_frame, for_each_recorded_frame};
+use crate::{FieldInfo, FieldType, FieldValue, UorbHeader, UorbMessage, UorbMsgMeta, UORB_MAGIC_V1};
+
+
+fn invalid_input(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, msg.to_string())
+}
+
+fn invalid_data(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+/// One column: an element of a field, or the whole of a field that is not an array
+#[derive(Debug, Clone, Copy)]
+struct Column {
+    field: FieldInfo,
+    index: usize,
+}
+
+impl Column {
+    fn name(&self) -> String {
+        match self.field.array_len {
+            Some(_) => format!("{}[{}]", self.field.name, self.index),
+            None => self.field.name.to_string(),
+        }
+    }
+
+    fn offset(&self) -> usize {
+        self.field.offset + self.index * self.field.field_type.encoded_len()
+    }
+}
+
+/// The columns of a message, in file order
+fn columns(fields: &[FieldInfo]) -> Vec<Column> {
+    column_order(fields)
+        .flat_map(|field| (0..field.array_len.unwrap_or(1)).map(move |index| Column { field: *field, index }))
+        .collect()
+}
+
+/// The header row names of the message with this hash, or None if it is unknown
+pub fn column_names(hash: u16) -> Option<Vec<String>> {
+    UorbMessage::fields_for_hash(hash).map(|fields| columns(fields).iter().map(Column::name).collect())
+}
+
+/// The name of the file holding one instance of a message, as ulog2csv names it
+pub fn file_name(prefix: &str, raw_name: &str, instance_id: u8) -> String {
+    format!("{}_{}_{}.csv", prefix, raw_name, instance_id)
+}
+
+/// The message hash and instance ID in a file name made by `file_name`, or None if
+/// it does not end in a known message name and an instance
+pub fn parse_file_name(file_name: &str) -> Option<(u16, u8)> {
+    let stem = file_name.strip_suffix(".csv")?;
+    let (rest, instance) = stem.rsplit_once('_')?;
+    let instance_id = instance.parse().ok()?;
+    // the longest match wins, as one message name may end with another
+    let hash = UorbMessage::KNOWN_HASHES.iter()
+        .filter_map(|&hash| UorbMessage::raw_name_for_hash(hash).map(|name| (hash, name)))
+        .filter(|(_, name)| rest == *name || rest.ends_with(&format!("_{}", name)))
+        .max_by_key(|(_, name)| name.len())?
+        .0;
+    Some((hash, instance_id))
+}
+
+fn format_value(value: FieldValue) -> String {
+    match value {
+        FieldValue::Bool(value) => (value as u8).to_string(),
+        FieldValue::Char(value) => (value as u32).to_string(),
+        FieldValue::Float32(value) if value.is_nan() => "nan".to_string(),
+        FieldValue::Float64(value) if value.is_nan() => "nan".to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn parse_value(field_type: FieldType, text: &str) -> Option<FieldValue> {
+    use crate::FieldType::*;
+    let value = match field_type {
+        Bool => match text {
+            "0" | "false" | "False" => FieldValue::Bool(false),
+            "1" | "true" | "True" => FieldValue::Bool(true),
+            _ => return None,
+        },
+        // pyulog reads chars as int8
+        Char => match text.parse::<i16>().ok()? {
+            code @ -128..=255 => FieldValue::Char(code as u8 as char),
+            _ => return None,
+        },
+        UInt8 | UInt16 | UInt32 | UInt64 => FieldValue::UInt(text.parse().ok()?),
+        Int8 | Int16 | Int32 | Int64 => FieldValue::Int(text.parse().ok()?),
+        Float32 => FieldValue::Float32(text.parse().ok()?),
+        Float64 => FieldValue::Float64(text.parse().ok()?),
+    };
+    Some(value)
+}
+
+
+/// Writes the frames of one message type as CSV rows
+#[derive(Debug)]
+pub struct CsvWriter<W: Write> {
+    inner: W,
+    hash: u16,
+    columns: Vec<Column>,
+    payload: Vec<u8>,
+    rows: u64,
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Start a file for the message with this hash by writing its header row
+    pub fn new(mut inner: W, hash: u16) -> Result<Self> {
+        let fields = UorbMessage::fields_for_hash(hash).ok_or_else(|| invalid_input("unknown message hash"))?;
+        let columns = columns(fields);
+        let names: Vec<String> = columns.iter().map(Column::name).collect();
+        writeln!(inner, "{}", names.join(","))?;
+        Ok(CsvWriter {
+            inner,
+            hash,
+            columns,
+            payload: vec![0; UorbMessage::encoded_len_for_hash(hash).unwrap_or(0)],
+            rows: 0,
+        })
+    }
+
+    pub fn hash(&self) -> u16 {
+        self.hash
+    }
+
+    /// Number of rows written, not counting the header row
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    /// Write one frame as a row. The header must carry this writer's message.
+    pub fn write_msg(&mut self, header: &UorbHeader, msg: &UorbMessage) -> Result<()> {
+        if header.hash != self.hash || msg.encoded_len() != self.payload.len() {
+            return Err(invalid_input("message does not match the CSV file"));
+        }
+        msg.ser_into_slice(&mut self.payload);
+        let cells: Vec<String> = self.columns.iter()
+            .map(|column| column.field.field_type.decode(&self.payload[column.offset()..]).map_or_else(String::new, format_value))
+            .collect();
+        writeln!(self.inner, "{}", cells.join(","))?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Flush the rows written and return the inner writer
+    pub fn into_inner(mut self) -> Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+
+/// An open file and its path
+type CsvFile = (PathBuf, CsvWriter<BufWriter<File>>);
+
+/// Writes frames from any source to a directory of CSV files, one per (message, instance).
+///
+/// Files are created as their first frame arrives, replacing any of the same name.
+#[derive(Debug)]
+pub struct CsvExporter {
+    dir: PathBuf,
+    prefix: String,
+    writers: BTreeMap<(u16, u8), CsvFile>,
+}
+
+impl CsvExporter {
+    /// Export to files named `<prefix>_<name>_<instance>.csv` in `dir`, which is
+    /// created if it does not exist
+    pub fn new<P: AsRef<Path>>(dir: P, prefix: &str) -> Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(CsvExporter {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: prefix.to_string(),
+            writers: BTreeMap::new(),
+        })
+    }
+
+    /// Add one frame to the file for its message and instance
+    pub fn write_msg(&mut self, header: &UorbHeader, msg: &UorbMessage) -> Result<()> {
+        let key = (header.hash, header.instance_id);
+        if !self.writers.contains_key(&key) {
+            let raw_name = UorbMessage::raw_name_for_hash(header.hash)
+                .ok_or_else(|| invalid_input("unknown message hash"))?;
+            let path = self.dir.join(file_name(&self.prefix, raw_name, header.instance_id));
+            let writer = CsvWriter::new(BufWriter::new(File::create(&path)?), header.hash)?;
+            self.writers.insert(key, (path, writer));
+        }
+        let (_path, writer) = self.writers.get_mut(&key).unwrap();
+        writer.write_msg(header, msg)
+    }
+
+    /// Export frames read with `read_msg` until the source ends. Returns the
+    /// number of frames exported.
+    pub fn export_stream<R: Read>(&mut self, r: &mut R) -> Result<u64> {
+        for_each_frame(r, |header, msg| self.write_msg(header, msg))
+    }
+
+    /// Export every frame of a recording, without waiting out its timing. Returns
+    /// the number of frames exported.
+    pub fn export_recording<R: Read + Seek>(&mut self, reader: R) -> Result<u64> {
+        for_each_recorded_frame(reader, |header, msg| self.write_msg(header, msg))
+    }
+
+    /// Paths of the files created so far, by message hash and instance
+    pub fn files(&self) -> Vec<&Path> {
+        self.writers.values().map(|(path, _writer)| path.as_path()).collect()
+    }
+
+    /// Flush every file, and return their paths
+    pub fn finish(self) -> Result<Vec<PathBuf>> {
+        self.writers.into_values()
+            .map(|(path, writer)| writer.into_inner().map(|_file| path))
+            .collect()
+    }
+}
+
+
+/// Reads the rows of a CSV file back into messages.
+///
+/// Columns are matched to fields by name, so they may come in any order. Columns
+/// that name no field are ignored, and a field without a column is an error.
+/// As an iterator, yields each row until the file ends.
+#[derive(Debug)]
+pub struct CsvReader<R> {
+    inner: R,
+    hash: u16,
+    instance_id: u8,
+    /// The column of each cell of a row, or None for cells that are not fields
+    columns: Vec<Option<Column>>,
+    payload_len: usize,
+    line: String,
+    line_no: u64,
+}
+
+impl CsvReader<BufReader<File>> {
+    /// Open a file named as `file_name` names it, taking the message and instance from its name
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let (hash, instance_id) = path.as_ref().file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse_file_name)
+            .ok_or_else(|| invalid_input("CSV file name does not end in a known message name and instance"))?;
+        CsvReader::new(BufReader::new(File::open(path)?), hash, instance_id)
+    }
+}
+
+impl<R: BufRead> CsvReader<R> {
+    /// Read rows of the message with this hash, starting with the header row
+    pub fn new(mut inner: R, hash: u16, instance_id: u8) -> Result<Self> {
+        let fields = UorbMessage::fields_for_hash(hash).ok_or_else(|| invalid_input("unknown message hash"))?;
+        let mut line = String::new();
+        if inner.read_line(&mut line)? == 0 {
+            return Err(invalid_data("CSV file has no header row".to_string()));
+        }
+        let mut by_name: HashMap<String, Column> = columns(fields).into_iter()
+            .map(|column| (column.name(), column))
+            .collect();
+        let columns = line.trim_end().split(',')
+            .map(|name| by_name.remove(name.trim().trim_matches('"')))
+            .collect();
+        if let Some(name) = by_name.keys().min() {
+            return Err(invalid_data(format!("CSV file has no column {}", name)));
+        }
+        Ok(CsvReader {
+            inner,
+            hash,
+            instance_id,
+            columns,
+            payload_len: UorbMessage::encoded_len_for_hash(hash).unwrap_or(0),
+            line,
+            line_no: 1,
+        })
+    }
+
+    pub fn hash(&self) -> u16 {
+        self.hash
+    }
+
+    pub fn instance_id(&self) -> u8 {
+        self.instance_id
+    }
+
+    /// Encode the next row as a payload. False at the end of the file.
+    fn read_payload(&mut self, payload: &mut [u8]) -> Result<bool> {
+        loop {
+            self.line.clear();
+            if self.inner.read_line(&mut self.line)? == 0 {
+                return Ok(false);
+            }
+            self.line_no += 1;
+            if !self.line.trim().is_empty() {
+                break;
+            }
+        }
+        let cells: Vec<&str> = self.line.trim_end().split(',').collect();
+        if cells.len() != self.columns.len() {
+            return Err(invalid_data(format!("line {}: {} cells for {} columns",
+                                            self.line_no, cells.len(), self.columns.len())));
+        }
+        for (column, cell) in self.columns.iter().zip(cells) {
+            if let Some(column) = column {
+                let cell = cell.trim();
+                let field_type = column.field.field_type;
+                parse_value(field_type, cell)
+                    .and_then(|value| field_type.encode(value, &mut payload[column.offset()..]))
+                    .ok_or_else(|| invalid_data(format!("line {}: bad value {:?} for {}",
+                                                        self.line_no, cell, column.name())))?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Read the next row, or None at the end of the file. The header's timestamp
+    /// is the message's `timestamp` field.
+    pub fn read_msg(&mut self) -> Result<Option<(UorbHeader, UorbMessage)>> {
+        let mut payload = vec![0; self.payload_len];
+        if !self.read_payload(&mut payload)? {
+            return Ok(None);
+        }
+        let timestamp = UorbMessage::fields_for_hash(self.hash).unwrap_or(&[]).iter()
+            .find(|field| field.name == "timestamp")
+            .and_then(|field| field.values(&payload).next())
+            .map_or(0, |value| match value {
+                FieldValue::UInt(value) => value,
+                _ => 0,
+            });
+        let header = UorbHeader {
+            version: UORB_MAGIC_V1,
+            hash: self.hash,
+            timestamp,
+            instance_id: self.instance_id,
+            payload_len: self.payload_len,
+        };
+        let msg = UorbMessage::parse(self.hash, &payload).ok_or_else(|| invalid_input("unknown message hash"))?;
+        Ok(Some((header, msg)))
+    }
+
+    /// Read the next row as a `T`, which must be this file's message
+    pub fn read_typed<T: UorbMsgMeta>(&mut self) -> Result<Option<T>> {
+        if T::MSG_HASH_CODE != self.hash {
+            return Err(invalid_input("CSV file holds another message"));
+        }
+        let mut payload = vec![0; T::ENCODED_LEN];
+        if !self.read_payload(&mut payload)? {
+            return Ok(None);
+        }
+        Ok(T::deser(&payload))
+    }
+}
+
+impl<R: BufRead> Iterator for CsvReader<R> {
+    type Item = Result<(UorbHeader, UorbMessage)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_msg().transpose()
+    }
+}
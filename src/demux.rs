@@ -0,0 +1,177 @@
+//! Separating decoded frames by topic instance, such as redundant sensors
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::{UorbHeader, UorbMessage, UorbMsgMeta};
+
+
+/// How the frames of one topic are held until they are taken
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlotKind {
+    /// Only the latest frame of each instance
+    Latest,
+    /// Up to this many frames of each instance, dropping the oldest when full
+    Queue(usize),
+}
+
+/// A change in the set of active topic instances
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DemuxEvent {
+    /// The first frame of a topic instance arrived
+    NewInstance { hash: u16, instance_id: u8 },
+    /// No frame of a topic instance arrived within the silence timeout
+    Silent { hash: u16, instance_id: u8, last_timestamp: u64 },
+    /// A silent topic instance sent a frame again
+    Resumed { hash: u16, instance_id: u8 },
+}
+
+#[derive(Debug)]
+struct InstanceSlot {
+    frames: VecDeque<(UorbHeader, UorbMessage)>,
+    last_timestamp: u64,
+    silent: bool,
+    dropped: u64,
+}
+
+
+/// Routes decoded frames into a slot per (MSG_HASH_CODE, instance ID), and reports
+/// instances that appear, go silent and resume.
+///
+/// Silence is judged by header timestamps: an instance is silent once the newest
+/// timestamp seen on any topic is `silence_timeout` past its own last frame.
+#[derive(Debug)]
+pub struct TopicDemux {
+    default_kind: SlotKind,
+    kinds: HashMap<u16, SlotKind>,
+    silence_timeout: Option<u64>,
+    slots: BTreeMap<(u16, u8), InstanceSlot>,
+    events: VecDeque<DemuxEvent>,
+    now: u64,
+}
+
+impl Default for TopicDemux {
+    fn default() -> Self {
+        Self::new(SlotKind::Latest)
+    }
+}
+
+impl TopicDemux {
+    /// Hold frames in slots of `default_kind`, unless set otherwise for a topic
+    pub fn new(default_kind: SlotKind) -> Self {
+        TopicDemux {
+            default_kind,
+            kinds: HashMap::new(),
+            silence_timeout: None,
+            slots: BTreeMap::new(),
+            events: VecDeque::new(),
+            now: 0,
+        }
+    }
+
+    /// Hold frames of the topic with message hash `hash` in slots of `kind`
+    pub fn set_slot_kind(&mut self, hash: u16, kind: SlotKind) {
+        self.kinds.insert(hash, kind);
+    }
+
+    pub fn set_slot_kind_for<T: UorbMsgMeta>(&mut self, kind: SlotKind) {
+        self.set_slot_kind(T::MSG_HASH_CODE, kind);
+    }
+
+    /// Report an instance as silent after `timeout` microseconds without a frame
+    pub fn set_silence_timeout(&mut self, timeout: Option<u64>) {
+        self.silence_timeout = timeout;
+    }
+
+    /// Route a decoded frame to the slot of its topic instance
+    pub fn push(&mut self, header: UorbHeader, msg: UorbMessage) {
+        let (hash, instance_id) = (header.hash, header.instance_id);
+        let timestamp = header.timestamp;
+        let capacity = match self.kinds.get(&hash).cloned().unwrap_or(self.default_kind) {
+            SlotKind::Latest => 1,
+            SlotKind::Queue(capacity) => capacity.max(1),
+        };
+
+        let events = &mut self.events;
+        let slot = self.slots.entry((hash, instance_id)).or_insert_with(|| {
+            events.push_back(DemuxEvent::NewInstance { hash, instance_id });
+            InstanceSlot { frames: VecDeque::new(), last_timestamp: timestamp, silent: false, dropped: 0 }
+        });
+        if slot.silent {
+            slot.silent = false;
+            events.push_back(DemuxEvent::Resumed { hash, instance_id });
+        }
+        slot.last_timestamp = slot.last_timestamp.max(timestamp);
+        if slot.frames.len() >= capacity {
+            slot.frames.pop_front();
+            slot.dropped += 1;
+        }
+        slot.frames.push_back((header, msg));
+
+        self.check_silence(timestamp);
+    }
+
+    /// Report instances silent as of `now`, a header timestamp. This is called on
+    /// every push, but can also be driven by a clock when no frames arrive at all.
+    pub fn check_silence(&mut self, now: u64) {
+        self.now = self.now.max(now);
+        let timeout = match self.silence_timeout {
+            Some(timeout) => timeout,
+            None => return,
+        };
+        for (&(hash, instance_id), slot) in self.slots.iter_mut() {
+            if !slot.silent && self.now >= slot.last_timestamp + timeout {
+                slot.silent = true;
+                self.events.push_back(DemuxEvent::Silent {
+                    hash, instance_id, last_timestamp: slot.last_timestamp });
+            }
+        }
+    }
+
+    /// Take the next instance event, in the order they occurred
+    pub fn poll_event(&mut self) -> Option<DemuxEvent> {
+        self.events.pop_front()
+    }
+
+    /// Take the oldest frame held for a topic instance
+    pub fn pop(&mut self, hash: u16, instance_id: u8) -> Option<(UorbHeader, UorbMessage)> {
+        self.slots.get_mut(&(hash, instance_id))?.frames.pop_front()
+    }
+
+    /// Take the oldest frame held for instance `instance_id` of topic `T`
+    pub fn pop_data<T: UorbMsgMeta>(&mut self, instance_id: u8) -> Option<(UorbHeader, T)> {
+        let (header, msg) = self.pop(T::MSG_HASH_CODE, instance_id)?;
+        T::unwrap_message(msg).map(|data| (header, data))
+    }
+
+    /// The newest frame held for a topic instance, without taking it
+    pub fn latest(&self, hash: u16, instance_id: u8) -> Option<&(UorbHeader, UorbMessage)> {
+        self.slots.get(&(hash, instance_id))?.frames.back()
+    }
+
+    /// Number of frames held for a topic instance
+    pub fn len(&self, hash: u16, instance_id: u8) -> usize {
+        self.slots.get(&(hash, instance_id)).map_or(0, |slot| slot.frames.len())
+    }
+
+    /// Number of frames dropped from a full slot of a topic instance
+    pub fn dropped(&self, hash: u16, instance_id: u8) -> u64 {
+        self.slots.get(&(hash, instance_id)).map_or(0, |slot| slot.dropped)
+    }
+
+    /// Instances of a topic seen so far, in ascending order
+    pub fn instances(&self, hash: u16) -> Vec<u8> {
+        self.slots.range((hash, 0)..=(hash, u8::MAX)).map(|(&(_, instance_id), _)| instance_id).collect()
+    }
+
+    /// Instances of a topic that are not silent, in ascending order
+    pub fn active_instances(&self, hash: u16) -> Vec<u8> {
+        self.slots.range((hash, 0)..=(hash, u8::MAX))
+            .filter(|(_, slot)| !slot.silent)
+            .map(|(&(_, instance_id), _)| instance_id)
+            .collect()
+    }
+
+    pub fn is_silent(&self, hash: u16, instance_id: u8) -> bool {
+        self.slots.get(&(hash, instance_id)).is_some_and(|slot| slot.silent)
+    }
+}
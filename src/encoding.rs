@@ -0,0 +1,228 @@
+//! Byte-stuffing encodings that wrap each frame in a delimited packet.
+//!
+//! Scanning for `UORB_MAGIC_V1` can lock onto a false frame start, since that
+//! byte may also appear inside timestamps and payloads. The encodings here
+//! reserve a delimiter byte that never appears inside a packet, so a reader
+//! always resynchronizes at the next packet boundary.
+
+use crate::{frame, DecodeMode, FrameError, UorbHeader, UorbMessage};
+use crate::{MAX_FRAME_LEN, UORB_HEADER_LEN};
+#[cfg(feature = "std")]
+use std::io::{Read, Result, Write};
+#[cfg(feature = "std")]
+use byteorder::ReadBytesExt;
+
+/// Size of the largest packet, including its delimiter, for any known message
+pub const MAX_PACKET_LEN: usize = 2 * MAX_FRAME_LEN + 2;
+
+
+/// A reversible encoding of a raw frame into a packet ending in `DELIMITER`
+pub trait PacketEncoding {
+    /// The byte marking the end of each packet, which never appears inside one
+    const DELIMITER: u8;
+
+    /// Largest packet, including its delimiter, that a raw frame of `raw_len` bytes encodes to
+    fn max_encoded_len(raw_len: usize) -> usize;
+
+    /// Encode `raw` into `out` followed by the delimiter, returning the number of bytes written
+    fn encode(raw: &[u8], out: &mut [u8]) -> core::result::Result<usize, FrameError>;
+
+    /// Decode a `packet`, excluding its delimiter, into `out`,
+    /// returning the number of bytes written
+    fn decode(packet: &[u8], out: &mut [u8]) -> core::result::Result<usize, FrameError>;
+}
+
+
+/// Consistent Overhead Byte Stuffing, delimited by 0x00
+#[derive(Debug, Clone, Copy)]
+pub struct Cobs;
+
+impl PacketEncoding for Cobs {
+    const DELIMITER: u8 = 0x00;
+
+    fn max_encoded_len(raw_len: usize) -> usize {
+        // one code byte per 254 data bytes, plus the leading code and the delimiter
+        raw_len + raw_len / 254 + 2
+    }
+
+    fn encode(raw: &[u8], out: &mut [u8]) -> core::result::Result<usize, FrameError> {
+        let out = frame::frame_buf(out, Self::max_encoded_len(raw.len()))?;
+        let mut code_idx = 0;
+        let mut code = 1u8;
+        let mut len = 1;
+        for &byte in raw {
+            if byte != 0 {
+                out[len] = byte;
+                len += 1;
+                code += 1;
+            }
+            if byte == 0 || code == 0xFF {
+                out[code_idx] = code;
+                code_idx = len;
+                len += 1;
+                code = 1;
+            }
+        }
+        out[code_idx] = code;
+        out[len] = Self::DELIMITER;
+        Ok(len + 1)
+    }
+
+    fn decode(packet: &[u8], out: &mut [u8]) -> core::result::Result<usize, FrameError> {
+        let mut idx = 0;
+        let mut len = 0;
+        while idx < packet.len() {
+            let code = packet[idx] as usize;
+            let end = idx + code;
+            if code == 0 || end > packet.len() {
+                return Err(FrameError::InvalidEncoding);
+            }
+            let run = &packet[idx + 1..end];
+            frame::frame_buf(&mut out[len..], run.len())?.copy_from_slice(run);
+            len += run.len();
+            idx = end;
+            // every block but a full one or the last is followed by a zero
+            if code != 0xFF && idx < packet.len() {
+                frame::frame_buf(&mut out[len..], 1)?[0] = 0;
+                len += 1;
+            }
+        }
+        Ok(len)
+    }
+}
+
+
+/// Serial Line Internet Protocol (RFC 1055) framing, delimited by 0xC0
+#[derive(Debug, Clone, Copy)]
+pub struct Slip;
+
+impl Slip {
+    const ESC: u8 = 0xDB;
+    const ESC_END: u8 = 0xDC;
+    const ESC_ESC: u8 = 0xDD;
+}
+
+impl PacketEncoding for Slip {
+    const DELIMITER: u8 = 0xC0;
+
+    fn max_encoded_len(raw_len: usize) -> usize {
+        // every byte may need escaping
+        2 * raw_len + 1
+    }
+
+    fn encode(raw: &[u8], out: &mut [u8]) -> core::result::Result<usize, FrameError> {
+        let out = frame::frame_buf(out, Self::max_encoded_len(raw.len()))?;
+        let mut len = 0;
+        for &byte in raw {
+            let escaped = match byte {
+                Self::DELIMITER => Some(Self::ESC_END),
+                Self::ESC => Some(Self::ESC_ESC),
+                _ => None,
+            };
+            if let Some(escaped) = escaped {
+                out[len] = Self::ESC;
+                out[len + 1] = escaped;
+                len += 2;
+            } else {
+                out[len] = byte;
+                len += 1;
+            }
+        }
+        out[len] = Self::DELIMITER;
+        Ok(len + 1)
+    }
+
+    fn decode(packet: &[u8], out: &mut [u8]) -> core::result::Result<usize, FrameError> {
+        let mut bytes = packet.iter();
+        let mut len = 0;
+        while let Some(&byte) = bytes.next() {
+            let byte = match byte {
+                Self::DELIMITER => return Err(FrameError::InvalidEncoding),
+                Self::ESC => match bytes.next() {
+                    Some(&Self::ESC_END) => Self::DELIMITER,
+                    Some(&Self::ESC_ESC) => Self::ESC,
+                    _ => return Err(FrameError::InvalidEncoding),
+                },
+                _ => byte,
+            };
+            frame::frame_buf(&mut out[len..], 1)?[0] = byte;
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+
+impl UorbMessage {
+    /// Encode a complete frame for this message as an `E` packet into `buf`,
+    /// returning the number of bytes written, including the delimiter
+    pub fn encode_packet_into<E: PacketEncoding>(&self, header: &UorbHeader, buf: &mut [u8])
+        -> core::result::Result<usize, FrameError> {
+        let mut frame = [0; MAX_FRAME_LEN];
+        let frame_len = self.encode_frame_into(header, &mut frame)?;
+        E::encode(&frame[..frame_len], buf)
+    }
+}
+
+/// Decode a single `E` packet, excluding its delimiter, holding one frame.
+/// In Lenient mode a known message may have a longer payload than any known
+/// message, as long as the packet fits in MAX_PACKET_LEN.
+pub fn decode_packet<E: PacketEncoding>(packet: &[u8], mode: DecodeMode)
+    -> core::result::Result<(UorbHeader, UorbMessage), FrameError> {
+    // a packet never decodes to more bytes than it holds
+    let mut frame = [0; MAX_PACKET_LEN];
+    let frame_len = E::decode(packet, &mut frame)?;
+    let header = UorbHeader::decode(&frame[..frame_len])
+        .ok_or(FrameError::InvalidEncoding)?;
+    frame::check_payload_len(&header, mode)?;
+    if UORB_HEADER_LEN + header.payload_len != frame_len {
+        return Err(FrameError::InvalidEncoding);
+    }
+    let payload = &frame[UORB_HEADER_LEN..frame_len];
+    UorbMessage::parse_with_mode(header.hash, payload, mode)
+        .map(|(msg, _fit)| (header, msg))
+}
+
+
+/// Write one frame as an `E` packet
+#[cfg(feature = "std")]
+pub fn write_msg<E: PacketEncoding, W: Write>(w: &mut W, header: &UorbHeader, data: &UorbMessage)
+    -> Result<()> {
+    let mut packet = [0; MAX_PACKET_LEN];
+    let packet_len = data.encode_packet_into::<E>(header, &mut packet)?;
+    w.write_all(&packet[..packet_len])
+}
+
+/// Read the next `E` packet, requiring its payload length to match strictly
+#[cfg(feature = "std")]
+pub fn read_msg<E: PacketEncoding, R: Read>(r: &mut R) -> Result<(UorbHeader, UorbMessage)> {
+    read_msg_with_mode::<E, R>(r, DecodeMode::Strict)
+}
+
+/// Read the next `E` packet. Empty packets are skipped, so senders may also
+/// precede each packet with a delimiter to flush any line noise.
+#[cfg(feature = "std")]
+pub fn read_msg_with_mode<E: PacketEncoding, R: Read>(r: &mut R, mode: DecodeMode)
+    -> Result<(UorbHeader, UorbMessage)> {
+    let mut packet = [0; MAX_PACKET_LEN];
+    let mut len = 0;
+    loop {
+        let byte = r.read_u8()?;
+        if byte != E::DELIMITER {
+            // keep counting an overlong packet, so the next read starts after it
+            if len < packet.len() {
+                packet[len] = byte;
+            }
+            len += 1;
+            continue;
+        }
+        if len == 0 {
+            continue;
+        }
+        if len > packet.len() {
+            // too long to hold a single frame
+            return Err(FrameError::InvalidEncoding.into());
+        }
+        return decode_packet::<E>(&packet[..len], mode).map_err(|err| err.into());
+    }
+}
@@ -0,0 +1,208 @@
+//! Generated layout metadata for the fields of each message
+
+use core::convert::TryFrom;
+use core::fmt;
+
+/// Primitive type of a message field, or of each element of an array field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Bool,
+    Char,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Float32,
+    Float64,
+}
+
+impl FieldType {
+    /// Encoded size, in bytes
+    pub fn encoded_len(&self) -> usize {
+        use self::FieldType::*;
+        match self {
+            Bool | Char | UInt8 | Int8 => 1,
+            UInt16 | Int16 => 2,
+            UInt32 | Int32 | Float32 => 4,
+            UInt64 | Int64 | Float64 => 8,
+        }
+    }
+
+    /// The C type name used for this type in ULog format definitions
+    pub fn ulog_name(&self) -> &'static str {
+        use self::FieldType::*;
+        match self {
+            Bool => "bool",
+            Char => "char",
+            UInt8 => "uint8_t",
+            UInt16 => "uint16_t",
+            UInt32 => "uint32_t",
+            UInt64 => "uint64_t",
+            Int8 => "int8_t",
+            Int16 => "int16_t",
+            Int32 => "int32_t",
+            Int64 => "int64_t",
+            Float32 => "float",
+            Float64 => "double",
+        }
+    }
+
+    pub fn from_ulog_name(name: &str) -> Option<FieldType> {
+        use self::FieldType::*;
+        let field_type = match name {
+            "bool" => Bool,
+            "char" => Char,
+            "uint8_t" => UInt8,
+            "uint16_t" => UInt16,
+            "uint32_t" => UInt32,
+            "uint64_t" => UInt64,
+            "int8_t" => Int8,
+            "int16_t" => Int16,
+            "int32_t" => Int32,
+            "int64_t" => Int64,
+            "float" => Float32,
+            "double" => Float64,
+            _ => return None,
+        };
+        Some(field_type)
+    }
+
+    /// Decode one little-endian element from the start of `buf`, or None if it is too short
+    pub fn decode(&self, buf: &[u8]) -> Option<FieldValue> {
+        use self::FieldType::*;
+        let bytes = buf.get(..self.encoded_len())?;
+        let mut wide = [0; 8];
+        wide[..bytes.len()].copy_from_slice(bytes);
+        let value = match self {
+            Bool => FieldValue::Bool(bytes[0] != 0),
+            Char => FieldValue::Char(bytes[0] as char),
+            UInt8 | UInt16 | UInt32 | UInt64 => FieldValue::UInt(u64::from_le_bytes(wide)),
+            Int8 => FieldValue::Int(bytes[0] as i8 as i64),
+            Int16 => FieldValue::Int(i16::from_le_bytes([bytes[0], bytes[1]]) as i64),
+            Int32 => FieldValue::Int(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64),
+            Int64 => FieldValue::Int(i64::from_le_bytes(wide)),
+            Float32 => FieldValue::Float32(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            Float64 => FieldValue::Float64(f64::from_le_bytes(wide)),
+        };
+        Some(value)
+    }
+
+    /// Encode one element little-endian at the start of `buf`. None if `buf` is too
+    /// short, or the value is of another kind or out of range for this type.
+    pub fn encode(&self, value: FieldValue, buf: &mut [u8]) -> Option<()> {
+        use self::FieldType::*;
+        let buf = buf.get_mut(..self.encoded_len())?;
+        match (self, value) {
+            (Bool, FieldValue::Bool(value)) => buf[0] = value as u8,
+            (Char, FieldValue::Char(value)) => buf[0] = u8::try_from(value as u32).ok()?,
+            (UInt8, FieldValue::UInt(value)) => buf[0] = u8::try_from(value).ok()?,
+            (UInt16, FieldValue::UInt(value)) => buf.copy_from_slice(&u16::try_from(value).ok()?.to_le_bytes()),
+            (UInt32, FieldValue::UInt(value)) => buf.copy_from_slice(&u32::try_from(value).ok()?.to_le_bytes()),
+            (UInt64, FieldValue::UInt(value)) => buf.copy_from_slice(&value.to_le_bytes()),
+            (Int8, FieldValue::Int(value)) => buf.copy_from_slice(&i8::try_from(value).ok()?.to_le_bytes()),
+            (Int16, FieldValue::Int(value)) => buf.copy_from_slice(&i16::try_from(value).ok()?.to_le_bytes()),
+            (Int32, FieldValue::Int(value)) => buf.copy_from_slice(&i32::try_from(value).ok()?.to_le_bytes()),
+            (Int64, FieldValue::Int(value)) => buf.copy_from_slice(&value.to_le_bytes()),
+            (Float32, FieldValue::Float32(value)) => buf.copy_from_slice(&value.to_le_bytes()),
+            (Float64, FieldValue::Float64(value)) => buf.copy_from_slice(&value.to_le_bytes()),
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+/// One decoded element of a field, for code that handles every message alike
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldValue {
+    Bool(bool),
+    Char(char),
+    UInt(u64),
+    Int(i64),
+    Float32(f32),
+    Float64(f64),
+}
+
+impl FieldValue {
+    /// The value as a float, as plotting tools see it
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            FieldValue::Bool(value) => value as u8 as f64,
+            FieldValue::Char(value) => value as u32 as f64,
+            FieldValue::UInt(value) => value as f64,
+            FieldValue::Int(value) => value as f64,
+            FieldValue::Float32(value) => value as f64,
+            FieldValue::Float64(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldValue::Bool(value) => write!(f, "{}", value),
+            FieldValue::Char(value) => write!(f, "{}", value),
+            FieldValue::UInt(value) => write!(f, "{}", value),
+            FieldValue::Int(value) => write!(f, "{}", value),
+            FieldValue::Float32(value) => write!(f, "{}", value),
+            FieldValue::Float64(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// The unit of a field, as its .msg comment gives it.
+///
+/// A comment may name the unit outright with an annotation such as `[Pa]` or
+/// `[1e-7 deg]`. Units of the vendored PX4 definitions are corrected through
+/// `UNIT_OVERRIDES` in build/parser.rs, so those files stay as they are upstream.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldUnit {
+    /// Normalized unit name, such as `m/s` or `deg`
+    pub name: &'static str,
+    /// Factor from the field's value to the unit, such as 1e-7 for a latitude in 1E-7 degrees
+    pub scale: f64,
+}
+
+/// Layout of one field within an encoded payload
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    pub field_type: FieldType,
+    /// Number of elements of an array field
+    pub array_len: Option<usize>,
+    /// Offset of the field from the start of the payload, in bytes
+    pub offset: usize,
+    pub unit: Option<FieldUnit>,
+}
+
+impl FieldInfo {
+    /// Encoded size of the whole field, in bytes
+    pub fn encoded_len(&self) -> usize {
+        self.field_type.encoded_len() * self.array_len.unwrap_or(1)
+    }
+
+    /// Decode each element of this field from an encoded payload. There are none
+    /// if the payload is too short.
+    pub fn values<'a>(&self, payload: &'a [u8]) -> impl Iterator<Item = FieldValue> + 'a {
+        let field_type = self.field_type;
+        let elem_len = field_type.encoded_len();
+        let bytes = payload.get(self.offset..self.offset + self.encoded_len()).unwrap_or(&[]);
+        bytes.chunks_exact(elem_len).filter_map(move |elem| field_type.decode(elem))
+    }
+
+    /// Each element of this field as a float in its unit, or as its value if it has none
+    pub fn scaled_values<'a>(&self, payload: &'a [u8]) -> impl Iterator<Item = f64> + 'a {
+        let scale = self.unit.map_or(1.0, |unit| unit.scale);
+        self.values(payload).map(move |value| value.as_f64() * scale)
+    }
+}
+
+/// The fields of a message in the column order of exported tables, as ulog2csv
+/// writes them: `timestamp` first, then the others in encoded order
+pub fn column_order(fields: &[FieldInfo]) -> impl Iterator<Item = &FieldInfo> {
+    let is_timestamp = |field: &&FieldInfo| field.name == "timestamp";
+    fields.iter().filter(is_timestamp).chain(fields.iter().filter(move |field| !is_timestamp(field)))
+}
@@ -0,0 +1,196 @@
+//! Framing of uORB messages over plain byte slices, usable without `std`
+
+use core::fmt;
+
+use crate::{UorbHeader, UorbMessage, UORB_HEADER_LEN, UORB_MAGIC_V1};
+use crate::common::MAX_ENCODED_LEN;
+
+
+/// Reasons a frame could not be encoded or decoded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameError {
+    /// The destination buffer cannot hold the whole frame
+    BufferTooSmall { needed: usize, available: usize },
+    /// The payload could not be decoded as the message named by the header hash
+    InvalidPayload { hash: u16 },
+    /// The header announces a payload larger than any known message
+    Oversize { payload_len: usize, max: usize },
+    /// The payload length does not match the ENCODED_LEN of its message
+    LengthMismatch { hash: u16, payload_len: usize, expected: usize },
+    /// A packet is not validly byte-stuffed, or does not hold exactly one frame
+    InvalidEncoding,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FrameError::BufferTooSmall { needed, available } =>
+                write!(f, "frame needs {} bytes, buffer has {}", needed, available),
+            FrameError::InvalidPayload { hash } =>
+                write!(f, "msg hash: {}", hash),
+            FrameError::Oversize { payload_len, max } =>
+                write!(f, "payload_len {} exceeds max {}", payload_len, max),
+            FrameError::LengthMismatch { hash, payload_len, expected } =>
+                write!(f, "msg hash: {} payload_len {} expected {}", hash, payload_len, expected),
+            FrameError::InvalidEncoding =>
+                write!(f, "invalid packet encoding"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FrameError {}
+
+#[cfg(feature = "std")]
+impl From<FrameError> for std::io::Error {
+    fn from(err: FrameError) -> std::io::Error {
+        let kind = match err {
+            FrameError::Oversize { .. } |
+            FrameError::LengthMismatch { .. } |
+            FrameError::InvalidEncoding => std::io::ErrorKind::InvalidData,
+            _ => std::io::ErrorKind::InvalidInput,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
+
+/// Return the leading `frame_len` bytes of `buf`, if it is large enough
+pub(crate) fn frame_buf(buf: &mut [u8], frame_len: usize) -> Result<&mut [u8], FrameError> {
+    if buf.len() < frame_len {
+        return Err(FrameError::BufferTooSmall { needed: frame_len, available: buf.len() });
+    }
+    Ok(&mut buf[..frame_len])
+}
+
+
+/// How strictly a payload's length must match the ENCODED_LEN of its message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecodeMode {
+    /// Reject any payload whose length differs from ENCODED_LEN
+    Strict,
+    /// Accept payloads from newer or older definitions of the same message
+    Lenient,
+}
+
+/// How a decoded payload's length compared with the ENCODED_LEN of its message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PayloadFit {
+    /// The payload length matched exactly
+    Exact,
+    /// Trailing bytes, such as fields appended by a newer definition, were ignored
+    Trailing { extra: usize },
+    /// The payload was short, as from an older definition; missing fields were defaulted
+    Padded { missing: usize },
+}
+
+impl UorbMessage {
+    /// Parse a payload, checking its length against the message named by `hash_val`
+    pub fn parse_with_mode(hash_val: u16, payload: &[u8], mode: DecodeMode)
+        -> Result<(UorbMessage, PayloadFit), FrameError> {
+        let expected = UorbMessage::encoded_len_for_hash(hash_val)
+            .ok_or(FrameError::InvalidPayload { hash: hash_val })?;
+        decode_fitted(hash_val, payload, expected, mode,
+                      |payload| UorbMessage::parse(hash_val, payload))
+    }
+}
+
+/// Fit a payload to the `expected` length of its message according to `mode`,
+/// then decode it with `parse`
+pub(crate) fn decode_fitted<T, F>(hash_val: u16, payload: &[u8], expected: usize, mode: DecodeMode,
+                                  parse: F) -> Result<(T, PayloadFit), FrameError>
+    where F: FnOnce(&[u8]) -> Option<T> {
+    let payload_len = payload.len();
+    let fit = if payload_len > expected {
+        PayloadFit::Trailing { extra: payload_len - expected }
+    } else if payload_len < expected {
+        PayloadFit::Padded { missing: expected - payload_len }
+    } else {
+        PayloadFit::Exact
+    };
+
+    if mode == DecodeMode::Strict && fit != PayloadFit::Exact {
+        return Err(FrameError::LengthMismatch { hash: hash_val, payload_len, expected });
+    }
+
+    let msg = if let PayloadFit::Padded { .. } = fit {
+        // zeroed bytes decode to the default value of every field type
+        let mut padded = [0; MAX_ENCODED_LEN];
+        padded[..payload_len].copy_from_slice(payload);
+        parse(&padded[..expected])
+    } else {
+        parse(&payload[..expected])
+    };
+    msg.map(|msg| (msg, fit))
+        .ok_or(FrameError::InvalidPayload { hash: hash_val })
+}
+
+/// Check a decoded header against the largest payload of any known message.
+/// In Lenient mode a known message may be longer, as its trailing bytes are ignored.
+pub(crate) fn check_payload_len(header: &UorbHeader, mode: DecodeMode) -> Result<(), FrameError> {
+    let known = UorbMessage::encoded_len_for_hash(header.hash).is_some();
+    if header.payload_len > MAX_ENCODED_LEN && !(mode == DecodeMode::Lenient && known) {
+        return Err(FrameError::Oversize { payload_len: header.payload_len, max: MAX_ENCODED_LEN });
+    }
+    Ok(())
+}
+
+
+/// Decodes successive frames from a byte slice, such as a DMA receive buffer.
+///
+/// Bytes preceding a frame start are skipped. Iteration stops at the first
+/// incomplete frame, which is then available from `remaining()` so that it can
+/// be completed with more received bytes. An oversize frame is reported as an
+/// error and skipped, along with as much of its payload as the slice holds,
+/// unless it is a known message decoded in Lenient mode.
+#[derive(Debug, Clone)]
+pub struct FrameDecoder<'a> {
+    buf: &'a [u8],
+    mode: DecodeMode,
+}
+
+impl<'a> FrameDecoder<'a> {
+    /// Decode frames from `buf`, requiring each payload length to match strictly
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self::with_mode(buf, DecodeMode::Strict)
+    }
+
+    /// Decode frames from `buf`, fitting each payload to its message according to `mode`
+    pub fn with_mode(buf: &'a [u8], mode: DecodeMode) -> Self {
+        FrameDecoder { buf, mode }
+    }
+
+    /// The bytes that have not been consumed yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.buf
+    }
+}
+
+impl<'a> Iterator for FrameDecoder<'a> {
+    type Item = Result<(UorbHeader, UorbMessage), FrameError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // search for the magic framing value indicating start of the message
+        match self.buf.iter().position(|&b| b == UORB_MAGIC_V1) {
+            Some(start) => self.buf = &self.buf[start..],
+            None => {
+                self.buf = &[];
+                return None;
+            }
+        }
+
+        let header = UorbHeader::decode(self.buf)?;
+        let frame_len = UORB_HEADER_LEN + header.payload_len;
+        if let Err(err) = check_payload_len(&header, self.mode) {
+            self.buf = &self.buf[frame_len.min(self.buf.len())..];
+            return Some(Err(err));
+        }
+        if self.buf.len() < frame_len {
+            return None;
+        }
+        let payload = &self.buf[UORB_HEADER_LEN..frame_len];
+        self.buf = &self.buf[frame_len..];
+
+        Some(UorbMessage::parse_with_mode(header.hash, payload, self.mode)
+            .map(|(msg, _fit)| (header, msg)))
+    }
+}
@@ -0,0 +1,160 @@
+//! Limiting the rate of delivered messages by their header timestamps, like `orb_set_interval`
+
+use std::collections::HashMap;
+
+use crate::{UorbHeader, UorbMessage, UorbMsgMeta};
+
+
+/// Which message of each interval is delivered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntervalMode {
+    /// The first message of each interval, delivered as soon as it arrives
+    First,
+    /// The last message of each interval, delivered once a message from a later
+    /// interval arrives, or on `flush`
+    Latest,
+}
+
+/// The interval state of one topic instance, holding back messages of type `P`
+#[derive(Debug, Clone)]
+pub(crate) struct Interval<P> {
+    interval: u64,
+    mode: IntervalMode,
+    /// Timestamp at which the current interval ends, once a message has been seen
+    due: Option<u64>,
+    last_timestamp: u64,
+    pending: Option<(UorbHeader, P)>,
+}
+
+impl<P> Interval<P> {
+    pub(crate) fn new(interval: u64, mode: IntervalMode) -> Self {
+        Interval { interval, mode, due: None, last_timestamp: 0, pending: None }
+    }
+
+    /// Move `due` past `timestamp` in whole intervals, without drifting when
+    /// timestamps jitter, but restarting after a gap of more than one interval
+    fn advance(&mut self, timestamp: u64) {
+        self.due = match self.due {
+            Some(due) if timestamp < due + self.interval => Some(due + self.interval),
+            _ => Some(timestamp + self.interval),
+        };
+    }
+
+    /// Offer the next message, returning the message to deliver now, if any
+    pub(crate) fn push(&mut self, header: UorbHeader, msg: P) -> Option<(UorbHeader, P)> {
+        let timestamp = header.timestamp;
+        if timestamp < self.last_timestamp {
+            // the stream restarted, as when a replay loops
+            self.due = None;
+        }
+        self.last_timestamp = timestamp;
+        let elapsed = self.due.is_none_or(|due| timestamp >= due);
+
+        match self.mode {
+            IntervalMode::First => {
+                if !elapsed {
+                    return None;
+                }
+                self.advance(timestamp);
+                Some((header, msg))
+            }
+            IntervalMode::Latest => {
+                let released = if elapsed {
+                    self.advance(timestamp);
+                    self.pending.take()
+                } else {
+                    None
+                };
+                self.pending = Some((header, msg));
+                released
+            }
+        }
+    }
+}
+
+
+/// Delivers at most one message per interval for each configured topic instance,
+/// keyed by MSG_HASH_CODE and instance ID. Other messages pass through unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct IntervalFilter {
+    intervals: HashMap<(u16, u8), Interval<UorbMessage>>,
+}
+
+impl IntervalFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deliver at most one message every `interval` microseconds for the topic
+    /// instance with message hash `hash`
+    pub fn set_interval(&mut self, hash: u16, instance: u8, interval: u64, mode: IntervalMode) {
+        self.intervals.insert((hash, instance), Interval::new(interval, mode));
+    }
+
+    /// Deliver at most one `T` every `interval` microseconds from instance `instance`
+    pub fn set_interval_for<T: UorbMsgMeta>(&mut self, instance: u8, interval: u64, mode: IntervalMode) {
+        self.set_interval(T::MSG_HASH_CODE, instance, interval, mode);
+    }
+
+    /// Deliver every message of a topic instance again, dropping any pending message
+    pub fn clear_interval(&mut self, hash: u16, instance: u8) {
+        self.intervals.remove(&(hash, instance));
+    }
+
+    /// Offer the next message of a stream, returning the message to deliver now, if any
+    pub fn push(&mut self, header: UorbHeader, msg: UorbMessage) -> Option<(UorbHeader, UorbMessage)> {
+        match self.intervals.get_mut(&(header.hash, header.instance_id)) {
+            Some(interval) => interval.push(header, msg),
+            None => Some((header, msg)),
+        }
+    }
+
+    /// Take the messages still held back in `IntervalMode::Latest`, in timestamp order
+    pub fn flush(&mut self) -> Vec<(UorbHeader, UorbMessage)> {
+        let mut pending: Vec<(UorbHeader, UorbMessage)> = self.intervals.values_mut()
+            .filter_map(|interval| interval.pending.take())
+            .collect();
+        pending.sort_by_key(|(header, _)| header.timestamp);
+        pending
+    }
+
+    /// Filter a stream of decoded messages, such as from a `UorbReader`,
+    /// flushing held back messages when the stream ends
+    pub fn throttle<I>(self, iter: I) -> Throttled<I> {
+        Throttled { filter: self, iter, flushed: None }
+    }
+}
+
+
+/// Iterator returned by `IntervalFilter::throttle`
+#[derive(Debug)]
+pub struct Throttled<I> {
+    filter: IntervalFilter,
+    iter: I,
+    flushed: Option<std::vec::IntoIter<(UorbHeader, UorbMessage)>>,
+}
+
+impl<I, E> Iterator for Throttled<I>
+    where I: Iterator<Item = Result<(UorbHeader, UorbMessage), E>> {
+    type Item = Result<(UorbHeader, UorbMessage), E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(flushed) = &mut self.flushed {
+            return flushed.next().map(Ok);
+        }
+        for res in &mut self.iter {
+            match res {
+                Ok((header, msg)) => {
+                    if let Some(delivered) = self.filter.push(header, msg) {
+                        return Some(Ok(delivered));
+                    }
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        let mut flushed = self.filter.flush().into_iter();
+        let next = flushed.next().map(Ok);
+        self.flushed = Some(flushed);
+        next
+    }
+}
@@ -1,21 +1,96 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "std")]
 extern crate bytes;
 extern crate byteorder;
-
-use std::io::{ Error, ErrorKind, Read, Result, Write};
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "serial")]
+extern crate libc;
+
+#[cfg(feature = "std")]
+use std::io::{ Read, Result, Write};
+#[cfg(feature = "std")]
+use byteorder::ReadBytesExt;
+use byteorder::{BigEndian, ByteOrder};
+#[cfg(feature = "std")]
+use bytes::BufMut;
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "std")]
+pub mod bridge;
+#[cfg(feature = "std")]
+pub mod bus;
+pub mod cdr;
+#[cfg(feature = "std")]
+pub mod clock;
+use self::cdr::{CdrEndian, CdrReader, CdrWriter, CDR_HEADER_LEN};
+#[cfg(feature = "std")]
+pub mod csv;
+#[cfg(feature = "std")]
+pub mod demux;
+#[cfg(feature = "std")]
+pub use self::demux::TopicDemux;
+#[cfg(feature = "std")]
+pub use self::bridge::Bridge;
+pub mod encoding;
+pub mod field;
+pub use self::field::{FieldInfo, FieldType, FieldUnit, FieldValue};
+pub mod frame;
+#[cfg(feature = "std")]
+pub mod interval;
+pub use self::frame::{DecodeMode, FrameDecoder, FrameError, PayloadFit};
+#[cfg(feature = "mavlink")]
+pub mod mavlink;
+
+#[cfg(feature = "std")]
+pub mod reader;
+#[cfg(feature = "std")]
+pub use self::reader::UorbReader;
+#[cfg(feature = "std")]
+pub mod recording;
+#[cfg(feature = "std")]
+pub use self::recording::{Player, Recorder};
+#[cfg(feature = "std")]
+pub mod rtps;
+#[cfg(all(feature = "serde", feature = "std"))]
+#[doc(hidden)]
+pub mod serde_arrays;
+#[cfg(all(feature = "serial", unix))]
+pub mod serial;
+#[cfg(feature = "std")]
+pub mod timesync;
+#[cfg(feature = "std")]
+pub use self::timesync::Timesync;
+#[cfg(feature = "std")]
+pub mod udp;
+#[cfg(feature = "std")]
+pub mod ulog;
+#[cfg(feature = "std")]
+pub use self::udp::UdpTransport;
 
 /// Compatible protocol version number, used for framing messages on the wire
 pub const UORB_MAGIC_V1: u8 = 0xAA;
 
+/// Size of the encoded UorbHeader that precedes every payload on the wire
+pub const UORB_HEADER_LEN: usize = 14;
+
+/// Size of the largest frame (header plus payload) for any known message
+pub const MAX_FRAME_LEN: usize = UORB_HEADER_LEN + common::MAX_ENCODED_LEN;
+
 
 pub trait UorbMsgMeta {
     const ENCODED_LEN: usize;
     const MSG_HASH_CODE: u16;
     const MSG_RAW_NAME: &'static str;
+    /// Layout of every field within the encoded payload, in encoded order
+    const FIELDS: &'static [FieldInfo];
+    /// Size of the CDR encoding, without an encapsulation header
+    const CDR_ENCODED_LEN: usize;
 
     fn get_hash_code(&self) ->u16 { Self::MSG_HASH_CODE}
 
+    fn encoded_len(&self) -> usize { Self::ENCODED_LEN }
+
     /// Create a UorbHeader for this data with the given uORB instance ID
     fn header_for_instance(&self, instance_id: u8, timestamp: u64) -> UorbHeader {
         UorbHeader {
@@ -35,30 +110,102 @@ pub trait UorbMsgMeta {
     }
 
     /// serialize this data as bytes
-    fn ser(&self) -> Vec<u8>;
+    #[cfg(feature = "std")]
+    fn ser(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(Self::ENCODED_LEN);
+        self.ser_into(&mut buf);
+        buf
+    }
+
+    /// serialize this data into the given buffer, without allocating
+    #[cfg(feature = "std")]
+    fn ser_into<B: BufMut>(&self, buf: &mut B);
+
+    /// serialize this data into the start of a byte slice, returning the number
+    /// of bytes written, or None if the slice is shorter than ENCODED_LEN
+    fn ser_into_slice(&self, buf: &mut [u8]) -> Option<usize>;
+
+    /// deserialize this data from the start of a payload, or None if it is shorter than ENCODED_LEN
+    fn deser(input: &[u8]) -> Option<Self> where Self: Sized;
+
+    /// Encode a complete frame (header and payload) for this data into `buf`,
+    /// returning the number of bytes written
+    fn encode_frame_into(&self, instance_id: u8, timestamp: u64, buf: &mut [u8])
+        -> core::result::Result<usize, FrameError> {
+        let header = self.header_for_instance(instance_id, timestamp);
+        let frame_len = UORB_HEADER_LEN + Self::ENCODED_LEN;
+        let frame = frame::frame_buf(buf, frame_len)?;
+        header.encode_into(frame, Self::ENCODED_LEN);
+        self.ser_into_slice(&mut frame[UORB_HEADER_LEN..]);
+        Ok(frame_len)
+    }
+
+    /// Write the fields as CDR in declaration order, as ROS 2 and the microRTPS
+    /// bridge do, without an encapsulation header. None if the buffer is too short.
+    fn ser_cdr_body(&self, w: &mut CdrWriter) -> Option<()>;
+
+    /// Read the fields as CDR in declaration order, without an encapsulation header
+    fn deser_cdr_body(r: &mut CdrReader) -> Option<Self> where Self: Sized;
+
+    /// serialize this data as CDR with an encapsulation header into the start of a
+    /// byte slice, returning the number of bytes written, or None if it is too short
+    fn ser_cdr_into_slice(&self, buf: &mut [u8], endian: CdrEndian) -> Option<usize> {
+        if buf.len() < CDR_HEADER_LEN {
+            return None;
+        }
+        let (header, body) = buf.split_at_mut(CDR_HEADER_LEN);
+        header.copy_from_slice(&cdr::encapsulation_header(endian));
+        let mut w = CdrWriter::new(body, endian);
+        self.ser_cdr_body(&mut w)?;
+        Some(CDR_HEADER_LEN + w.position())
+    }
 
+    /// serialize this data as CDR with an encapsulation header
+    #[cfg(feature = "std")]
+    fn ser_cdr(&self, endian: CdrEndian) -> Vec<u8> {
+        let mut buf = vec![0; CDR_HEADER_LEN + Self::CDR_ENCODED_LEN];
+        let len = self.ser_cdr_into_slice(&mut buf, endian).unwrap();
+        buf.truncate(len);
+        buf
+    }
 
+    /// deserialize this data from CDR with an encapsulation header, in either byte order
+    fn deser_cdr(input: &[u8]) -> Option<Self> where Self: Sized {
+        let endian = cdr::decode_encapsulation_header(input)?;
+        Self::deser_cdr_body(&mut CdrReader::new(&input[CDR_HEADER_LEN..], endian))
+    }
 
     /// upcast this inner data to the corresponding UorbMessage
     fn wrap(&self) -> UorbMessage;
 
+    /// take this inner data out of a UorbMessage, or None if it holds another message
+    fn unwrap_message(msg: UorbMessage) -> Option<Self> where Self: Sized;
+
 }
 
 
 
 // import code generated by parser at build time
+#[allow(clippy::field_reassign_with_default)]
 pub mod common {
-    use crate::UorbMsgMeta;
-    use bytes::{Buf, BufMut, Bytes, IntoBuf};
+    use crate::cdr::{CdrReader, CdrWriter};
+    use crate::{FieldInfo, FieldType, FieldUnit, UorbMsgMeta};
+    use byteorder::{ByteOrder, LittleEndian};
+    #[cfg(feature = "std")]
+    use bytes::BufMut;
+    #[cfg(feature = "serde")]
+    use serde::{Deserialize, Serialize};
 
     include!(concat!(env!("OUT_DIR"), "/common.rs"));
 }
 
 /// import all the message definitions
 pub use self::common::UorbMessage as UorbMessage;
+pub use self::common::UorbMessageView as UorbMessageView;
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UorbHeader {
     /// indicates which version of the header we are sending
     pub version: u8,
@@ -72,69 +219,139 @@ pub struct UorbHeader {
     pub payload_len: usize,
 }
 
+impl UorbHeader {
+    /// Decode a header from the start of `buf`, which must begin with the magic framing value
+    pub fn decode(buf: &[u8]) -> Option<UorbHeader> {
+        if buf.len() < UORB_HEADER_LEN || buf[0] != UORB_MAGIC_V1 {
+            return None;
+        }
+        Some(UorbHeader {
+            version: UORB_MAGIC_V1,
+            hash: BigEndian::read_u16(&buf[1..]),
+            timestamp: BigEndian::read_u64(&buf[3..]),
+            instance_id: buf[11],
+            payload_len: BigEndian::read_u16(&buf[12..]) as usize,
+        })
+    }
 
+    /// Encode this header, with the given payload length, into the start of `buf`
+    fn encode_into(&self, buf: &mut [u8], payload_len: usize) {
+        buf[0] = self.version;
+        BigEndian::write_u16(&mut buf[1..], self.hash);
+        BigEndian::write_u64(&mut buf[3..], self.timestamp);
+        buf[11] = self.instance_id;
+        BigEndian::write_u16(&mut buf[12..], payload_len as u16);
+    }
+}
 
-/// Utilities for sending and receiving uORB via a reliable transport
 
 
-pub fn write_msg<W: Write>(w: &mut W, header: &UorbHeader, data: &UorbMessage) -> Result<()> {
-    let payload = data.ser();
+impl UorbMessage {
+    /// Encode a complete frame (header and payload) for this message into `buf`,
+    /// returning the number of bytes written.
+    /// The header's payload_len must match the encoded length of this message.
+    pub fn encode_frame_into(&self, header: &UorbHeader, buf: &mut [u8])
+        -> core::result::Result<usize, FrameError> {
+        let payload_len = self.encoded_len();
+        if header.payload_len != payload_len {
+            return Err(FrameError::LengthMismatch {
+                hash: header.hash, payload_len: header.payload_len, expected: payload_len });
+        }
+        let frame_len = UORB_HEADER_LEN + payload_len;
+        let frame = frame::frame_buf(buf, frame_len)?;
+        header.encode_into(frame, payload_len);
+        self.ser_into_slice(&mut frame[UORB_HEADER_LEN..]);
+        Ok(frame_len)
+    }
+}
 
-    w.write_u8(header.version)?;
-    w.write_u16::<BigEndian>(header.hash)?;
-    w.write_u64::<BigEndian>(header.timestamp)?;
-    w.write_u8(header.instance_id)?;
-    w.write_u16::<BigEndian>(payload.len() as u16)?;
 
-    w.write_all(&payload[..])?;
+// Utilities for sending and receiving uORB via a reliable transport
 
-    Ok(())
+
+#[cfg(feature = "std")]
+pub fn write_msg<W: Write>(w: &mut W, header: &UorbHeader, data: &UorbMessage) -> Result<()> {
+    // encode on the stack so the frame goes out in a single write
+    let mut frame = [0; MAX_FRAME_LEN];
+    let frame_len = data.encode_frame_into(header, &mut frame)?;
+    w.write_all(&frame[..frame_len])
 }
 
 
+/// Read the next frame, requiring its payload length to match strictly
+#[cfg(feature = "std")]
 pub fn read_msg<R: Read>(r: &mut R) -> Result<(UorbHeader, UorbMessage)> {
+    read_msg_with_mode(r, DecodeMode::Strict).map(|(header, msg, _fit)| (header, msg))
+}
 
-    loop {
-        // search for the magic framing value indicating start of the message
-        if r.read_u8()? != UORB_MAGIC_V1 {
-            continue;
-        }
-        let hash:u16 = r.read_u16::<BigEndian>()?;
-        let timestamp:u64 = r.read_u64::<BigEndian>()?;
-        let instance_id = r.read_u8()?;
-        let payload_len:usize =  r.read_u16::<BigEndian>()? as usize;
 
-        let header = UorbHeader {
-            version: UORB_MAGIC_V1,
-            hash,
-            timestamp,
-            instance_id,
-            payload_len,
-        };
+/// Read the next frame, reporting how its payload length fit the message definition
+#[cfg(feature = "std")]
+pub fn read_msg_with_mode<R: Read>(r: &mut R, mode: DecodeMode)
+    -> Result<(UorbHeader, UorbMessage, PayloadFit)> {
+    let header = read_header(r)?;
+    // sized for the largest known message
+    let mut payload_buf = [0; common::MAX_ENCODED_LEN];
+    let expected = UorbMessage::encoded_len_for_hash(header.hash);
+    let (payload, skipped) = read_payload(r, &header, expected, mode, &mut payload_buf)?;
+
+    //println!("parse {} len {}", header.hash, payload.len());
+    let (msg, fit) = UorbMessage::parse_with_mode(header.hash, payload, mode)?;
+    let fit = if skipped > 0 { PayloadFit::Trailing { extra: skipped } } else { fit };
+    Ok((header, msg, fit))
+}
 
-        let mut payload_buf = [0; 255];
-        if payload_len > 255 {
-            //println!("payload_len excessive: {}", payload_len);
-            continue;
-        }
-        let payload = &mut payload_buf[..payload_len.into()];
-        r.read_exact(payload)?;
-
-        //println!("parse {} len {}", header.hash, payload_len);
-        if let Some(msg) = UorbMessage::parse(header.hash, payload) {
-            return Ok((header, msg));
-        }
-        else {
-            let err = Error::new(ErrorKind::InvalidInput,
-                                     format!("msg hash: {}",  header.hash));
-            return Err(err);
-        }
-    }
 
+/// Read up to and including the next header, skipping bytes before the magic framing value
+#[cfg(feature = "std")]
+pub(crate) fn read_header<R: Read>(r: &mut R) -> Result<UorbHeader> {
+    // search for the magic framing value indicating start of the message
+    while r.read_u8()? != UORB_MAGIC_V1 {}
+    let mut header_buf = [UORB_MAGIC_V1; UORB_HEADER_LEN];
+    r.read_exact(&mut header_buf[1..])?;
+    Ok(UorbHeader::decode(&header_buf).unwrap())
 }
 
 
+/// Read the payload that follows `header` into `buf`, which must hold MAX_ENCODED_LEN bytes.
+/// In Lenient mode, only the `expected` length of a known message is kept, and the number
+/// of trailing bytes skipped after it is returned as well.
+#[cfg(feature = "std")]
+pub(crate) fn read_payload<'b, R: Read>(r: &mut R, header: &UorbHeader, expected: Option<usize>,
+                                        mode: DecodeMode, buf: &'b mut [u8])
+    -> Result<(&'b [u8], usize)> {
+    if let (DecodeMode::Lenient, Some(expected)) = (mode, expected) {
+        if header.payload_len > expected {
+            let payload = &mut buf[..expected];
+            r.read_exact(payload)?;
+            let extra = header.payload_len - expected;
+            skip_bytes(r, extra)?;
+            return Ok((payload, extra));
+        }
+    }
+    if let Err(err) = frame::check_payload_len(header, mode) {
+        // skip the whole payload, so the next read starts after this frame
+        skip_payload(r, header)?;
+        return Err(err.into());
+    }
+    let payload = &mut buf[..header.payload_len];
+    r.read_exact(payload)?;
+    Ok((payload, 0))
+}
 
 
+/// Discard the payload that follows `header`
+#[cfg(feature = "std")]
+pub(crate) fn skip_payload<R: Read>(r: &mut R, header: &UorbHeader) -> Result<()> {
+    skip_bytes(r, header.payload_len)
+}
 
-
+#[cfg(feature = "std")]
+fn skip_bytes<R: Read>(r: &mut R, len: usize) -> Result<()> {
+    let len = len as u64;
+    let skipped = std::io::copy(&mut r.take(len), &mut std::io::sink())?;
+    if skipped < len {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "truncated payload"));
+    }
+    Ok(())
+}
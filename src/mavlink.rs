@@ -0,0 +1,508 @@
+//! Conversions between common messages and their MAVLink counterparts, for ground stations
+//!
+//! Units follow the comments in the .msg files on the uORB side, and the MAVLink
+//! common dialect on the other: for example GPS speed is m/s in `vel_m_s` but cm/s
+//! in GPS_RAW_INT `vel`. MAVLink "unknown" markers such as UINT16_MAX become NaN
+//! or -1, as the .msg comments describe.
+
+// MAVLink 2 extension fields appear when another crate enables them
+#![allow(clippy::needless_update)]
+
+use ::mavlink::dialects::common::{
+    GpsFixType, MavAutopilot, MavBatteryFunction, MavBatteryType, MavMessage, MavModeFlag, MavState,
+    MavSysStatusSensor, MavType, ATTITUDE_QUATERNION_DATA, BATTERY_STATUS_DATA, GPS2_RAW_DATA,
+    GPS_RAW_INT_DATA, HEARTBEAT_DATA, SERVO_OUTPUT_RAW_DATA, SYS_STATUS_DATA,
+};
+use num_traits::FromPrimitive;
+
+use crate::common::{
+    ActuatorOutputsData, BatteryStatusData, UorbMessage, VehicleAttitudeData, VehicleGpsPositionData,
+    VehicleStatusData,
+};
+use crate::{UorbHeader, UorbMsgMeta};
+
+
+/// PX4 main modes, in bits 16..24 of the HEARTBEAT custom mode
+mod main_mode {
+    pub const MANUAL: u8 = 1;
+    pub const ALTCTL: u8 = 2;
+    pub const POSCTL: u8 = 3;
+    pub const AUTO: u8 = 4;
+    pub const ACRO: u8 = 5;
+    pub const OFFBOARD: u8 = 6;
+    pub const STABILIZED: u8 = 7;
+    pub const RATTITUDE: u8 = 8;
+}
+
+/// PX4 auto sub modes, in bits 24..32 of the HEARTBEAT custom mode
+mod auto_mode {
+    pub const TAKEOFF: u8 = 2;
+    pub const LOITER: u8 = 3;
+    pub const MISSION: u8 = 4;
+    pub const RTL: u8 = 5;
+    pub const LAND: u8 = 6;
+    pub const RTGS: u8 = 7;
+    pub const FOLLOW_TARGET: u8 = 8;
+    pub const PRECLAND: u8 = 9;
+}
+
+/// Outputs per SERVO_OUTPUT_RAW port
+const SERVOS_PER_PORT: usize = 8;
+
+
+/// A float scaled into a u16, or UINT16_MAX if it is not finite
+fn to_u16_or_max(value: f32, scale: f32) -> u16 {
+    if value.is_finite() {
+        (value * scale).round().clamp(0.0, (u16::MAX - 1) as f32) as u16
+    } else {
+        u16::MAX
+    }
+}
+
+/// A u16 scaled back into a float, or NaN if it is UINT16_MAX
+fn from_u16_or_max(value: u16, scale: f32) -> f32 {
+    if value == u16::MAX {
+        f32::NAN
+    } else {
+        value as f32 / scale
+    }
+}
+
+/// Course over ground in radians, as centidegrees in 0..36000
+fn cog_cdeg(cog_rad: f32) -> u16 {
+    if !cog_rad.is_finite() {
+        return u16::MAX;
+    }
+    let cdeg = (cog_rad.to_degrees().rem_euclid(360.0) * 100.0).round() as u16;
+    cdeg % 36000
+}
+
+/// Centidegrees in 0..36000 as a course over ground in radians, in -PI..PI
+fn cog_rad(cog_cdeg: u16) -> f32 {
+    if cog_cdeg == u16::MAX {
+        return f32::NAN;
+    }
+    let deg = cog_cdeg as f32 / 100.0;
+    if deg > 180.0 { deg - 360.0 } else { deg }.to_radians()
+}
+
+
+/// GPS_RAW_INT from the primary GPS; HDOP and VDOP go in `eph` and `epv`
+pub fn gps_raw_int(gps: &VehicleGpsPositionData) -> GPS_RAW_INT_DATA {
+    GPS_RAW_INT_DATA {
+        time_usec: gps.timestamp,
+        lat: gps.lat,
+        lon: gps.lon,
+        alt: gps.alt,
+        eph: to_u16_or_max(gps.hdop, 100.0),
+        epv: to_u16_or_max(gps.vdop, 100.0),
+        vel: to_u16_or_max(gps.vel_m_s, 100.0),
+        cog: cog_cdeg(gps.cog_rad),
+        fix_type: GpsFixType::from_u8(gps.fix_type).unwrap_or_default(),
+        satellites_visible: gps.satellites_used,
+        ..Default::default()
+    }
+}
+
+pub fn from_gps_raw_int(raw: &GPS_RAW_INT_DATA) -> VehicleGpsPositionData {
+    VehicleGpsPositionData {
+        timestamp: raw.time_usec,
+        lat: raw.lat,
+        lon: raw.lon,
+        alt: raw.alt,
+        hdop: from_u16_or_max(raw.eph, 100.0),
+        vdop: from_u16_or_max(raw.epv, 100.0),
+        vel_m_s: from_u16_or_max(raw.vel, 100.0),
+        cog_rad: cog_rad(raw.cog),
+        fix_type: raw.fix_type as u8,
+        satellites_used: raw.satellites_visible,
+        ..Default::default()
+    }
+}
+
+/// GPS2_RAW from the secondary GPS, with the same units as GPS_RAW_INT
+pub fn gps2_raw(gps: &VehicleGpsPositionData) -> GPS2_RAW_DATA {
+    let raw = gps_raw_int(gps);
+    GPS2_RAW_DATA {
+        time_usec: raw.time_usec,
+        lat: raw.lat,
+        lon: raw.lon,
+        alt: raw.alt,
+        eph: raw.eph,
+        epv: raw.epv,
+        vel: raw.vel,
+        cog: raw.cog,
+        fix_type: raw.fix_type,
+        satellites_visible: raw.satellites_visible,
+        ..Default::default()
+    }
+}
+
+pub fn from_gps2_raw(raw: &GPS2_RAW_DATA) -> VehicleGpsPositionData {
+    from_gps_raw_int(&GPS_RAW_INT_DATA {
+        time_usec: raw.time_usec,
+        lat: raw.lat,
+        lon: raw.lon,
+        alt: raw.alt,
+        eph: raw.eph,
+        epv: raw.epv,
+        vel: raw.vel,
+        cog: raw.cog,
+        fix_type: raw.fix_type,
+        satellites_visible: raw.satellites_visible,
+        ..Default::default()
+    })
+}
+
+
+/// ATTITUDE_QUATERNION, whose timestamp is in milliseconds since boot
+pub fn attitude_quaternion(attitude: &VehicleAttitudeData) -> ATTITUDE_QUATERNION_DATA {
+    ATTITUDE_QUATERNION_DATA {
+        time_boot_ms: (attitude.timestamp / 1000) as u32,
+        q1: attitude.q[0],
+        q2: attitude.q[1],
+        q3: attitude.q[2],
+        q4: attitude.q[3],
+        rollspeed: attitude.rollspeed,
+        pitchspeed: attitude.pitchspeed,
+        yawspeed: attitude.yawspeed,
+        ..Default::default()
+    }
+}
+
+pub fn from_attitude_quaternion(att: &ATTITUDE_QUATERNION_DATA) -> VehicleAttitudeData {
+    VehicleAttitudeData {
+        timestamp: att.time_boot_ms as u64 * 1000,
+        q: [att.q1, att.q2, att.q3, att.q4],
+        rollspeed: att.rollspeed,
+        pitchspeed: att.pitchspeed,
+        yawspeed: att.yawspeed,
+        ..Default::default()
+    }
+}
+
+
+/// BATTERY_STATUS for the battery with the given ID. Cell voltages are in mV;
+/// without a cell count, the whole battery voltage goes in the first cell.
+pub fn battery_status(battery: &BatteryStatusData, id: u8) -> BATTERY_STATUS_DATA {
+    let mut voltages = [u16::MAX; 10];
+    let cell_count = battery.cell_count.clamp(0, voltages.len() as i32) as usize;
+    if cell_count == 0 {
+        voltages[0] = to_u16_or_max(battery.voltage_v, 1000.0);
+    }
+    for (cell, voltage) in voltages.iter_mut().take(cell_count).enumerate() {
+        let cell_v = match battery.voltage_cell_v.get(cell) {
+            Some(&cell_v) if cell_v > 0.0 => cell_v,
+            _ => battery.voltage_v / cell_count as f32,
+        };
+        *voltage = to_u16_or_max(cell_v, 1000.0);
+    }
+    BATTERY_STATUS_DATA {
+        id,
+        battery_function: MavBatteryFunction::MAV_BATTERY_FUNCTION_ALL,
+        mavtype: MavBatteryType::MAV_BATTERY_TYPE_LIPO,
+        temperature: if battery.temperature.is_finite() {
+            (battery.temperature * 100.0).round() as i16
+        } else {
+            i16::MAX
+        },
+        voltages,
+        current_battery: if battery.current_a == -1.0 { -1 } else { (battery.current_a * 100.0).round() as i16 },
+        current_consumed: battery.discharged_mah.round() as i32,
+        energy_consumed: -1,
+        battery_remaining: if battery.remaining < 0.0 { -1 } else { (battery.remaining * 100.0).round() as i8 },
+        ..Default::default()
+    }
+}
+
+pub fn from_battery_status(status: &BATTERY_STATUS_DATA) -> BatteryStatusData {
+    let cells: Vec<f32> = status.voltages.iter()
+        .take_while(|&&mv| mv != u16::MAX)
+        .map(|&mv| mv as f32 / 1000.0)
+        .collect();
+    let mut battery = BatteryStatusData {
+        voltage_v: cells.iter().sum(),
+        current_a: if status.current_battery == -1 { -1.0 } else { status.current_battery as f32 / 100.0 },
+        discharged_mah: status.current_consumed as f32,
+        remaining: if status.battery_remaining < 0 { -1.0 } else { status.battery_remaining as f32 / 100.0 },
+        temperature: if status.temperature == i16::MAX { f32::NAN } else { status.temperature as f32 / 100.0 },
+        ..Default::default()
+    };
+    // a single value is the whole battery voltage
+    if cells.len() > 1 {
+        battery.cell_count = cells.len() as i32;
+        for (cell_v, &voltage) in battery.voltage_cell_v.iter_mut().zip(cells.iter()) {
+            *cell_v = voltage;
+        }
+    }
+    battery.voltage_filtered_v = battery.voltage_v;
+    battery.current_filtered_a = battery.current_a;
+    battery.connected = battery.voltage_v > 0.0;
+    battery
+}
+
+
+/// The PX4 custom mode for a navigation state, as (main mode, sub mode)
+fn custom_mode(nav_state: u8) -> (u8, u8) {
+    match nav_state {
+        VehicleStatusData::NAVIGATION_STATE_MANUAL | VehicleStatusData::NAVIGATION_STATE_TERMINATION =>
+            (main_mode::MANUAL, 0),
+        VehicleStatusData::NAVIGATION_STATE_ALTCTL => (main_mode::ALTCTL, 0),
+        VehicleStatusData::NAVIGATION_STATE_POSCTL | VehicleStatusData::NAVIGATION_STATE_ORBIT =>
+            (main_mode::POSCTL, 0),
+        VehicleStatusData::NAVIGATION_STATE_AUTO_MISSION => (main_mode::AUTO, auto_mode::MISSION),
+        VehicleStatusData::NAVIGATION_STATE_AUTO_LOITER => (main_mode::AUTO, auto_mode::LOITER),
+        VehicleStatusData::NAVIGATION_STATE_AUTO_RTL => (main_mode::AUTO, auto_mode::RTL),
+        VehicleStatusData::NAVIGATION_STATE_AUTO_RCRECOVER | VehicleStatusData::NAVIGATION_STATE_AUTO_RTGS =>
+            (main_mode::AUTO, auto_mode::RTGS),
+        VehicleStatusData::NAVIGATION_STATE_AUTO_LANDENGFAIL
+        | VehicleStatusData::NAVIGATION_STATE_AUTO_LANDGPSFAIL
+        | VehicleStatusData::NAVIGATION_STATE_DESCEND
+        | VehicleStatusData::NAVIGATION_STATE_AUTO_LAND => (main_mode::AUTO, auto_mode::LAND),
+        VehicleStatusData::NAVIGATION_STATE_ACRO => (main_mode::ACRO, 0),
+        VehicleStatusData::NAVIGATION_STATE_OFFBOARD => (main_mode::OFFBOARD, 0),
+        VehicleStatusData::NAVIGATION_STATE_STAB => (main_mode::STABILIZED, 0),
+        VehicleStatusData::NAVIGATION_STATE_RATTITUDE => (main_mode::RATTITUDE, 0),
+        VehicleStatusData::NAVIGATION_STATE_AUTO_TAKEOFF => (main_mode::AUTO, auto_mode::TAKEOFF),
+        VehicleStatusData::NAVIGATION_STATE_AUTO_FOLLOW_TARGET => (main_mode::AUTO, auto_mode::FOLLOW_TARGET),
+        VehicleStatusData::NAVIGATION_STATE_AUTO_PRECLAND => (main_mode::AUTO, auto_mode::PRECLAND),
+        _ => (0, 0),
+    }
+}
+
+/// The navigation state for a PX4 custom mode; states that share a mode come back as the most common one
+fn nav_state(main: u8, sub: u8) -> Option<u8> {
+    let nav_state = match (main, sub) {
+        (main_mode::MANUAL, _) => VehicleStatusData::NAVIGATION_STATE_MANUAL,
+        (main_mode::ALTCTL, _) => VehicleStatusData::NAVIGATION_STATE_ALTCTL,
+        (main_mode::POSCTL, _) => VehicleStatusData::NAVIGATION_STATE_POSCTL,
+        (main_mode::AUTO, auto_mode::MISSION) => VehicleStatusData::NAVIGATION_STATE_AUTO_MISSION,
+        (main_mode::AUTO, auto_mode::LOITER) => VehicleStatusData::NAVIGATION_STATE_AUTO_LOITER,
+        (main_mode::AUTO, auto_mode::RTL) => VehicleStatusData::NAVIGATION_STATE_AUTO_RTL,
+        (main_mode::AUTO, auto_mode::RTGS) => VehicleStatusData::NAVIGATION_STATE_AUTO_RTGS,
+        (main_mode::AUTO, auto_mode::LAND) => VehicleStatusData::NAVIGATION_STATE_AUTO_LAND,
+        (main_mode::AUTO, auto_mode::TAKEOFF) => VehicleStatusData::NAVIGATION_STATE_AUTO_TAKEOFF,
+        (main_mode::AUTO, auto_mode::FOLLOW_TARGET) => VehicleStatusData::NAVIGATION_STATE_AUTO_FOLLOW_TARGET,
+        (main_mode::AUTO, auto_mode::PRECLAND) => VehicleStatusData::NAVIGATION_STATE_AUTO_PRECLAND,
+        (main_mode::ACRO, _) => VehicleStatusData::NAVIGATION_STATE_ACRO,
+        (main_mode::OFFBOARD, _) => VehicleStatusData::NAVIGATION_STATE_OFFBOARD,
+        (main_mode::STABILIZED, _) => VehicleStatusData::NAVIGATION_STATE_STAB,
+        (main_mode::RATTITUDE, _) => VehicleStatusData::NAVIGATION_STATE_RATTITUDE,
+        _ => return None,
+    };
+    Some(nav_state)
+}
+
+/// HEARTBEAT of a PX4 autopilot, with the navigation state as its custom mode
+pub fn heartbeat(status: &VehicleStatusData) -> HEARTBEAT_DATA {
+    let armed = status.arming_state == VehicleStatusData::ARMING_STATE_ARMED;
+    let mut base_mode = MavModeFlag::MAV_MODE_FLAG_CUSTOM_MODE_ENABLED;
+    if armed {
+        base_mode |= MavModeFlag::MAV_MODE_FLAG_SAFETY_ARMED;
+    }
+    if status.hil_state == VehicleStatusData::HIL_STATE_ON {
+        base_mode |= MavModeFlag::MAV_MODE_FLAG_HIL_ENABLED;
+    }
+    let (main, sub) = custom_mode(status.nav_state);
+    base_mode |= match main {
+        main_mode::MANUAL | main_mode::ACRO | main_mode::RATTITUDE =>
+            MavModeFlag::MAV_MODE_FLAG_MANUAL_INPUT_ENABLED,
+        main_mode::ALTCTL | main_mode::POSCTL | main_mode::STABILIZED =>
+            MavModeFlag::MAV_MODE_FLAG_MANUAL_INPUT_ENABLED | MavModeFlag::MAV_MODE_FLAG_STABILIZE_ENABLED,
+        main_mode::AUTO | main_mode::OFFBOARD =>
+            MavModeFlag::MAV_MODE_FLAG_AUTO_ENABLED | MavModeFlag::MAV_MODE_FLAG_STABILIZE_ENABLED
+            | MavModeFlag::MAV_MODE_FLAG_GUIDED_ENABLED,
+        _ => MavModeFlag::empty(),
+    };
+
+    let system_status = if status.nav_state == VehicleStatusData::NAVIGATION_STATE_TERMINATION {
+        MavState::MAV_STATE_FLIGHT_TERMINATION
+    } else if status.failsafe || status.arming_state == VehicleStatusData::ARMING_STATE_STANDBY_ERROR {
+        MavState::MAV_STATE_CRITICAL
+    } else {
+        match status.arming_state {
+            VehicleStatusData::ARMING_STATE_INIT => MavState::MAV_STATE_BOOT,
+            VehicleStatusData::ARMING_STATE_ARMED | VehicleStatusData::ARMING_STATE_IN_AIR_RESTORE =>
+                MavState::MAV_STATE_ACTIVE,
+            VehicleStatusData::ARMING_STATE_REBOOT => MavState::MAV_STATE_POWEROFF,
+            _ => MavState::MAV_STATE_STANDBY,
+        }
+    };
+
+    HEARTBEAT_DATA {
+        custom_mode: ((sub as u32) << 24) | ((main as u32) << 16),
+        mavtype: MavType::from_u8(status.system_type).unwrap_or_default(),
+        autopilot: MavAutopilot::MAV_AUTOPILOT_PX4,
+        base_mode,
+        system_status,
+        mavlink_version: 3,
+    }
+}
+
+/// The vehicle status described by a HEARTBEAT, without the SYS_STATUS sensor bits
+pub fn from_heartbeat(heartbeat: &HEARTBEAT_DATA) -> VehicleStatusData {
+    let base_mode = heartbeat.base_mode;
+    let mut status = VehicleStatusData {
+        system_type: heartbeat.mavtype as u8,
+        arming_state: if base_mode.contains(MavModeFlag::MAV_MODE_FLAG_SAFETY_ARMED) {
+            VehicleStatusData::ARMING_STATE_ARMED
+        } else {
+            VehicleStatusData::ARMING_STATE_STANDBY
+        },
+        hil_state: if base_mode.contains(MavModeFlag::MAV_MODE_FLAG_HIL_ENABLED) {
+            VehicleStatusData::HIL_STATE_ON
+        } else {
+            VehicleStatusData::HIL_STATE_OFF
+        },
+        failsafe: heartbeat.system_status == MavState::MAV_STATE_CRITICAL,
+        ..Default::default()
+    };
+    if base_mode.contains(MavModeFlag::MAV_MODE_FLAG_CUSTOM_MODE_ENABLED) {
+        let main = (heartbeat.custom_mode >> 16) as u8;
+        let sub = (heartbeat.custom_mode >> 24) as u8;
+        if let Some(nav_state) = nav_state(main, sub) {
+            status.nav_state = nav_state;
+        }
+    }
+    if heartbeat.system_status == MavState::MAV_STATE_FLIGHT_TERMINATION {
+        status.nav_state = VehicleStatusData::NAVIGATION_STATE_TERMINATION;
+    }
+    status
+}
+
+/// SYS_STATUS with the sensor bitmaps of `status`, and battery values if known
+pub fn sys_status(status: &VehicleStatusData, battery: Option<&BatteryStatusData>) -> SYS_STATUS_DATA {
+    let mut sys = SYS_STATUS_DATA {
+        onboard_control_sensors_present:
+            MavSysStatusSensor::from_bits_truncate(status.onboard_control_sensors_present),
+        onboard_control_sensors_enabled:
+            MavSysStatusSensor::from_bits_truncate(status.onboard_control_sensors_enabled),
+        onboard_control_sensors_health:
+            MavSysStatusSensor::from_bits_truncate(status.onboard_control_sensors_health),
+        voltage_battery: u16::MAX,
+        current_battery: -1,
+        battery_remaining: -1,
+        ..Default::default()
+    };
+    if let Some(battery) = battery.filter(|battery| battery.connected) {
+        let status = battery_status(battery, 0);
+        sys.voltage_battery = to_u16_or_max(battery.voltage_v, 1000.0);
+        sys.current_battery = status.current_battery;
+        sys.battery_remaining = status.battery_remaining;
+    }
+    sys
+}
+
+/// Copy the sensor bitmaps of a SYS_STATUS into `status`
+pub fn merge_sys_status(status: &mut VehicleStatusData, sys: &SYS_STATUS_DATA) {
+    status.onboard_control_sensors_present = sys.onboard_control_sensors_present.bits();
+    status.onboard_control_sensors_enabled = sys.onboard_control_sensors_enabled.bits();
+    status.onboard_control_sensors_health = sys.onboard_control_sensors_health.bits();
+}
+
+
+/// One SERVO_OUTPUT_RAW per port of eight outputs, with values in microseconds.
+/// The timestamp wraps, as MAVLink only has 32 bits for it.
+pub fn servo_output_raw(outputs: &ActuatorOutputsData) -> Vec<SERVO_OUTPUT_RAW_DATA> {
+    let noutputs = (outputs.noutputs as usize).clamp(1, outputs.output.len());
+    outputs.output[..noutputs].chunks(SERVOS_PER_PORT)
+        .enumerate()
+        .map(|(port, chunk)| {
+            let mut servos = [0; SERVOS_PER_PORT];
+            for (servo, &value) in servos.iter_mut().zip(chunk) {
+                *servo = if value.is_finite() { value.round().clamp(0.0, u16::MAX as f32) as u16 } else { 0 };
+            }
+            SERVO_OUTPUT_RAW_DATA {
+                time_usec: outputs.timestamp as u32,
+                port: port as u8,
+                servo1_raw: servos[0],
+                servo2_raw: servos[1],
+                servo3_raw: servos[2],
+                servo4_raw: servos[3],
+                servo5_raw: servos[4],
+                servo6_raw: servos[5],
+                servo7_raw: servos[6],
+                servo8_raw: servos[7],
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+fn servos(raw: &SERVO_OUTPUT_RAW_DATA) -> [u16; SERVOS_PER_PORT] {
+    [raw.servo1_raw, raw.servo2_raw, raw.servo3_raw, raw.servo4_raw,
+     raw.servo5_raw, raw.servo6_raw, raw.servo7_raw, raw.servo8_raw]
+}
+
+/// Combine the ports of one SERVO_OUTPUT_RAW update. The output count runs up
+/// to the last non-zero output, and ports past the 16 outputs are ignored.
+pub fn from_servo_output_raw(ports: &[SERVO_OUTPUT_RAW_DATA]) -> ActuatorOutputsData {
+    let mut outputs = ActuatorOutputsData::default();
+    for raw in ports {
+        let first = raw.port as usize * SERVOS_PER_PORT;
+        if first >= outputs.output.len() {
+            continue;
+        }
+        outputs.timestamp = outputs.timestamp.max(raw.time_usec as u64);
+        for (idx, &value) in servos(raw).iter().enumerate() {
+            outputs.output[first + idx] = value as f32;
+            if value != 0 {
+                outputs.noutputs = outputs.noutputs.max((first + idx + 1) as u32);
+            }
+        }
+    }
+    outputs
+}
+
+
+/// The MAVLink messages for a decoded frame, if it is one of the converted types.
+/// Instance 1 of `vehicle_gps_position` is sent as GPS2_RAW, and the instance ID
+/// of `battery_status` is the battery ID.
+pub fn to_mavlink(header: &UorbHeader, msg: &UorbMessage) -> Vec<MavMessage> {
+    match msg {
+        UorbMessage::VehicleGpsPosition(gps) => match header.instance_id {
+            0 => vec![MavMessage::GPS_RAW_INT(gps_raw_int(gps))],
+            1 => vec![MavMessage::GPS2_RAW(gps2_raw(gps))],
+            _ => vec![],
+        },
+        UorbMessage::VehicleAttitude(attitude) =>
+            vec![MavMessage::ATTITUDE_QUATERNION(attitude_quaternion(attitude))],
+        UorbMessage::BatteryStatus(battery) =>
+            vec![MavMessage::BATTERY_STATUS(battery_status(battery, header.instance_id))],
+        UorbMessage::VehicleStatus(status) => vec![
+            MavMessage::HEARTBEAT(heartbeat(status)),
+            MavMessage::SYS_STATUS(sys_status(status, None)),
+        ],
+        UorbMessage::ActuatorOutputs(outputs) =>
+            servo_output_raw(outputs).into_iter().map(MavMessage::SERVO_OUTPUT_RAW).collect(),
+        _ => vec![],
+    }
+}
+
+/// The frame for a MAVLink message, if it is one of the converted types.
+/// A SYS_STATUS alone carries too little to make a `vehicle_status`; use
+/// `merge_sys_status` for it instead.
+pub fn from_mavlink(msg: &MavMessage) -> Option<(UorbHeader, UorbMessage)> {
+    let pair = match msg {
+        MavMessage::GPS_RAW_INT(raw) => {
+            let gps = from_gps_raw_int(raw);
+            gps.gen_ready_pair(0, gps.timestamp)
+        }
+        MavMessage::GPS2_RAW(raw) => {
+            let gps = from_gps2_raw(raw);
+            gps.gen_ready_pair(1, gps.timestamp)
+        }
+        MavMessage::ATTITUDE_QUATERNION(att) => {
+            let attitude = from_attitude_quaternion(att);
+            attitude.gen_ready_pair(0, attitude.timestamp)
+        }
+        MavMessage::BATTERY_STATUS(status) => from_battery_status(status).gen_ready_pair(status.id, 0),
+        MavMessage::HEARTBEAT(heartbeat) => from_heartbeat(heartbeat).gen_ready_pair(0, 0),
+        MavMessage::SERVO_OUTPUT_RAW(raw) => {
+            let outputs = from_servo_output_raw(std::slice::from_ref(raw));
+            outputs.gen_ready_pair(0, outputs.timestamp)
+        }
+        _ => return None,
+    };
+    Some(pair)
+}
@@ -0,0 +1,77 @@
+//! Reading streams of uORB frames from any `std::io::Read` transport
+
+use std::io::{ErrorKind, Read, Result};
+
+use crate::{frame, read_header, read_msg_with_mode, read_payload, skip_payload};
+use crate::{DecodeMode, UorbHeader, UorbMessage, UorbMsgMeta};
+use crate::common::MAX_ENCODED_LEN;
+
+
+/// Reads successive frames from a byte stream.
+///
+/// As an iterator, yields every decoded message until the stream ends.
+/// A frame that cannot be decoded is reported as an error, and iteration
+/// resumes with the following frame. Reaching the end of the stream,
+/// even part way through a frame, ends the iteration.
+#[derive(Debug)]
+pub struct UorbReader<R> {
+    inner: R,
+    mode: DecodeMode,
+}
+
+impl<R: Read> UorbReader<R> {
+    /// Read frames from `inner`, requiring each payload length to match strictly
+    pub fn new(inner: R) -> Self {
+        Self::with_mode(inner, DecodeMode::Strict)
+    }
+
+    /// Read frames from `inner`, fitting each payload to its message according to `mode`
+    pub fn with_mode(inner: R, mode: DecodeMode) -> Self {
+        UorbReader { inner, mode }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Read the next frame of any message type
+    pub fn read_msg(&mut self) -> Result<(UorbHeader, UorbMessage)> {
+        read_msg_with_mode(&mut self.inner, self.mode).map(|(header, msg, _fit)| (header, msg))
+    }
+
+    /// Read the next frame carrying a `T`, skipping the payloads of all other messages
+    pub fn read_typed<T: UorbMsgMeta>(&mut self) -> Result<(UorbHeader, T)> {
+        loop {
+            let header = read_header(&mut self.inner)?;
+            if header.hash != T::MSG_HASH_CODE {
+                skip_payload(&mut self.inner, &header)?;
+                continue;
+            }
+            let mut payload_buf = [0; MAX_ENCODED_LEN];
+            let (payload, _skipped) = read_payload(&mut self.inner, &header, Some(T::ENCODED_LEN),
+                                                   self.mode, &mut payload_buf)?;
+            let (data, _fit) = frame::decode_fitted(header.hash, payload, T::ENCODED_LEN,
+                                                    self.mode, T::deser)?;
+            return Ok((header, data));
+        }
+    }
+}
+
+impl<R: Read> Iterator for UorbReader<R> {
+    type Item = Result<(UorbHeader, UorbMessage)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_msg() {
+            Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => None,
+            res => Some(res),
+        }
+    }
+}
@@ -0,0 +1,402 @@
+//! Recording frames with their receive times, and replaying them with the same timing
+//!
+//! A recording starts with `RECORDING_MAGIC`, followed by one record per frame:
+//! the receive time in microseconds as a little-endian u64, the frame length as a
+//! little-endian u16, and the frame as `write_msg` sends it. `Recorder::finish`
+//! appends an index of (receive time, file offset) pairs, as little-endian u64s,
+//! and a footer holding the index offset, the entry count and `INDEX_MAGIC`.
+//! A recording cut off before its index can still be played.
+
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::bus::Bus;
+use crate::clock::{Clock, VirtualClock};
+use crate::{read_msg, write_msg, UorbHeader, UorbMessage, MAX_FRAME_LEN};
+
+
+/// Start of every recording, ending in the format version
+pub const RECORDING_MAGIC: [u8; 8] = *b"UORBREC\x01";
+
+/// End of the footer of a finished recording
+pub const INDEX_MAGIC: [u8; 8] = *b"UORBIDX\x01";
+
+/// Receive time between index entries, in microseconds
+pub const DEFAULT_INDEX_INTERVAL: u64 = 1_000_000;
+
+/// Receive time and frame length before each frame
+const RECORD_HEADER_LEN: u64 = 10;
+const INDEX_ENTRY_LEN: u64 = 16;
+const FOOTER_LEN: u64 = 24;
+
+/// Where frames are found in a recording
+#[derive(Debug)]
+struct RecordingIndex {
+    /// (receive time, file offset) of a frame for each index interval
+    entries: Vec<(u64, u64)>,
+    /// File offset just past the last frame
+    data_end: u64,
+}
+
+
+/// Writes frames from any source to a recording
+#[derive(Debug)]
+pub struct Recorder<W: Write> {
+    inner: W,
+    pos: u64,
+    index: Vec<(u64, u64)>,
+    index_interval: u64,
+    last_time: u64,
+    frames: u64,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut inner: W) -> Result<Self> {
+        inner.write_all(&RECORDING_MAGIC)?;
+        Ok(Recorder {
+            inner,
+            pos: RECORDING_MAGIC.len() as u64,
+            index: Vec::new(),
+            index_interval: DEFAULT_INDEX_INTERVAL,
+            last_time: 0,
+            frames: 0,
+        })
+    }
+
+    /// Set the receive time between index entries, trading index size for seek speed
+    pub fn set_index_interval(&mut self, index_interval: u64) {
+        self.index_interval = index_interval;
+    }
+
+    /// Number of frames recorded so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Record a frame received at `receive_time` microseconds, which must not go backwards
+    pub fn record(&mut self, receive_time: u64, header: &UorbHeader, msg: &UorbMessage) -> Result<()> {
+        if receive_time < self.last_time {
+            return Err(Error::new(ErrorKind::InvalidInput, "receive time went backwards"));
+        }
+        let mut frame = [0; MAX_FRAME_LEN];
+        let frame_len = msg.encode_frame_into(header, &mut frame)?;
+
+        if self.index.last().is_none_or(|&(time, _)| receive_time >= time + self.index_interval) {
+            self.index.push((receive_time, self.pos));
+        }
+        self.inner.write_u64::<LittleEndian>(receive_time)?;
+        self.inner.write_u16::<LittleEndian>(frame_len as u16)?;
+        self.inner.write_all(&frame[..frame_len])?;
+        self.pos += RECORD_HEADER_LEN + frame_len as u64;
+        self.last_time = receive_time;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Record frames read with `read_msg`, stamped by `clock`, until the source
+    /// ends. Returns the number of frames recorded.
+    pub fn record_stream<R: Read, C: Clock>(&mut self, r: &mut R, clock: &C) -> Result<u64> {
+        for_each_frame(r, |header, msg| self.record(clock.now(), header, msg))
+    }
+
+    /// Write the index and footer, and return the inner writer
+    pub fn finish(mut self) -> Result<W> {
+        let index_offset = self.pos;
+        for &(time, offset) in &self.index {
+            self.inner.write_u64::<LittleEndian>(time)?;
+            self.inner.write_u64::<LittleEndian>(offset)?;
+        }
+        self.inner.write_u64::<LittleEndian>(index_offset)?;
+        self.inner.write_u64::<LittleEndian>(self.index.len() as u64)?;
+        self.inner.write_all(&INDEX_MAGIC)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+
+/// Pass each frame read with `read_msg` to `f` until the source ends, returning
+/// the number of frames
+pub(crate) fn for_each_frame<R, F>(r: &mut R, mut f: F) -> Result<u64>
+    where R: Read, F: FnMut(&UorbHeader, &UorbMessage) -> Result<()> {
+    let mut count = 0;
+    loop {
+        match read_msg(r) {
+            Ok((header, msg)) => {
+                f(&header, &msg)?;
+                count += 1;
+            }
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(count),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Pass each frame of a recording to `f`, without waiting out its timing,
+/// returning the number of frames
+pub(crate) fn for_each_recorded_frame<R, F>(reader: R, mut f: F) -> Result<u64>
+    where R: Read + Seek, F: FnMut(&UorbHeader, &UorbMessage) -> Result<()> {
+    let mut player = Player::open(reader, VirtualClock::new(0))?;
+    let mut count = 0;
+    while let Some((header, msg)) = player.next_frame()? {
+        f(&header, &msg)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+
+/// Replays a recording with its original timing, measured on `clock`.
+///
+/// The first frame is due as soon as it is asked for, and each later frame is due
+/// once the clock has moved on by its receive time gap, divided by the speed.
+/// Frames keep their original headers.
+#[derive(Debug)]
+pub struct Player<R: Read + Seek, C: Clock> {
+    reader: R,
+    clock: C,
+    index: Vec<(u64, u64)>,
+    data_start: u64,
+    data_end: u64,
+    pos: u64,
+    next: Option<(u64, UorbHeader, UorbMessage)>,
+    speed: f64,
+    looping: bool,
+    paused_at: Option<u64>,
+    /// A receive time and the clock time at which it plays
+    anchor: Option<(u64, u64)>,
+}
+
+impl<R: Read + Seek, C: Clock> Player<R, C> {
+    /// Open a recording, using its index, or scanning it if it was not finished
+    pub fn open(mut reader: R, clock: C) -> Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != RECORDING_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a uORB recording"));
+        }
+        let data_start = RECORDING_MAGIC.len() as u64;
+        let len = reader.seek(SeekFrom::End(0))?;
+
+        let index = match Self::read_index(&mut reader, data_start, len)? {
+            Some(index) => index,
+            None => Self::scan_index(&mut reader, data_start, len)?,
+        };
+        reader.seek(SeekFrom::Start(data_start))?;
+        Ok(Player {
+            reader,
+            clock,
+            index: index.entries,
+            data_start,
+            data_end: index.data_end,
+            pos: data_start,
+            next: None,
+            speed: 1.0,
+            looping: false,
+            paused_at: None,
+            anchor: None,
+        })
+    }
+
+    /// Receive time of the first frame, if there is one
+    pub fn start_time(&self) -> Option<u64> {
+        self.index.first().map(|&(time, _)| time)
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Play at `speed` times the original rate, from the current position
+    pub fn set_speed(&mut self, speed: f64) {
+        assert!(speed > 0.0, "playback speed must be positive");
+        if let Some((time, clock_time)) = self.anchor {
+            let now = self.paused_at.unwrap_or_else(|| self.clock.now());
+            let elapsed = now.saturating_sub(clock_time) as f64 * self.speed;
+            self.anchor = Some((time + elapsed as u64, now));
+        }
+        self.speed = speed;
+    }
+
+    /// Start again from the first frame after the last, rather than finishing
+    pub fn set_loop(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// Stop frames from coming due until `resume`
+    pub fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(self.clock.now());
+        }
+    }
+
+    /// Carry on from where playback was paused, shifting later frames by the pause
+    pub fn resume(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            if let Some((_, clock_time)) = &mut self.anchor {
+                *clock_time += self.clock.now().saturating_sub(paused_at);
+            }
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Continue from the first frame received at or after `timestamp`. That frame
+    /// is due at once.
+    pub fn seek(&mut self, timestamp: u64) -> Result<()> {
+        let entry = self.index.partition_point(|&(time, _)| time <= timestamp);
+        let offset = match entry {
+            0 => self.data_start,
+            entry => self.index[entry - 1].1,
+        };
+        self.seek_to_offset(offset)?;
+        while self.fill(false)? {
+            match &self.next {
+                Some((time, _, _)) if *time < timestamp => self.next = None,
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Receive time of the next frame, or None once playback has finished
+    pub fn position(&mut self) -> Result<Option<u64>> {
+        self.fill(self.looping)?;
+        Ok(self.next.as_ref().map(|(time, _, _)| *time))
+    }
+
+    /// Clock time at which the next frame is due, or None while paused or once finished
+    pub fn next_due(&mut self) -> Result<Option<u64>> {
+        if self.is_paused() || !self.fill(self.looping)? {
+            return Ok(None);
+        }
+        let time = self.next.as_ref().map_or(0, |(time, _, _)| *time);
+        let now = self.clock.now();
+        let (anchor_time, clock_time) = *self.anchor.get_or_insert((time, now));
+        Ok(Some(clock_time + (time.saturating_sub(anchor_time) as f64 / self.speed) as u64))
+    }
+
+    /// The next frame if it is due, without waiting
+    pub fn poll(&mut self) -> Result<Option<(UorbHeader, UorbMessage)>> {
+        match self.next_due()? {
+            Some(due) if due <= self.clock.now() => Ok(self.take()),
+            _ => Ok(None),
+        }
+    }
+
+    /// Wait for the next frame to come due and return it, or None once playback
+    /// has finished. Fails with `WouldBlock` while paused.
+    pub fn next_frame(&mut self) -> Result<Option<(UorbHeader, UorbMessage)>> {
+        if self.is_paused() {
+            return Err(Error::new(ErrorKind::WouldBlock, "playback is paused"));
+        }
+        match self.next_due()? {
+            Some(due) => {
+                self.clock.sleep_until(due);
+                Ok(self.take())
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Play to the end with `write_msg`, returning the number of frames written
+    pub fn play_to<W: Write>(&mut self, w: &mut W) -> Result<u64> {
+        let mut count = 0;
+        while let Some((header, msg)) = self.next_frame()? {
+            write_msg(w, &header, &msg)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Play to the end by publishing on `bus`, returning the number of frames published
+    pub fn play_to_bus(&mut self, bus: &Bus) -> Result<u64> {
+        let mut count = 0;
+        while let Some((header, msg)) = self.next_frame()? {
+            bus.publish_msg(&header, &msg);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn take(&mut self) -> Option<(UorbHeader, UorbMessage)> {
+        self.next.take().map(|(_, header, msg)| (header, msg))
+    }
+
+    fn seek_to_offset(&mut self, offset: u64) -> Result<()> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.pos = offset;
+        self.next = None;
+        self.anchor = None;
+        Ok(())
+    }
+
+    /// Read the next frame if none is held, wrapping around if `wrap` is set.
+    /// Returns whether a frame is held.
+    fn fill(&mut self, wrap: bool) -> Result<bool> {
+        if self.next.is_some() {
+            return Ok(true);
+        }
+        if self.pos >= self.data_end {
+            if !wrap || self.data_end == self.data_start {
+                return Ok(false);
+            }
+            self.seek_to_offset(self.data_start)?;
+        }
+        let time = self.reader.read_u64::<LittleEndian>()?;
+        let frame_len = self.reader.read_u16::<LittleEndian>()?;
+        let mut frame = vec![0; frame_len as usize];
+        self.reader.read_exact(&mut frame)?;
+        self.pos += RECORD_HEADER_LEN + frame_len as u64;
+        let (header, msg) = read_msg(&mut frame.as_slice())?;
+        self.next = Some((time, header, msg));
+        Ok(true)
+    }
+
+    /// The index and end of the records of a finished recording, if the footer is intact
+    fn read_index(reader: &mut R, data_start: u64, len: u64) -> Result<Option<RecordingIndex>> {
+        if len < data_start + FOOTER_LEN {
+            return Ok(None);
+        }
+        reader.seek(SeekFrom::Start(len - FOOTER_LEN))?;
+        let index_offset = reader.read_u64::<LittleEndian>()?;
+        let count = reader.read_u64::<LittleEndian>()?;
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        let index_len = count.checked_mul(INDEX_ENTRY_LEN);
+        if magic != INDEX_MAGIC || index_offset < data_start
+            || index_len.and_then(|index_len| index_offset.checked_add(index_len)) != Some(len - FOOTER_LEN) {
+            return Ok(None);
+        }
+        reader.seek(SeekFrom::Start(index_offset))?;
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let time = reader.read_u64::<LittleEndian>()?;
+            let offset = reader.read_u64::<LittleEndian>()?;
+            entries.push((time, offset));
+        }
+        Ok(Some(RecordingIndex { entries, data_end: index_offset }))
+    }
+
+    /// Build an index by reading every record header, stopping at a record that was cut off
+    fn scan_index(reader: &mut R, data_start: u64, len: u64) -> Result<RecordingIndex> {
+        let mut entries: Vec<(u64, u64)> = Vec::new();
+        let mut pos = reader.seek(SeekFrom::Start(data_start))?;
+        while pos + RECORD_HEADER_LEN <= len {
+            let time = reader.read_u64::<LittleEndian>()?;
+            let frame_len = reader.read_u16::<LittleEndian>()?;
+            let end = pos + RECORD_HEADER_LEN + frame_len as u64;
+            if end > len {
+                break;
+            }
+            if entries.last().is_none_or(|&(last, _)| time >= last + DEFAULT_INDEX_INTERVAL) {
+                entries.push((time, pos));
+            }
+            pos = reader.seek(SeekFrom::Start(end))?;
+        }
+        Ok(RecordingIndex { entries, data_end: pos })
+    }
+}
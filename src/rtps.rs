@@ -0,0 +1,334 @@
+//! The framing of the PX4 microRTPS bridge, to talk to an unmodified `micrortps_client`
+//!
+//! Each frame is the `>>>` marker, a topic ID, a sequence number, the big-endian
+//! payload length and CRC16 of the payload, then the message serialized as
+//! little-endian CDR, with fields in declaration order as for ROS 2 `px4_msgs`.
+//! That order has not yet been checked against frames captured from a real
+//! `micrortps_client`.
+//! Topic IDs come from a `TopicTable`, in the format of PX4's
+//! `uorb_rtps_message_ids.yaml`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::path::Path;
+
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::cdr::{CdrEndian, CdrReader, CdrWriter};
+use crate::{UorbHeader, UorbMessage, UORB_MAGIC_V1};
+
+
+/// Leading bytes of every frame
+pub const RTPS_MARKER: [u8; 3] = *b">>>";
+
+/// Size of the frame header, including the marker
+pub const RTPS_HEADER_LEN: usize = 9;
+
+/// CRC-16/ARC of `data`, as used by the microRTPS transport
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u16, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+
+/// The header of a microRTPS frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RtpsHeader {
+    pub topic_id: u8,
+    pub seq: u8,
+    pub payload_len: u16,
+    pub crc: u16,
+}
+
+impl RtpsHeader {
+    pub fn encode(&self, buf: &mut [u8]) {
+        buf[..3].copy_from_slice(&RTPS_MARKER);
+        buf[3] = self.topic_id;
+        buf[4] = self.seq;
+        BigEndian::write_u16(&mut buf[5..], self.payload_len);
+        BigEndian::write_u16(&mut buf[7..], self.crc);
+    }
+
+    /// Decode a header whose marker has already been checked
+    pub fn decode(buf: &[u8]) -> RtpsHeader {
+        RtpsHeader {
+            topic_id: buf[3],
+            seq: buf[4],
+            payload_len: BigEndian::read_u16(&buf[5..]),
+            crc: BigEndian::read_u16(&buf[7..]),
+        }
+    }
+}
+
+
+/// One entry of a `TopicTable`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtpsTopic {
+    pub id: u8,
+    /// Topic name, such as `vehicle_mocap_odometry`
+    pub name: String,
+    /// The MSG_HASH_CODE of the topic's message type, if it is a known message
+    pub hash: Option<u16>,
+    /// Whether the topic is an alias of another message type
+    pub is_alias: bool,
+    /// Whether the vehicle sends this topic
+    pub send: bool,
+    /// Whether the vehicle receives this topic
+    pub receive: bool,
+}
+
+/// Maps microRTPS topic IDs onto known message types
+#[derive(Debug, Clone, Default)]
+pub struct TopicTable {
+    topics: BTreeMap<u8, RtpsTopic>,
+}
+
+impl TopicTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the `rtps:` list of a `uorb_rtps_message_ids.yaml` file, whose entries
+    /// look like `- msg: vehicle_mocap_odometry` followed by `id: 81`,
+    /// `alias: vehicle_odometry`, `send: true` or `receive: true`
+    pub fn parse(text: &str) -> Result<TopicTable> {
+        let mut table = TopicTable::new();
+        let mut entry: Option<(usize, BTreeMap<String, String>)> = None;
+        for (line_num, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let line = match line.strip_prefix('-') {
+                Some(rest) => {
+                    if let Some((start, keys)) = entry.take() {
+                        table.insert_entry(start, &keys)?;
+                    }
+                    entry = Some((line_num + 1, BTreeMap::new()));
+                    rest.trim()
+                }
+                None => line,
+            };
+            if let (Some((_, keys)), Some(colon)) = (entry.as_mut(), line.find(':')) {
+                keys.insert(line[..colon].trim().to_string(), line[colon + 1..].trim().to_string());
+            }
+        }
+        if let Some((start, keys)) = entry {
+            table.insert_entry(start, &keys)?;
+        }
+        Ok(table)
+    }
+
+    /// Read and parse a `uorb_rtps_message_ids.yaml` file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<TopicTable> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    fn insert_entry(&mut self, line_num: usize, keys: &BTreeMap<String, String>) -> Result<()> {
+        let invalid = |what: &str| Error::new(ErrorKind::InvalidData,
+                                              format!("{} in topic entry at line {}", what, line_num));
+        let name = keys.get("msg").ok_or_else(|| invalid("missing msg"))?;
+        let id = keys.get("id").and_then(|id| id.parse().ok())
+            .ok_or_else(|| invalid("missing or invalid id"))?;
+        let flag = |key: &str| keys.get(key).is_some_and(|value| value == "true");
+        self.insert(id, name, keys.get("alias").map(String::as_str));
+        let topic = self.topics.get_mut(&id).unwrap();
+        topic.send = flag("send");
+        topic.receive = flag("receive");
+        Ok(())
+    }
+
+    /// Add or replace topic `id`, whose message type is `alias` if given, else `name`
+    pub fn insert(&mut self, id: u8, name: &str, alias: Option<&str>) {
+        self.topics.insert(id, RtpsTopic {
+            id,
+            name: name.to_string(),
+            hash: UorbMessage::hash_for_raw_name(alias.unwrap_or(name)),
+            is_alias: alias.is_some(),
+            send: false,
+            receive: false,
+        });
+    }
+
+    pub fn get(&self, id: u8) -> Option<&RtpsTopic> {
+        self.topics.get(&id)
+    }
+
+    /// The ID of the topic named after the message with hash `hash`, not an alias of it
+    pub fn id_for_hash(&self, hash: u16) -> Option<u8> {
+        self.topics.values()
+            .find(|topic| !topic.is_alias && topic.hash == Some(hash))
+            .map(|topic| topic.id)
+    }
+
+    /// All topics, in ascending order of ID
+    pub fn topics(&self) -> impl Iterator<Item = &RtpsTopic> {
+        self.topics.values()
+    }
+}
+
+
+/// Encodes and decodes microRTPS frames, mapping topic IDs through a `TopicTable`.
+///
+/// The bridge has no notion of uORB instances, so every instance of a topic is
+/// sent under the same ID and received messages have instance ID 0. Header
+/// timestamps are taken from the message's `timestamp` field.
+#[derive(Debug)]
+pub struct RtpsCodec {
+    table: TopicTable,
+    seq: u8,
+    /// Bytes read but not yet decoded
+    pending: Vec<u8>,
+    crc_errors: u64,
+}
+
+impl RtpsCodec {
+    pub fn new(table: TopicTable) -> Self {
+        RtpsCodec { table, seq: 0, pending: vec![], crc_errors: 0 }
+    }
+
+    pub fn table(&self) -> &TopicTable {
+        &self.table
+    }
+
+    /// Encode a message as a complete frame, under the topic named after its type
+    pub fn encode_frame(&mut self, header: &UorbHeader, msg: &UorbMessage) -> Result<Vec<u8>> {
+        let topic_id = self.table.id_for_hash(header.hash)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "no RTPS topic for message"))?;
+        if UorbMessage::encoded_len_for_hash(header.hash) != Some(msg.encoded_len()) {
+            return Err(Error::new(ErrorKind::InvalidInput, "message does not match header hash"));
+        }
+        let cdr_len = UorbMessage::cdr_encoded_len_for_hash(header.hash)
+            .filter(|&len| len <= u16::MAX as usize)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "message does not fit in an RTPS frame"))?;
+        let mut cdr = vec![0; cdr_len];
+        msg.ser_cdr_body(&mut CdrWriter::new(&mut cdr, CdrEndian::Little))
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "message does not fit in an RTPS frame"))?;
+
+        let rtps_header = RtpsHeader {
+            topic_id,
+            seq: self.seq,
+            payload_len: cdr.len() as u16,
+            crc: crc16(&cdr),
+        };
+        self.seq = self.seq.wrapping_add(1);
+        let mut frame = vec![0; RTPS_HEADER_LEN];
+        rtps_header.encode(&mut frame);
+        frame.extend_from_slice(&cdr);
+        Ok(frame)
+    }
+
+    pub fn write_msg<W: Write>(&mut self, w: &mut W, header: &UorbHeader, msg: &UorbMessage) -> Result<()> {
+        let frame = self.encode_frame(header, msg)?;
+        w.write_all(&frame)
+    }
+
+    /// Read the next frame of a topic in the table. Input is buffered in the
+    /// codec, so keep reading one stream through the same codec.
+    ///
+    /// A `>>>` marker can also occur inside a payload, so a header with a topic
+    /// not in the table or a payload too long for its message, or a frame whose
+    /// CRC does not match, is taken as a false start and the search resumes just
+    /// past its marker. CRC mismatches are counted in `crc_errors`.
+    pub fn read_frame<R: Read>(&mut self, r: &mut R) -> Result<(RtpsHeader, Vec<u8>)> {
+        loop {
+            let start = match self.pending.windows(RTPS_MARKER.len()).position(|w| w == RTPS_MARKER) {
+                Some(start) => start,
+                None => {
+                    // keep a possible partial marker
+                    let keep = self.pending.len().min(RTPS_MARKER.len() - 1);
+                    self.pending.drain(..self.pending.len() - keep);
+                    self.fill(r)?;
+                    continue;
+                }
+            };
+            self.pending.drain(..start);
+            if self.pending.len() < RTPS_HEADER_LEN {
+                self.fill(r)?;
+                continue;
+            }
+            let header = RtpsHeader::decode(&self.pending);
+            if !self.is_plausible(&header) {
+                self.pending.drain(..1);
+                continue;
+            }
+            let frame_len = RTPS_HEADER_LEN + header.payload_len as usize;
+            if self.pending.len() < frame_len {
+                self.fill(r)?;
+                continue;
+            }
+            let payload = &self.pending[RTPS_HEADER_LEN..frame_len];
+            if crc16(payload) != header.crc {
+                self.crc_errors += 1;
+                self.pending.drain(..1);
+                continue;
+            }
+            let payload = payload.to_vec();
+            self.pending.drain(..frame_len);
+            return Ok((header, payload));
+        }
+    }
+
+    /// Whether a header could start a frame, before waiting for its payload
+    fn is_plausible(&self, header: &RtpsHeader) -> bool {
+        match self.table.get(header.topic_id) {
+            Some(topic) => topic.hash
+                .and_then(UorbMessage::cdr_encoded_len_for_hash)
+                .is_none_or(|len| header.payload_len as usize <= len),
+            None => false,
+        }
+    }
+
+    /// Number of frames skipped because their CRC did not match
+    pub fn crc_errors(&self) -> u64 {
+        self.crc_errors
+    }
+
+    fn fill<R: Read>(&mut self, r: &mut R) -> Result<()> {
+        let mut chunk = [0; 512];
+        let len = loop {
+            match r.read(&mut chunk) {
+                Ok(0) => return Err(Error::new(ErrorKind::UnexpectedEof, "end of RTPS stream")),
+                Ok(len) => break len,
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        };
+        self.pending.extend_from_slice(&chunk[..len]);
+        Ok(())
+    }
+
+    /// Decode the payload of a frame, or None if its topic is not a known message
+    pub fn decode_frame(&self, header: &RtpsHeader, payload: &[u8])
+        -> Result<Option<(UorbHeader, UorbMessage)>> {
+        let hash = match self.table.get(header.topic_id).and_then(|topic| topic.hash) {
+            Some(hash) => hash,
+            None => return Ok(None),
+        };
+        let msg = UorbMessage::deser_cdr_body(hash, &mut CdrReader::new(payload, CdrEndian::Little))
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid RTPS payload"))?;
+        let uorb_header = UorbHeader {
+            version: UORB_MAGIC_V1,
+            hash,
+            timestamp: msg.timestamp().unwrap_or(0),
+            instance_id: 0,
+            payload_len: msg.encoded_len(),
+        };
+        Ok(Some((uorb_header, msg)))
+    }
+
+    /// Read frames until one of a known message, skipping other topics
+    pub fn read_msg<R: Read>(&mut self, r: &mut R) -> Result<(UorbHeader, UorbMessage)> {
+        loop {
+            let (header, payload) = self.read_frame(r)?;
+            if let Some(decoded) = self.decode_frame(&header, &payload)? {
+                return Ok(decoded);
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Deserializing the Vec-backed arrays of generated messages, which serde would
+//! otherwise accept at any length
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{Deserializer, Error, SeqAccess, Visitor};
+use serde::Deserialize;
+
+
+struct ExactLen<T, const N: usize>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>, const N: usize> Visitor<'de> for ExactLen<T, N> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an array of {} elements", N)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<T>, A::Error> {
+        let mut values = Vec::with_capacity(N);
+        while let Some(value) = seq.next_element()? {
+            if values.len() == N {
+                return Err(A::Error::invalid_length(N + 1, &self));
+            }
+            values.push(value);
+        }
+        if values.len() != N {
+            return Err(A::Error::invalid_length(values.len(), &self));
+        }
+        Ok(values)
+    }
+}
+
+/// Deserialize a sequence of exactly `N` elements
+pub fn exact_len<'de, D: Deserializer<'de>, T: Deserialize<'de>, const N: usize>(deserializer: D)
+    -> Result<Vec<T>, D::Error> {
+    deserializer.deserialize_seq(ExactLen::<T, N>(PhantomData))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    /// Longer than the 32 elements serde handles natively for arrays
+    #[derive(Debug, Deserialize)]
+    struct Large {
+        #[serde(deserialize_with = "super::exact_len::<_, _, 40>")]
+        values: Vec<u16>,
+    }
+
+    fn parse(len: usize) -> serde_json::Result<Large> {
+        let values: Vec<String> = (0..len).map(|value| value.to_string()).collect();
+        serde_json::from_str(&format!("{{\"values\":[{}]}}", values.join(",")))
+    }
+
+    #[test]
+    fn test_exact_len() {
+        let large = parse(40).unwrap();
+        assert_eq!(large.values, (0..40).collect::<Vec<u16>>());
+
+        let err = parse(39).unwrap_err().to_string();
+        assert!(err.contains("invalid length 39, expected an array of 40 elements"), "{}", err);
+        let err = parse(41).unwrap_err().to_string();
+        assert!(err.contains("invalid length 41, expected an array of 40 elements"), "{}", err);
+        assert!(parse(0).is_err());
+    }
+}
@@ -0,0 +1,289 @@
+//! Reading and writing frames over a serial port (tty), such as a PX4 TELEM link
+
+use std::fs::{File, OpenOptions};
+use std::io::{Error, ErrorKind, Result, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::{read_msg_with_mode, DecodeMode, UorbHeader, UorbMessage, MAX_FRAME_LEN};
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlowControl {
+    None,
+    /// RTS/CTS
+    Hardware,
+    /// XON/XOFF, only safe when those bytes never occur in frames
+    Software,
+}
+
+/// Line settings for a serial port, with 8 data bits and 1 stop bit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SerialConfig {
+    pub baud: u32,
+    pub parity: Parity,
+    pub flow_control: FlowControl,
+}
+
+impl SerialConfig {
+    /// 8N1 at `baud`, without flow control
+    pub fn new(baud: u32) -> Self {
+        SerialConfig { baud, parity: Parity::None, flow_control: FlowControl::None }
+    }
+}
+
+impl Default for SerialConfig {
+    /// The PX4 default for TELEM ports
+    fn default() -> Self {
+        Self::new(57600)
+    }
+}
+
+
+fn baud_to_speed(baud: u32) -> Result<libc::speed_t> {
+    let speed = match baud {
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115_200 => libc::B115200,
+        230_400 => libc::B230400,
+        #[cfg(target_os = "linux")]
+        460_800 => libc::B460800,
+        #[cfg(target_os = "linux")]
+        500_000 => libc::B500000,
+        #[cfg(target_os = "linux")]
+        921_600 => libc::B921600,
+        #[cfg(target_os = "linux")]
+        1_000_000 => libc::B1000000,
+        #[cfg(target_os = "linux")]
+        1_500_000 => libc::B1500000,
+        #[cfg(target_os = "linux")]
+        2_000_000 => libc::B2000000,
+        #[cfg(target_os = "linux")]
+        3_000_000 => libc::B3000000,
+        _ => return Err(Error::new(ErrorKind::InvalidInput, format!("unsupported baud rate {}", baud))),
+    };
+    Ok(speed)
+}
+
+fn check(res: libc::c_int) -> Result<()> {
+    if res < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Set raw mode and the line settings of `config` on `tio`
+fn apply_config(tio: &mut libc::termios, config: &SerialConfig) -> Result<()> {
+    let speed = baud_to_speed(config.baud)?;
+    unsafe {
+        libc::cfmakeraw(tio);
+        check(libc::cfsetispeed(tio, speed))?;
+        check(libc::cfsetospeed(tio, speed))?;
+    }
+
+    tio.c_cflag |= libc::CLOCAL | libc::CREAD;
+    tio.c_cflag &= !(libc::PARENB | libc::PARODD | libc::CSTOPB | libc::CRTSCTS);
+    tio.c_iflag &= !(libc::INPCK | libc::IXON | libc::IXOFF);
+    match config.parity {
+        Parity::None => {}
+        Parity::Odd => {
+            tio.c_cflag |= libc::PARENB | libc::PARODD;
+            tio.c_iflag |= libc::INPCK;
+        }
+        Parity::Even => {
+            tio.c_cflag |= libc::PARENB;
+            tio.c_iflag |= libc::INPCK;
+        }
+    }
+    match config.flow_control {
+        FlowControl::None => {}
+        FlowControl::Hardware => tio.c_cflag |= libc::CRTSCTS,
+        FlowControl::Software => tio.c_iflag |= libc::IXON | libc::IXOFF,
+    }
+    // block until at least one byte arrives
+    tio.c_cc[libc::VMIN] = 1;
+    tio.c_cc[libc::VTIME] = 0;
+    Ok(())
+}
+
+/// Put the tty in raw mode with the given line settings
+fn configure(port: &File, config: &SerialConfig) -> Result<()> {
+    let fd = port.as_raw_fd();
+    unsafe {
+        let mut tio: libc::termios = std::mem::zeroed();
+        check(libc::tcgetattr(fd, &mut tio))?;
+        apply_config(&mut tio, config)?;
+        check(libc::tcsetattr(fd, libc::TCSANOW, &tio))?;
+        check(libc::tcflush(fd, libc::TCIOFLUSH))?;
+    }
+    Ok(())
+}
+
+/// Whether an error means the device has gone away, as when a USB adapter is unplugged
+fn is_disconnect(err: &Error) -> bool {
+    if err.kind() == ErrorKind::UnexpectedEof {
+        return true;
+    }
+    match err.raw_os_error() {
+        Some(code) => code == libc::EIO || code == libc::ENXIO || code == libc::ENODEV,
+        None => false,
+    }
+}
+
+
+/// Carries frames over a tty, resynchronizing on the magic framing value after
+/// line noise, and reopening the device if it disappears.
+#[derive(Debug)]
+pub struct SerialTransport {
+    path: PathBuf,
+    config: SerialConfig,
+    mode: DecodeMode,
+    reconnect_interval: Option<Duration>,
+    port: Option<File>,
+}
+
+impl SerialTransport {
+    /// Open and configure the tty at `path`
+    pub fn open<P: AsRef<Path>>(path: P, config: SerialConfig) -> Result<Self> {
+        let mut transport = SerialTransport {
+            path: path.as_ref().to_path_buf(),
+            config,
+            mode: DecodeMode::Strict,
+            reconnect_interval: Some(Duration::from_millis(500)),
+            port: None,
+        };
+        transport.port = Some(transport.connect()?);
+        Ok(transport)
+    }
+
+    fn connect(&self) -> Result<File> {
+        let port = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(&self.path)?;
+        configure(&port, &self.config)?;
+        Ok(port)
+    }
+
+    /// Wait `interval` between attempts to reopen a vanished device,
+    /// or with None, report the disconnect as an error instead
+    pub fn set_reconnect_interval(&mut self, interval: Option<Duration>) {
+        self.reconnect_interval = interval;
+    }
+
+    /// Set how strictly received payload lengths must match their message definitions
+    pub fn set_decode_mode(&mut self, mode: DecodeMode) {
+        self.mode = mode;
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.port.is_some()
+    }
+
+    /// The open device, reopening it first if it was lost
+    fn port(&mut self) -> Result<&mut File> {
+        while self.port.is_none() {
+            match self.connect() {
+                Ok(port) => self.port = Some(port),
+                Err(err) => match self.reconnect_interval {
+                    Some(interval) => thread::sleep(interval),
+                    None => return Err(err),
+                },
+            }
+        }
+        Ok(self.port.as_mut().unwrap())
+    }
+
+    /// Close the device after a disconnect, so that the next call reopens it
+    fn drop_port_on(&mut self, err: &Error) {
+        if is_disconnect(err) {
+            self.port = None;
+        }
+    }
+
+    /// Read the next frame, waiting for the device to return if it is unplugged
+    pub fn read_msg(&mut self) -> Result<(UorbHeader, UorbMessage)> {
+        loop {
+            let mode = self.mode;
+            let res = read_msg_with_mode(self.port()?, mode);
+            match res {
+                Ok((header, msg, _fit)) => return Ok((header, msg)),
+                Err(err) => {
+                    self.drop_port_on(&err);
+                    if self.port.is_some() || self.reconnect_interval.is_none() {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write a frame. If the device has been unplugged the error is returned,
+    /// and the next call waits for it to be reopened.
+    pub fn write_msg(&mut self, header: &UorbHeader, data: &UorbMessage) -> Result<()> {
+        let mut frame = [0; MAX_FRAME_LEN];
+        let frame_len = data.encode_frame_into(header, &mut frame)?;
+        let res = self.port()?.write_all(&frame[..frame_len]);
+        if let Err(ref err) = res {
+            self.drop_port_on(err);
+        }
+        res
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn termios_for(tio: &libc::termios, config: SerialConfig) -> libc::termios {
+        let mut tio = *tio;
+        apply_config(&mut tio, &config).unwrap();
+        tio
+    }
+
+    #[test]
+    fn test_apply_config() {
+        let zeroed: libc::termios = unsafe { std::mem::zeroed() };
+        let odd_rts = termios_for(&zeroed, SerialConfig {
+            baud: 115_200,
+            parity: Parity::Odd,
+            flow_control: FlowControl::Hardware,
+        });
+        assert_eq!(unsafe { libc::cfgetospeed(&odd_rts) }, libc::B115200);
+        assert_eq!(odd_rts.c_cflag & libc::CSIZE, libc::CS8);
+        assert_eq!(odd_rts.c_cflag & (libc::PARENB | libc::PARODD), libc::PARENB | libc::PARODD);
+        assert_eq!(odd_rts.c_cflag & libc::CRTSCTS, libc::CRTSCTS);
+        assert_eq!(odd_rts.c_iflag & (libc::INPCK | libc::IXON), libc::INPCK);
+
+        let even_xon = termios_for(&odd_rts, SerialConfig {
+            baud: 57600,
+            parity: Parity::Even,
+            flow_control: FlowControl::Software,
+        });
+        assert_eq!(even_xon.c_cflag & (libc::PARENB | libc::PARODD), libc::PARENB);
+        assert_eq!(even_xon.c_cflag & libc::CRTSCTS, 0);
+        assert_eq!(even_xon.c_iflag & (libc::IXON | libc::IXOFF), libc::IXON | libc::IXOFF);
+
+        // settings of an earlier configuration are cleared
+        let plain = termios_for(&odd_rts, SerialConfig::default());
+        assert_eq!(plain.c_cflag & (libc::PARENB | libc::PARODD | libc::CSTOPB | libc::CRTSCTS), 0);
+        assert_eq!(plain.c_iflag & (libc::INPCK | libc::IXON | libc::IXOFF), 0);
+        assert_eq!(plain.c_cc[libc::VMIN], 1);
+
+        assert!(apply_config(&mut plain.clone(), &SerialConfig::new(12345)).is_err());
+    }
+}
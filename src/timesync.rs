@@ -0,0 +1,249 @@
+//! Estimating the clock offset to a remote system with PX4's timesync protocol
+//!
+//! Either side sends `timesync` requests with `tc1` zero and its own transmit
+//! time in `ts1`, both in nanoseconds; the other side answers with its receive
+//! time in `tc1`. Each answer gives a round trip time and an observed offset,
+//! which are smoothed as PX4's `Timesync` class does.
+
+use std::io::{Result, Write};
+
+use crate::common::{TimesyncData, TimesyncStatusData, UorbMessage};
+use crate::{write_msg, UorbHeader, UorbMsgMeta};
+
+
+/// Filter gains while converging and once converged
+pub const ALPHA_GAIN_INITIAL: f64 = 0.05;
+pub const BETA_GAIN_INITIAL: f64 = 0.05;
+pub const ALPHA_GAIN_FINAL: f64 = 0.003;
+pub const BETA_GAIN_FINAL: f64 = 0.00005;
+
+/// Number of accepted samples before the estimate counts as converged
+pub const CONVERGENCE_WINDOW: u32 = 500;
+
+/// Longest round trip time of an accepted sample, in microseconds
+pub const MAX_RTT_SAMPLE: u32 = 10_000;
+/// Largest deviation from a converged estimate of an accepted sample, in microseconds
+pub const MAX_DEVIATION_SAMPLE: i64 = 100_000;
+/// Rejected samples in a row, of either kind, after which the filter restarts
+pub const MAX_CONSECUTIVE_HIGH_RTT: u32 = 5;
+pub const MAX_CONSECUTIVE_HIGH_DEVIATION: u32 = 5;
+
+
+/// The outcome of handling a `timesync` message
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimesyncEvent {
+    /// A request from the remote, answered by this response, which should be sent back
+    Respond(TimesyncData),
+    /// A response whose sample updated the estimate
+    Updated(TimesyncStatusData),
+    /// A response whose sample was dropped for its round trip time or deviation
+    Rejected(TimesyncStatusData),
+}
+
+
+/// Tracks the offset between the local clock and a remote one, both in
+/// microseconds since system start. Times are passed in by the caller, so it
+/// works over any transport and with any clock.
+#[derive(Debug, Clone)]
+pub struct Timesync {
+    seq: u8,
+    /// Smoothed `local - remote`, in microseconds
+    time_offset: f64,
+    time_skew: f64,
+    alpha: f64,
+    beta: f64,
+    /// Accepted samples since the filter last restarted
+    sequence: u32,
+    high_rtt_count: u32,
+    high_deviation_count: u32,
+    status: Option<TimesyncStatusData>,
+}
+
+impl Default for Timesync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timesync {
+    pub fn new() -> Self {
+        Timesync {
+            seq: 0,
+            time_offset: 0.0,
+            time_skew: 0.0,
+            alpha: ALPHA_GAIN_INITIAL,
+            beta: BETA_GAIN_INITIAL,
+            sequence: 0,
+            high_rtt_count: 0,
+            high_deviation_count: 0,
+            status: None,
+        }
+    }
+
+    /// Forget the estimate, as when the remote reboots
+    pub fn reset(&mut self) {
+        self.time_offset = 0.0;
+        self.time_skew = 0.0;
+        self.alpha = ALPHA_GAIN_INITIAL;
+        self.beta = BETA_GAIN_INITIAL;
+        self.sequence = 0;
+        self.high_rtt_count = 0;
+        self.high_deviation_count = 0;
+    }
+
+    /// Whether enough samples have been accepted for the estimate to settle
+    pub fn converged(&self) -> bool {
+        self.sequence >= CONVERGENCE_WINDOW
+    }
+
+    /// Whether any sample has been accepted since the filter last restarted
+    pub fn synced(&self) -> bool {
+        self.sequence > 0
+    }
+
+    /// Smoothed `local - remote` offset, in microseconds
+    pub fn estimated_offset(&self) -> i64 {
+        self.time_offset.round() as i64
+    }
+
+    /// Status from the latest response, accepted or not
+    pub fn status(&self) -> Option<&TimesyncStatusData> {
+        self.status.as_ref()
+    }
+
+    /// A remote timestamp on the local clock. Timestamps pass through unchanged
+    /// until a sample has been accepted.
+    pub fn to_local(&self, remote_timestamp: u64) -> u64 {
+        if !self.synced() {
+            return remote_timestamp;
+        }
+        let offset = self.estimated_offset();
+        if offset < 0 {
+            remote_timestamp.saturating_sub(offset.unsigned_abs())
+        } else {
+            remote_timestamp.saturating_add(offset as u64)
+        }
+    }
+
+    /// Move the timestamp of a frame from the remote onto the local clock
+    pub fn to_local_header(&self, header: &mut UorbHeader) {
+        header.timestamp = self.to_local(header.timestamp);
+    }
+
+    /// A request frame sent at local time `now`
+    pub fn request(&mut self, now: u64) -> (UorbHeader, UorbMessage) {
+        let request = TimesyncData {
+            timestamp: now,
+            seq: self.seq,
+            tc1: 0,
+            ts1: now as i64 * 1000,
+        };
+        self.seq = self.seq.wrapping_add(1);
+        request.gen_ready_pair(0, now)
+    }
+
+    /// Handle a request or response received at local time `now`
+    pub fn handle(&mut self, sync: &TimesyncData, now: u64) -> Option<TimesyncEvent> {
+        let now_ns = now as i64 * 1000;
+        if sync.tc1 == 0 {
+            return Some(TimesyncEvent::Respond(TimesyncData {
+                timestamp: now,
+                seq: sync.seq,
+                tc1: now_ns,
+                ts1: sync.ts1,
+            }));
+        }
+        if sync.tc1 < 0 || sync.ts1 <= 0 || sync.ts1 > now_ns {
+            // not an answer to one of our requests
+            return None;
+        }
+
+        let rtt = ((now_ns - sync.ts1) / 1000).min(u32::MAX as i64) as u32;
+        let observed_offset = (sync.ts1 + now_ns - sync.tc1 * 2) / 2 / 1000;
+        let deviation = (self.estimated_offset() - observed_offset).abs();
+
+        let accepted = if rtt >= MAX_RTT_SAMPLE {
+            self.high_rtt_count += 1;
+            if self.high_rtt_count > MAX_CONSECUTIVE_HIGH_RTT {
+                self.reset();
+            }
+            false
+        } else if self.converged() && deviation > MAX_DEVIATION_SAMPLE {
+            self.high_deviation_count += 1;
+            if self.high_deviation_count > MAX_CONSECUTIVE_HIGH_DEVIATION {
+                self.reset();
+            }
+            false
+        } else {
+            self.update_gains();
+            self.add_sample(observed_offset as f64);
+            self.sequence = self.sequence.saturating_add(1);
+            self.high_rtt_count = 0;
+            self.high_deviation_count = 0;
+            true
+        };
+
+        let status = TimesyncStatusData {
+            timestamp: now,
+            remote_timestamp: (sync.tc1 / 1000) as u64,
+            observed_offset,
+            estimated_offset: self.estimated_offset(),
+            round_trip_time: rtt,
+        };
+        self.status = Some(status);
+        Some(if accepted { TimesyncEvent::Updated(status) } else { TimesyncEvent::Rejected(status) })
+    }
+
+    /// Handle a decoded frame, ignoring anything but `timesync`
+    pub fn handle_msg(&mut self, msg: &UorbMessage, now: u64) -> Option<TimesyncEvent> {
+        match msg {
+            UorbMessage::Timesync(sync) => self.handle(sync, now),
+            _ => None,
+        }
+    }
+
+    /// Send a request with `write_msg`
+    pub fn send_request<W: Write>(&mut self, w: &mut W, now: u64) -> Result<()> {
+        let (header, msg) = self.request(now);
+        write_msg(w, &header, &msg)
+    }
+
+    /// Handle a decoded frame, answering requests with `write_msg`, and return
+    /// the status if it was a response
+    pub fn process<W: Write>(&mut self, w: &mut W, msg: &UorbMessage, now: u64)
+        -> Result<Option<TimesyncStatusData>> {
+        match self.handle_msg(msg, now) {
+            Some(TimesyncEvent::Respond(response)) => {
+                let (header, msg) = response.gen_ready_pair(0, now);
+                write_msg(w, &header, &msg)?;
+                Ok(None)
+            }
+            Some(TimesyncEvent::Updated(status)) | Some(TimesyncEvent::Rejected(status)) => Ok(Some(status)),
+            None => Ok(None),
+        }
+    }
+
+    /// Move the gains from their initial to their final values over the convergence window
+    fn update_gains(&mut self) {
+        if self.converged() {
+            self.alpha = ALPHA_GAIN_FINAL;
+            self.beta = BETA_GAIN_FINAL;
+            return;
+        }
+        let progress = self.sequence as f64 / CONVERGENCE_WINDOW as f64;
+        let p = 1.0 - (0.5 * (1.0 - 1.0 / (1.0 - progress))).exp();
+        self.alpha = p * ALPHA_GAIN_FINAL + (1.0 - p) * ALPHA_GAIN_INITIAL;
+        self.beta = p * BETA_GAIN_FINAL + (1.0 - p) * BETA_GAIN_INITIAL;
+    }
+
+    /// Exponential smoothing of the offset, with a skew term to follow clock drift
+    fn add_sample(&mut self, offset: f64) {
+        let time_offset_prev = self.time_offset;
+        if self.sequence == 0 {
+            self.time_offset = offset;
+        } else {
+            self.time_offset = self.alpha * offset + (1.0 - self.alpha) * (self.time_offset + self.time_skew);
+            self.time_skew = self.beta * (self.time_offset - time_offset_prev) + (1.0 - self.beta) * self.time_skew;
+        }
+    }
+}
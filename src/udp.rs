@@ -0,0 +1,188 @@
+//! Sending and receiving uORB frames over UDP
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use crate::{read_msg_with_mode, DecodeMode, UorbHeader, UorbMessage, MAX_FRAME_LEN, UORB_MAGIC_V1};
+
+/// Largest payload of a UDP datagram over IPv4
+const MAX_DATAGRAM_LEN: usize = 65_507;
+
+/// Typical payload limit for a datagram that is not fragmented on Ethernet
+pub const DEFAULT_MTU: usize = 1472;
+
+
+/// What has been received from one remote peer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeerStats {
+    /// Datagrams received
+    pub datagrams: u64,
+    /// Frames decoded successfully
+    pub frames: u64,
+    /// Frames that could not be decoded
+    pub errors: u64,
+    /// Datagrams that ended part way through a frame
+    pub truncated: u64,
+    /// Datagrams that were empty, or had bytes after their last frame that do not start another
+    pub malformed: u64,
+    /// Header timestamp of the most recently decoded frame
+    pub last_timestamp: u64,
+}
+
+
+/// Carries frames in UDP datagrams, one frame per datagram unless batching is enabled.
+///
+/// With batching, frames for the same destination are collected until the next frame
+/// would exceed the MTU, or until `flush` is called. A frame larger than the MTU on its
+/// own is sent straight away in a datagram of its own, after any frames batched before it.
+/// Dropping the transport sends the batched frames, ignoring any error.
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: UdpSocket,
+    mode: DecodeMode,
+    batch_mtu: Option<usize>,
+    batch: Vec<u8>,
+    batch_dest: Option<SocketAddr>,
+    recv_buf: Vec<u8>,
+    recv_pos: usize,
+    recv_len: usize,
+    recv_from: Option<SocketAddr>,
+    peers: HashMap<SocketAddr, PeerStats>,
+}
+
+impl UdpTransport {
+    /// Send each frame in its own datagram
+    pub fn new(socket: UdpSocket) -> Self {
+        UdpTransport {
+            socket,
+            mode: DecodeMode::Strict,
+            batch_mtu: None,
+            batch: Vec::new(),
+            batch_dest: None,
+            recv_buf: vec![0; MAX_DATAGRAM_LEN],
+            recv_pos: 0,
+            recv_len: 0,
+            recv_from: None,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Batch frames into datagrams of at most `mtu` bytes
+    pub fn with_batching(socket: UdpSocket, mtu: usize) -> Self {
+        let mut transport = Self::new(socket);
+        transport.batch_mtu = Some(mtu.min(MAX_DATAGRAM_LEN));
+        transport.batch.reserve(mtu);
+        transport
+    }
+
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self> {
+        UdpSocket::bind(addr).map(Self::new)
+    }
+
+    /// Set how strictly received payload lengths must match their message definitions
+    pub fn set_decode_mode(&mut self, mode: DecodeMode) {
+        self.mode = mode;
+    }
+
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Send a frame to `dest`, or queue it when batching
+    pub fn send_msg_to<A: ToSocketAddrs>(&mut self, dest: A, header: &UorbHeader, data: &UorbMessage)
+        -> Result<()> {
+        let dest = dest.to_socket_addrs()?.next()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "no destination address"))?;
+        let mut frame = [0; MAX_FRAME_LEN];
+        let frame_len = data.encode_frame_into(header, &mut frame)?;
+        let frame = &frame[..frame_len];
+
+        let mtu = match self.batch_mtu {
+            Some(mtu) => mtu,
+            None => return self.socket.send_to(frame, dest).map(|_| ()),
+        };
+        if self.batch_dest != Some(dest) || self.batch.len() + frame_len > mtu {
+            self.flush()?;
+        }
+        if frame_len > mtu {
+            return self.socket.send_to(frame, dest).map(|_| ());
+        }
+        self.batch_dest = Some(dest);
+        self.batch.extend_from_slice(frame);
+        Ok(())
+    }
+
+    /// Send any batched frames
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(dest) = self.batch_dest.take() {
+            let res = self.socket.send_to(&self.batch, dest);
+            self.batch.clear();
+            res?;
+        }
+        Ok(())
+    }
+
+    /// Receive the next frame, and the address it came from.
+    ///
+    /// Frames are taken in order from each datagram before the next one is
+    /// received. A datagram that ends part way through a frame, is empty, or has
+    /// stray bytes after its last frame is reported as an `UnexpectedEof` error,
+    /// and the following call moves on to the next datagram.
+    pub fn recv_msg(&mut self) -> Result<(SocketAddr, UorbHeader, UorbMessage)> {
+        if self.recv_pos >= self.recv_len {
+            let (len, from) = self.socket.recv_from(&mut self.recv_buf)?;
+            self.recv_pos = 0;
+            self.recv_len = len;
+            self.recv_from = Some(from);
+            self.peers.entry(from).or_default().datagrams += 1;
+        }
+        let from = self.recv_from.expect("datagram source");
+        let mut remaining = &self.recv_buf[self.recv_pos..self.recv_len];
+        let starts_frame = remaining.contains(&UORB_MAGIC_V1);
+        let res = read_msg_with_mode(&mut remaining, self.mode);
+        self.recv_pos = self.recv_len - remaining.len();
+
+        let stats = self.peers.entry(from).or_default();
+        match res {
+            Ok((header, msg, _fit)) => {
+                stats.frames += 1;
+                stats.last_timestamp = header.timestamp;
+                Ok((from, header, msg))
+            }
+            Err(err) => {
+                if err.kind() == ErrorKind::UnexpectedEof {
+                    if starts_frame {
+                        stats.truncated += 1;
+                    } else {
+                        stats.malformed += 1;
+                    }
+                    self.recv_pos = self.recv_len;
+                } else {
+                    stats.errors += 1;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// What has been received from `peer`, if anything
+    pub fn peer(&self, peer: &SocketAddr) -> Option<&PeerStats> {
+        self.peers.get(peer)
+    }
+
+    /// All peers that have sent at least one datagram
+    pub fn peers(&self) -> impl Iterator<Item = (&SocketAddr, &PeerStats)> {
+        self.peers.iter()
+    }
+}
+
+impl Drop for UdpTransport {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
@@ -0,0 +1,328 @@
+//! PX4 ULog (.ulg) flight log files.
+//!
+//! See the [ULog file format](https://docs.px4.io/main/en/dev_log/ulog_file_format.html).
+//! Data records whose format name matches a known MSG_RAW_NAME are decoded into
+//! `UorbMessage` values, with the subscription's `multi_id` as the instance ID.
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{FieldInfo, FieldType, UorbHeader, UorbMessage};
+
+pub mod reader;
+pub mod writer;
+pub use self::reader::ULogReader;
+pub use self::writer::ULogWriter;
+
+/// Leading bytes of every ULog file, before the version byte
+pub const ULOG_MAGIC: [u8; 7] = [b'U', b'L', b'o', b'g', 0x01, 0x12, 0x35];
+
+/// Size of the file header: magic, version and timestamp
+pub const ULOG_HEADER_LEN: usize = 16;
+
+/// Leading bytes of a synchronization record
+pub const ULOG_SYNC_MAGIC: [u8; 8] = [0x2F, 0x73, 0x13, 0x20, 0x25, 0x0C, 0xBB, 0x12];
+
+/// Size of the flag bits record body
+pub(crate) const FLAG_BITS_LEN: usize = 40;
+
+/// The incompat flag bit that marks appended data, which readers may ignore
+pub(crate) const INCOMPAT_DATA_APPENDED: u8 = 0x01;
+
+/// Record types, as the byte following each record's size
+pub(crate) mod record_type {
+    pub const FLAG_BITS: u8 = b'B';
+    pub const FORMAT: u8 = b'F';
+    pub const INFO: u8 = b'I';
+    pub const MULTI_INFO: u8 = b'M';
+    pub const PARAMETER: u8 = b'P';
+    pub const DEFAULT_PARAMETER: u8 = b'Q';
+    pub const ADD_LOGGED: u8 = b'A';
+    pub const REMOVE_LOGGED: u8 = b'R';
+    pub const DATA: u8 = b'D';
+    pub const LOGGING: u8 = b'L';
+    pub const TAGGED_LOGGING: u8 = b'C';
+    pub const SYNC: u8 = b'S';
+    pub const DROPOUT: u8 = b'O';
+}
+
+
+/// The file header
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ULogHeader {
+    pub version: u8,
+    /// Time at which logging started, in microseconds
+    pub timestamp: u64,
+}
+
+/// Compatibility flags, which must directly follow the file header
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ULogFlags {
+    pub compat_flags: [u8; 8],
+    pub incompat_flags: [u8; 8],
+    pub appended_offsets: [u64; 3],
+}
+
+/// One field of a format definition, such as `float[4] q`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ULogField {
+    /// A primitive C type name such as `uint64_t`, or the name of a nested format
+    pub type_name: String,
+    pub array_len: Option<usize>,
+    pub name: String,
+}
+
+impl ULogField {
+    pub fn parse(desc: &str) -> Option<ULogField> {
+        let mut toks = desc.split_whitespace();
+        let type_desc = toks.next()?;
+        let name = toks.next()?.to_string();
+        let (type_name, array_len) = match type_desc.find('[') {
+            Some(start) if type_desc.ends_with(']') => {
+                let len = type_desc[start + 1..type_desc.len() - 1].parse().ok()?;
+                (type_desc[..start].to_string(), Some(len))
+            }
+            _ => (type_desc.to_string(), None),
+        };
+        Some(ULogField { type_name, array_len, name })
+    }
+
+    /// The primitive type of this field, or None for a nested format
+    pub fn field_type(&self) -> Option<FieldType> {
+        FieldType::from_ulog_name(&self.type_name)
+    }
+
+    /// Padding inserted by PX4 to match the C struct layout
+    pub fn is_padding(&self) -> bool {
+        self.name.starts_with("_padding")
+    }
+}
+
+/// A format definition, describing the layout of a logged message
+#[derive(Debug, Clone, PartialEq)]
+pub struct ULogFormat {
+    pub name: String,
+    pub fields: Vec<ULogField>,
+}
+
+impl ULogFormat {
+    /// Parse a definition such as `sensor_gyro:uint64_t timestamp;float x;`
+    pub fn parse(desc: &str) -> Option<ULogFormat> {
+        let colon = desc.find(':')?;
+        let fields = desc[colon + 1..].split(';')
+            .filter(|field| !field.trim().is_empty())
+            .map(ULogField::parse)
+            .collect::<Option<Vec<ULogField>>>()?;
+        Some(ULogFormat { name: desc[..colon].to_string(), fields })
+    }
+
+    /// The format of a generated message type, with fields in encoded order
+    pub fn from_fields(name: &str, fields: &[FieldInfo]) -> ULogFormat {
+        ULogFormat {
+            name: name.to_string(),
+            fields: fields.iter()
+                .map(|field| ULogField {
+                    type_name: field.field_type.ulog_name().to_string(),
+                    array_len: field.array_len,
+                    name: field.name.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Render as the text of a format record
+    pub fn to_definition(&self) -> String {
+        let mut desc = format!("{}:", self.name);
+        for field in &self.fields {
+            match field.array_len {
+                Some(len) => desc.push_str(&format!("{}[{}] {};", field.type_name, len, field.name)),
+                None => desc.push_str(&format!("{} {};", field.type_name, field.name)),
+            }
+        }
+        desc
+    }
+}
+
+/// A key and value, as carried by info and parameter records
+#[derive(Debug, Clone, PartialEq)]
+pub struct ULogInfo {
+    /// The type of the value, such as `char[5]`, `int32_t` or `float`
+    pub key_type: String,
+    pub key: String,
+    pub value: Vec<u8>,
+}
+
+impl ULogInfo {
+    fn new(key_type: &str, key: &str, value: Vec<u8>) -> ULogInfo {
+        ULogInfo { key_type: key_type.to_string(), key: key.to_string(), value }
+    }
+
+    /// A `char[n]` value, as used for info such as `sys_name`
+    pub fn string(key: &str, value: &str) -> ULogInfo {
+        Self::new(&format!("char[{}]", value.len()), key, value.as_bytes().to_vec())
+    }
+
+    pub fn int32(key: &str, value: i32) -> ULogInfo {
+        Self::new("int32_t", key, value.to_le_bytes().to_vec())
+    }
+
+    pub fn uint32(key: &str, value: u32) -> ULogInfo {
+        Self::new("uint32_t", key, value.to_le_bytes().to_vec())
+    }
+
+    pub fn uint64(key: &str, value: u64) -> ULogInfo {
+        Self::new("uint64_t", key, value.to_le_bytes().to_vec())
+    }
+
+    pub fn float(key: &str, value: f32) -> ULogInfo {
+        Self::new("float", key, value.to_le_bytes().to_vec())
+    }
+
+    /// Parse the `key_len`-prefixed key and value that make up the body of the record
+    pub(crate) fn parse(body: &[u8]) -> Option<ULogInfo> {
+        let key_len = *body.first()? as usize;
+        let key_desc = std::str::from_utf8(body.get(1..1 + key_len)?).ok()?;
+        let mut toks = key_desc.split_whitespace();
+        let key_type = toks.next()?.to_string();
+        let key = toks.next()?.to_string();
+        Some(ULogInfo { key_type, key, value: body[1 + key_len..].to_vec() })
+    }
+
+    /// The record body that `parse` reads, or None if the key is too long
+    pub(crate) fn to_body(&self) -> Option<Vec<u8>> {
+        let key_desc = format!("{} {}", self.key_type, self.key);
+        if key_desc.len() > u8::MAX as usize {
+            return None;
+        }
+        let mut body = Vec::with_capacity(1 + key_desc.len() + self.value.len());
+        body.push(key_desc.len() as u8);
+        body.extend_from_slice(key_desc.as_bytes());
+        body.extend_from_slice(&self.value);
+        Some(body)
+    }
+
+    /// The value of a `char[n]` info, such as `sys_name`
+    pub fn as_str(&self) -> Option<&str> {
+        if !self.key_type.starts_with("char") {
+            return None;
+        }
+        std::str::from_utf8(&self.value).ok()
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        if self.key_type != "int32_t" || self.value.len() < 4 {
+            return None;
+        }
+        Some(LittleEndian::read_i32(&self.value))
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        if self.key_type != "uint32_t" || self.value.len() < 4 {
+            return None;
+        }
+        Some(LittleEndian::read_u32(&self.value))
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        if self.key_type != "uint64_t" || self.value.len() < 8 {
+            return None;
+        }
+        Some(LittleEndian::read_u64(&self.value))
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        if self.key_type != "float" || self.value.len() < 4 {
+            return None;
+        }
+        Some(LittleEndian::read_f32(&self.value))
+    }
+}
+
+/// Subscription of a message id to one instance of a logged topic
+#[derive(Debug, Clone, PartialEq)]
+pub struct ULogSubscription {
+    pub multi_id: u8,
+    pub msg_id: u16,
+    /// Name of the topic's format
+    pub name: String,
+}
+
+/// One record of a ULog file
+// data records are by far the most common, so they are not boxed
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ULogRecord {
+    Flags(ULogFlags),
+    Format(ULogFormat),
+    Info(ULogInfo),
+    /// A multi-record info, which may continue the previous one with the same key
+    MultiInfo { is_continued: bool, info: ULogInfo },
+    Parameter(ULogInfo),
+    /// A default parameter value; `default_types` is a bitfield of which defaults it is
+    DefaultParameter { default_types: u8, param: ULogInfo },
+    AddLogged(ULogSubscription),
+    RemoveLogged { msg_id: u16 },
+    /// A data record of a known message
+    Data(UorbHeader, UorbMessage),
+    /// A data record whose format does not match any known message
+    RawData { subscription: ULogSubscription, payload: Vec<u8> },
+    /// A logged string; `tag` is only set for tagged logging records
+    Logging { level: u8, tag: Option<u16>, timestamp: u64, message: String },
+    Sync,
+    /// Data was lost for `duration_ms` milliseconds
+    Dropout { duration_ms: u16 },
+    /// A record type this reader does not know
+    Unknown { record_type: u8, body: Vec<u8> },
+}
+
+
+/// Encoded size and field offsets of each format, resolving nested formats
+#[derive(Debug, Default)]
+pub(crate) struct FormatLayouts {
+    formats: HashMap<String, ULogFormat>,
+}
+
+impl FormatLayouts {
+    pub fn insert(&mut self, format: ULogFormat) {
+        self.formats.insert(format.name.clone(), format);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ULogFormat> {
+        self.formats.get(name)
+    }
+
+    /// Encoded size of one element of `type_name`
+    fn type_len(&self, type_name: &str, depth: usize) -> Option<usize> {
+        if let Some(field_type) = FieldType::from_ulog_name(type_name) {
+            return Some(field_type.encoded_len());
+        }
+        if depth > 8 {
+            // guard against self-referential definitions
+            return None;
+        }
+        let format = self.formats.get(type_name)?;
+        format.fields.iter()
+            .map(|field| self.type_len(&field.type_name, depth + 1)
+                .map(|len| len * field.array_len.unwrap_or(1)))
+            .sum()
+    }
+
+    /// The offset of every top-level field of the named format, and the total size
+    pub fn offsets(&self, name: &str) -> Option<(Vec<usize>, usize)> {
+        let format = self.formats.get(name)?;
+        let mut offsets = Vec::with_capacity(format.fields.len());
+        let mut offset = 0;
+        for field in &format.fields {
+            offsets.push(offset);
+            offset += self.type_len(&field.type_name, 0)? * field.array_len.unwrap_or(1);
+        }
+        Some((offsets, offset))
+    }
+}
+
+
+pub(crate) fn invalid_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
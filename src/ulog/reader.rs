@@ -0,0 +1,314 @@
+//! Reading ULog files record by record
+
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Result};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use super::{invalid_data, record_type, FormatLayouts, ULogFlags, ULogFormat, ULogHeader, ULogInfo,
+            ULogRecord, ULogSubscription, FLAG_BITS_LEN, INCOMPAT_DATA_APPENDED, ULOG_HEADER_LEN,
+            ULOG_MAGIC};
+use crate::{UorbHeader, UorbMessage, UORB_MAGIC_V1};
+
+
+/// How to rebuild a generated message payload from a logged data record
+#[derive(Debug)]
+struct DataMapping {
+    hash: u16,
+    encoded_len: usize,
+    /// Size of the logged record, without its message id
+    logged_len: usize,
+    timestamp_offset: Option<usize>,
+    /// (logged offset, payload offset, length) of each field found in both layouts
+    copies: Vec<(usize, usize, usize)>,
+}
+
+impl DataMapping {
+    /// Match the fields of a logged format to the message with the same raw name.
+    /// Fields missing from the log keep their default value, but a field whose
+    /// type differs means the format does not describe the same message.
+    fn new(layouts: &FormatLayouts, format_name: &str) -> Option<DataMapping> {
+        let hash = UorbMessage::hash_for_raw_name(format_name)?;
+        let fields = UorbMessage::fields_for_hash(hash)?;
+        let format = layouts.get(format_name)?;
+        let (offsets, logged_len) = layouts.offsets(format_name)?;
+
+        let mut copies = vec![];
+        let mut timestamp_offset = None;
+        for (logged, &logged_offset) in format.fields.iter().zip(offsets.iter()) {
+            if logged.is_padding() {
+                continue;
+            }
+            let field = match fields.iter().find(|field| field.name == logged.name) {
+                Some(field) => field,
+                None => continue,
+            };
+            if logged.field_type() != Some(field.field_type) || logged.array_len != field.array_len {
+                return None;
+            }
+            if field.name == "timestamp" && field.array_len.is_none() {
+                timestamp_offset = Some(logged_offset);
+            }
+            copies.push((logged_offset, field.offset, field.encoded_len()));
+        }
+        Some(DataMapping {
+            hash,
+            encoded_len: UorbMessage::encoded_len_for_hash(hash)?,
+            logged_len,
+            timestamp_offset,
+            copies,
+        })
+    }
+
+    fn decode(&self, logged: &[u8], instance_id: u8) -> Option<(UorbHeader, UorbMessage)> {
+        if logged.len() < self.logged_len {
+            return None;
+        }
+        let mut payload = vec![0; self.encoded_len];
+        for &(from, to, len) in &self.copies {
+            payload[to..to + len].copy_from_slice(&logged[from..from + len]);
+        }
+        let msg = UorbMessage::parse(self.hash, &payload)?;
+        let header = UorbHeader {
+            version: UORB_MAGIC_V1,
+            hash: self.hash,
+            timestamp: self.timestamp_offset.map_or(0, |offset| LittleEndian::read_u64(&logged[offset..])),
+            instance_id,
+            payload_len: self.encoded_len,
+        };
+        Some((header, msg))
+    }
+}
+
+#[derive(Debug)]
+struct Subscription {
+    info: ULogSubscription,
+    mapping: Option<DataMapping>,
+}
+
+
+/// Read `buf.len()` bytes unless the stream ends first, returning the number read
+fn read_up_to<R: Read>(r: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(len) => filled += len,
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+fn read_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+
+/// Reads the records of a ULog file in order.
+///
+/// As an iterator, yields every record until the end of the file. A log cut off
+/// part way through a record, as when power is lost in flight, simply ends there.
+#[derive(Debug)]
+pub struct ULogReader<R> {
+    inner: R,
+    header: ULogHeader,
+    layouts: FormatLayouts,
+    subscriptions: HashMap<u16, Subscription>,
+}
+
+impl<R: Read> ULogReader<R> {
+    /// Read the file header from `inner`
+    pub fn new(mut inner: R) -> Result<Self> {
+        let mut header = [0; ULOG_HEADER_LEN];
+        inner.read_exact(&mut header)?;
+        if header[..ULOG_MAGIC.len()] != ULOG_MAGIC {
+            return Err(invalid_data("not a ULog file"));
+        }
+        Ok(ULogReader {
+            inner,
+            header: ULogHeader { version: header[7], timestamp: LittleEndian::read_u64(&header[8..]) },
+            layouts: FormatLayouts::default(),
+            subscriptions: HashMap::new(),
+        })
+    }
+
+    pub fn header(&self) -> &ULogHeader {
+        &self.header
+    }
+
+    /// The format definition with the given name, once its record has been read
+    pub fn format(&self, name: &str) -> Option<&ULogFormat> {
+        self.layouts.get(name)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Read the next record, or None at the end of the file
+    pub fn read_record(&mut self) -> Result<Option<ULogRecord>> {
+        let mut record_header = [0; 3];
+        if read_up_to(&mut self.inner, &mut record_header)? < record_header.len() {
+            return Ok(None);
+        }
+        let body_len = LittleEndian::read_u16(&record_header) as usize;
+        let mut body = vec![0; body_len];
+        if read_up_to(&mut self.inner, &mut body)? < body_len {
+            return Ok(None);
+        }
+        self.decode_record(record_header[2], body).map(Some)
+    }
+
+    /// Read records until the next one of a known message, or None at the end of the file
+    pub fn read_msg(&mut self) -> Result<Option<(UorbHeader, UorbMessage)>> {
+        while let Some(record) = self.read_record()? {
+            if let ULogRecord::Data(header, msg) = record {
+                return Ok(Some((header, msg)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Iterate over just the known messages, skipping all other records
+    pub fn messages(self) -> ULogMessages<R> {
+        ULogMessages { reader: self }
+    }
+
+    fn decode_record(&mut self, kind: u8, body: Vec<u8>) -> Result<ULogRecord> {
+        let short = || invalid_data("ULog record too short");
+        let record = match kind {
+            record_type::FLAG_BITS => {
+                if body.len() < FLAG_BITS_LEN {
+                    return Err(short());
+                }
+                let mut flags = ULogFlags::default();
+                flags.compat_flags.copy_from_slice(&body[..8]);
+                flags.incompat_flags.copy_from_slice(&body[8..16]);
+                LittleEndian::read_u64_into(&body[16..FLAG_BITS_LEN], &mut flags.appended_offsets);
+                let unknown_incompat = flags.incompat_flags[0] & !INCOMPAT_DATA_APPENDED != 0
+                    || flags.incompat_flags[1..].iter().any(|&bits| bits != 0);
+                if unknown_incompat {
+                    return Err(invalid_data("ULog file uses unknown incompatible features"));
+                }
+                ULogRecord::Flags(flags)
+            }
+            record_type::FORMAT => {
+                let format = ULogFormat::parse(&read_string(&body))
+                    .ok_or_else(|| invalid_data("invalid ULog format definition"))?;
+                self.layouts.insert(format.clone());
+                ULogRecord::Format(format)
+            }
+            record_type::INFO => ULogRecord::Info(ULogInfo::parse(&body).ok_or_else(short)?),
+            record_type::MULTI_INFO => {
+                let info = body.get(1..).and_then(ULogInfo::parse).ok_or_else(short)?;
+                ULogRecord::MultiInfo { is_continued: body[0] != 0, info }
+            }
+            record_type::PARAMETER => ULogRecord::Parameter(ULogInfo::parse(&body).ok_or_else(short)?),
+            record_type::DEFAULT_PARAMETER => {
+                let param = body.get(1..).and_then(ULogInfo::parse).ok_or_else(short)?;
+                ULogRecord::DefaultParameter { default_types: body[0], param }
+            }
+            record_type::ADD_LOGGED => {
+                if body.len() < 3 {
+                    return Err(short());
+                }
+                let info = ULogSubscription {
+                    multi_id: body[0],
+                    msg_id: LittleEndian::read_u16(&body[1..]),
+                    name: read_string(&body[3..]),
+                };
+                let mapping = DataMapping::new(&self.layouts, &info.name);
+                self.subscriptions.insert(info.msg_id, Subscription { info: info.clone(), mapping });
+                ULogRecord::AddLogged(info)
+            }
+            record_type::REMOVE_LOGGED => {
+                if body.len() < 2 {
+                    return Err(short());
+                }
+                let msg_id = LittleEndian::read_u16(&body);
+                self.subscriptions.remove(&msg_id);
+                ULogRecord::RemoveLogged { msg_id }
+            }
+            record_type::DATA => {
+                if body.len() < 2 {
+                    return Err(short());
+                }
+                let msg_id = LittleEndian::read_u16(&body);
+                let subscription = self.subscriptions.get(&msg_id)
+                    .ok_or_else(|| invalid_data("ULog data for an unknown msg_id"))?;
+                let logged = &body[2..];
+                let decoded = subscription.mapping.as_ref()
+                    .and_then(|mapping| mapping.decode(logged, subscription.info.multi_id));
+                match decoded {
+                    Some((header, msg)) => ULogRecord::Data(header, msg),
+                    None => ULogRecord::RawData {
+                        subscription: subscription.info.clone(),
+                        payload: logged.to_vec(),
+                    },
+                }
+            }
+            record_type::LOGGING => {
+                if body.len() < 9 {
+                    return Err(short());
+                }
+                ULogRecord::Logging {
+                    level: body[0],
+                    tag: None,
+                    timestamp: LittleEndian::read_u64(&body[1..]),
+                    message: read_string(&body[9..]),
+                }
+            }
+            record_type::TAGGED_LOGGING => {
+                if body.len() < 11 {
+                    return Err(short());
+                }
+                ULogRecord::Logging {
+                    level: body[0],
+                    tag: Some(LittleEndian::read_u16(&body[1..])),
+                    timestamp: LittleEndian::read_u64(&body[3..]),
+                    message: read_string(&body[11..]),
+                }
+            }
+            record_type::SYNC => ULogRecord::Sync,
+            record_type::DROPOUT => {
+                if body.len() < 2 {
+                    return Err(short());
+                }
+                ULogRecord::Dropout { duration_ms: LittleEndian::read_u16(&body) }
+            }
+            _ => ULogRecord::Unknown { record_type: kind, body },
+        };
+        Ok(record)
+    }
+}
+
+impl<R: Read> Iterator for ULogReader<R> {
+    type Item = Result<ULogRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+
+/// Iterator returned by `ULogReader::messages`
+#[derive(Debug)]
+pub struct ULogMessages<R> {
+    reader: ULogReader<R>,
+}
+
+impl<R: Read> ULogMessages<R> {
+    pub fn into_reader(self) -> ULogReader<R> {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for ULogMessages<R> {
+    type Item = Result<(UorbHeader, UorbMessage)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.read_msg().transpose()
+    }
+}
@@ -0,0 +1,166 @@
+//! Writing ULog files from decoded message streams
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result, Write};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use super::{record_type, ULogFormat, ULogInfo, FLAG_BITS_LEN, ULOG_MAGIC};
+use crate::{UorbHeader, UorbMessage};
+
+
+/// Bytes of records held before they are written out to the inner writer
+pub const DEFAULT_FLUSH_THRESHOLD: usize = 4096;
+
+fn invalid_input(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, msg.to_string())
+}
+
+
+/// Writes a ULog file that PX4 tools such as pyulog can open.
+///
+/// The definitions section, holding a format for every known message, is closed
+/// by the first message written; info and parameter records may come before or
+/// after. Each (topic, instance) gets its own subscription, with the instance ID
+/// as `multi_id`. The logged timestamp is the message's own `timestamp` field.
+///
+/// Records are buffered and written out once `flush_threshold` bytes are held,
+/// or on `flush`. Like `BufWriter`, dropping the writer flushes it but ignores any
+/// error, so call `into_inner` or `flush` to learn whether the log was written.
+#[derive(Debug)]
+pub struct ULogWriter<W: Write> {
+    /// Only taken by `into_inner`
+    inner: Option<W>,
+    buf: Vec<u8>,
+    flush_threshold: usize,
+    in_definitions: bool,
+    msg_ids: HashMap<(u16, u8), u16>,
+}
+
+impl<W: Write> ULogWriter<W> {
+    /// Start a log whose logging began at `timestamp` microseconds
+    pub fn new(inner: W, timestamp: u64) -> Result<Self> {
+        let mut writer = ULogWriter {
+            inner: Some(inner),
+            buf: Vec::with_capacity(DEFAULT_FLUSH_THRESHOLD),
+            flush_threshold: DEFAULT_FLUSH_THRESHOLD,
+            in_definitions: true,
+            msg_ids: HashMap::new(),
+        };
+        writer.buf.extend_from_slice(&ULOG_MAGIC);
+        writer.buf.push(1);
+        writer.buf.write_u64::<LittleEndian>(timestamp)?;
+        writer.write_record(record_type::FLAG_BITS, &[0; FLAG_BITS_LEN])?;
+        Ok(writer)
+    }
+
+    pub fn set_flush_threshold(&mut self, flush_threshold: usize) {
+        self.flush_threshold = flush_threshold;
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Flush any buffered records and return the inner writer
+    pub fn into_inner(mut self) -> Result<W> {
+        self.flush()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    /// Add an info record, such as `ULogInfo::string("sys_name", "PX4")`
+    pub fn write_info(&mut self, info: &ULogInfo) -> Result<()> {
+        let body = info.to_body().ok_or_else(|| invalid_input("ULog info key too long"))?;
+        self.write_record(record_type::INFO, &body)
+    }
+
+    /// Add a parameter record. Those written after the first message log a
+    /// parameter change.
+    pub fn write_parameter(&mut self, param: &ULogInfo) -> Result<()> {
+        let body = param.to_body().ok_or_else(|| invalid_input("ULog parameter name too long"))?;
+        self.write_record(record_type::PARAMETER, &body)
+    }
+
+    /// Add a data record, subscribing to the message's topic instance on first use
+    pub fn write_msg(&mut self, header: &UorbHeader, msg: &UorbMessage) -> Result<()> {
+        if UorbMessage::encoded_len_for_hash(header.hash) != Some(msg.encoded_len()) {
+            return Err(invalid_input("message does not match header hash"));
+        }
+        self.end_definitions()?;
+
+        let key = (header.hash, header.instance_id);
+        let msg_id = match self.msg_ids.get(&key) {
+            Some(&msg_id) => msg_id,
+            None => self.add_logged(header.hash, header.instance_id)?,
+        };
+        let mut body = Vec::with_capacity(2 + msg.encoded_len());
+        body.write_u16::<LittleEndian>(msg_id)?;
+        msg.ser_into(&mut body);
+        self.write_record(record_type::DATA, &body)
+    }
+
+    /// Write out buffered records and flush the inner writer
+    pub fn flush(&mut self) -> Result<()> {
+        self.write_buffered()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+
+    fn add_logged(&mut self, hash: u16, instance_id: u8) -> Result<u16> {
+        let name = UorbMessage::raw_name_for_hash(hash)
+            .ok_or_else(|| invalid_input("unknown message hash"))?;
+        if self.msg_ids.len() > u16::MAX as usize {
+            return Err(invalid_input("too many ULog subscriptions"));
+        }
+        let msg_id = self.msg_ids.len() as u16;
+        let mut body = vec![instance_id];
+        body.write_u16::<LittleEndian>(msg_id)?;
+        body.extend_from_slice(name.as_bytes());
+        self.write_record(record_type::ADD_LOGGED, &body)?;
+        self.msg_ids.insert((hash, instance_id), msg_id);
+        Ok(msg_id)
+    }
+
+    /// Write the format of every known message, which must all come before the
+    /// first subscription
+    fn end_definitions(&mut self) -> Result<()> {
+        if !self.in_definitions {
+            return Ok(());
+        }
+        self.in_definitions = false;
+        for &hash in UorbMessage::KNOWN_HASHES {
+            if let (Some(name), Some(fields)) =
+                (UorbMessage::raw_name_for_hash(hash), UorbMessage::fields_for_hash(hash)) {
+                let definition = ULogFormat::from_fields(name, fields).to_definition();
+                self.write_record(record_type::FORMAT, definition.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_record(&mut self, kind: u8, body: &[u8]) -> Result<()> {
+        if body.len() > u16::MAX as usize {
+            return Err(invalid_input("ULog record too long"));
+        }
+        self.buf.write_u16::<LittleEndian>(body.len() as u16)?;
+        self.buf.push(kind);
+        self.buf.extend_from_slice(body);
+        if self.buf.len() >= self.flush_threshold {
+            self.write_buffered()?;
+        }
+        Ok(())
+    }
+
+    fn write_buffered(&mut self) -> Result<()> {
+        self.inner.as_mut().unwrap().write_all(&self.buf)?;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for ULogWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.flush();
+        }
+    }
+}
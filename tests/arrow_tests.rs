@@ -0,0 +1,131 @@
+#![cfg(feature = "arrow")]
+
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_arrow {
+    use crate::test_shared;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Float32Type, UInt64Type};
+    use arrow_array::RecordBatch;
+    use arrow_schema::DataType;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::fs::File;
+    use std::io::Cursor;
+    use std::path::Path;
+    use uorb_codec::arrow::{self, BatchBuilder, ParquetExporter};
+    use uorb_codec::common::{EstimatorStatusData, SensorGyroData, UorbMessage, VehicleAttitudeData};
+    use uorb_codec::recording::Recorder;
+    use uorb_codec::{write_msg, UorbHeader, UorbMsgMeta};
+
+    /// Gyro instances 0 and 1, and attitude
+    fn frames() -> Vec<(UorbHeader, UorbMessage)> {
+        let mut frames = Vec::new();
+        for idx in 0..5u64 {
+            for instance in 0..2 {
+                let gyro = SensorGyroData {
+                    timestamp: 1_000_000 + idx * 10_000,
+                    x: idx as f32,
+                    ..test_shared::get_sensor_gyro()
+                };
+                frames.push(gyro.gen_ready_pair(instance, gyro.timestamp));
+            }
+            let attitude = VehicleAttitudeData {
+                timestamp: 1_000_000 + idx * 10_000,
+                q: [1.0, 0.0, 0.0, idx as f32],
+                ..Default::default()
+            };
+            frames.push(attitude.gen_ready_pair(0, attitude.timestamp));
+        }
+        frames
+    }
+
+    fn read_parquet(path: &Path) -> Vec<RecordBatch> {
+        ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap()).unwrap()
+            .build().unwrap()
+            .map(|batch| batch.unwrap())
+            .collect()
+    }
+
+    fn column_u64(batch: &RecordBatch, name: &str) -> Vec<u64> {
+        batch.column_by_name(name).unwrap().as_primitive::<UInt64Type>().values().to_vec()
+    }
+
+    #[test]
+    pub fn test_schema_and_batch() {
+        let schema = arrow::schema(EstimatorStatusData::MSG_HASH_CODE).unwrap();
+        assert_eq!(schema.field(0).name(), "timestamp");
+        assert_eq!(schema.metadata()["uorb.name"], "estimator_status");
+        match schema.field_with_name("states").unwrap().data_type() {
+            DataType::FixedSizeList(item, 24) => assert_eq!(item.data_type(), &DataType::Float32),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(arrow::schema(0xFFFF).is_none());
+
+        let mut builder = BatchBuilder::new(VehicleAttitudeData::MSG_HASH_CODE).unwrap();
+        for (header, msg) in frames().iter().filter(|(header, _)| header.hash == VehicleAttitudeData::MSG_HASH_CODE) {
+            builder.append(header, msg).unwrap();
+        }
+        let (header, msg) = test_shared::get_sensor_gyro().gen_ready_pair(0, 0);
+        assert!(builder.append(&header, &msg).is_err());
+        assert_eq!(builder.len(), 5);
+
+        let batch = builder.finish().unwrap();
+        assert!(builder.is_empty());
+        assert_eq!(batch.num_rows(), 5);
+        assert_eq!(column_u64(&batch, "timestamp"), vec![1_000_000, 1_010_000, 1_020_000, 1_030_000, 1_040_000]);
+        let q = batch.column_by_name("q").unwrap().as_fixed_size_list();
+        let q3 = q.value(3);
+        assert_eq!(q3.as_primitive::<Float32Type>().values().to_vec(), vec![1.0, 0.0, 0.0, 3.0]);
+    }
+
+    #[test]
+    pub fn test_parquet_partitions() {
+        let mut stream = Vec::new();
+        for (header, msg) in frames() {
+            write_msg(&mut stream, &header, &msg).unwrap();
+        }
+        let dir = std::env::temp_dir().join(format!("uorb-parquet-test-{}", std::process::id()));
+        let mut exporter = ParquetExporter::new(&dir).unwrap();
+        exporter.set_batch_rows(2);
+        assert_eq!(exporter.export_stream(&mut stream.as_slice()).unwrap(), frames().len() as u64);
+        let files = exporter.finish().unwrap();
+        let mut partitions: Vec<String> = files.iter()
+            .map(|path| path.strip_prefix(&dir).unwrap().to_str().unwrap().to_string())
+            .collect();
+        partitions.sort();
+        assert_eq!(partitions, vec![
+            "topic=sensor_gyro/instance=0/data.parquet",
+            "topic=sensor_gyro/instance=1/data.parquet",
+            "topic=vehicle_attitude/instance=0/data.parquet",
+        ]);
+
+        let gyro_path = dir.join(ParquetExporter::partition_path("sensor_gyro", 1));
+        let batches = read_parquet(&gyro_path);
+        let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+        assert_eq!(rows, 5);
+        let x: Vec<f32> = batches.iter()
+            .flat_map(|batch| batch.column_by_name("x").unwrap().as_primitive::<Float32Type>().values().to_vec())
+            .collect();
+        assert_eq!(x, vec![0.0, 1.0, 2.0, 3.0, 4.0]);
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&gyro_path).unwrap()).unwrap();
+        assert_eq!(reader.schema().metadata()["uorb.name"], "sensor_gyro");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // a recording gives the same rows
+        let mut recorder = Recorder::new(Vec::new()).unwrap();
+        for (header, msg) in frames() {
+            recorder.record(header.timestamp, &header, &msg).unwrap();
+        }
+        let mut exporter = ParquetExporter::new(&dir).unwrap();
+        exporter.export_recording(Cursor::new(recorder.finish().unwrap())).unwrap();
+        assert_eq!(exporter.files().len(), 3);
+        exporter.finish().unwrap();
+        let batches = read_parquet(&dir.join(ParquetExporter::partition_path("vehicle_attitude", 0)));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(column_u64(&batches[0], "timestamp")[4], 1_040_000);
+    }
+}
@@ -0,0 +1,240 @@
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_bridge {
+    use crate::test_shared;
+    use std::io::{Read, Result, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc::{channel, Receiver, Sender};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use uorb_codec::bridge::{Bridge, DropPolicy, TopicFilter};
+    use uorb_codec::common::{VehicleGpsPositionData, VehicleStatusData};
+    use uorb_codec::{UorbMessage, UorbMsgMeta, UorbReader};
+
+    /// Collects written frames. When gated, announces each write on the first
+    /// channel, then waits for a token on the second before completing it.
+    struct GatedWriter {
+        gate: Option<(Sender<()>, Receiver<()>)>,
+        out: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Write for GatedWriter {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            if let Some((entered, release)) = &self.gate {
+                let _ = entered.send(());
+                release.recv().unwrap();
+            }
+            self.out.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn collecting_writer() -> (GatedWriter, Arc<Mutex<Vec<u8>>>) {
+        let out = Arc::new(Mutex::new(vec![]));
+        (GatedWriter { gate: None, out: out.clone() }, out)
+    }
+
+    fn timestamps(out: &Arc<Mutex<Vec<u8>>>) -> Vec<u64> {
+        let frames = out.lock().unwrap().clone();
+        UorbReader::new(frames.as_slice()).map(|res| res.unwrap().0.timestamp).collect()
+    }
+
+    #[test]
+    pub fn test_bridge_tcp_fan_out() {
+        let bridge = Bridge::new(16, DropPolicy::DropNewest);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        bridge.serve(listener);
+
+        let clients: Vec<TcpStream> = (0..2).map(|_| TcpStream::connect(addr).unwrap()).collect();
+        while bridge.client_count() < 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let status = test_shared::get_vehicle_status();
+        let mut upstream = vec![];
+        for timestamp in 0..3 {
+            let (hdr, msg) = status.gen_ready_pair(0, timestamp);
+            uorb_codec::write_msg(&mut upstream, &hdr, &msg).unwrap();
+        }
+        bridge.run(upstream.as_slice()).unwrap();
+
+        for client in clients {
+            client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let received: Vec<u64> = UorbReader::new(client).take(3)
+                .map(|res| res.unwrap().0.timestamp)
+                .collect();
+            assert_eq!(received, vec![0, 1, 2]);
+        }
+    }
+
+    #[test]
+    pub fn test_bridge_tcp_hash_requests() {
+        let bridge = Bridge::new(16, DropPolicy::DropNewest);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        bridge.serve(listener);
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        while bridge.client_count() < 1 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        client.write_all(&VehicleStatusData::MSG_HASH_CODE.to_be_bytes()).unwrap();
+
+        // the request is applied by another thread, so send a gps and a status frame
+        // with the same timestamp until the gps frame no longer gets through
+        let gps = test_shared::get_vehicle_gps_position();
+        let status = test_shared::get_vehicle_status();
+        let mut reader = UorbReader::new(client.try_clone().unwrap());
+        let mut timestamp = 0;
+        loop {
+            assert!(timestamp < 1000, "hash request never applied");
+            for (hdr, msg) in [gps.gen_ready_pair(0, timestamp), status.gen_ready_pair(0, timestamp)] {
+                bridge.forward(&hdr, &msg).unwrap();
+            }
+            let (hdr, _) = reader.next().unwrap().unwrap();
+            if hdr.hash == VehicleStatusData::MSG_HASH_CODE {
+                assert_eq!(hdr.timestamp, timestamp);
+                break;
+            }
+            assert_eq!(reader.next().unwrap().unwrap().0.hash, VehicleStatusData::MSG_HASH_CODE);
+            timestamp += 1;
+        }
+
+        for timestamp in 1000..1003 {
+            for (hdr, msg) in [gps.gen_ready_pair(0, timestamp), status.gen_ready_pair(0, timestamp)] {
+                bridge.forward(&hdr, &msg).unwrap();
+            }
+        }
+        let received: Vec<(u16, u64)> = reader.take(3)
+            .map(|res| res.map(|(hdr, _)| (hdr.hash, hdr.timestamp)).unwrap())
+            .collect();
+        let hash = VehicleStatusData::MSG_HASH_CODE;
+        assert_eq!(received, vec![(hash, 1000), (hash, 1001), (hash, 1002)]);
+    }
+
+    #[test]
+    pub fn test_bridge_shutdown_stalled_client() {
+        let bridge = Bridge::new(4, DropPolicy::DropNewest);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        bridge.serve(listener);
+        // connected, but never reads
+        let _client = TcpStream::connect(addr).unwrap();
+        while bridge.client_count() < 1 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // drops only pile up once the socket buffers are full and the writer is blocked
+        let gps = test_shared::get_vehicle_gps_position();
+        let (hdr, msg) = gps.gen_ready_pair(0, 0);
+        let start = Instant::now();
+        while bridge.dropped() < 10_000 {
+            assert!(start.elapsed() < Duration::from_secs(30), "client never stalled");
+            bridge.forward(&hdr, &msg).unwrap();
+        }
+
+        let start = Instant::now();
+        bridge.shutdown_within(Duration::from_millis(50));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(bridge.client_count(), 0);
+    }
+
+    #[test]
+    pub fn test_bridge_shutdown_idle_client() {
+        let bridge = Bridge::new(4, DropPolicy::DropNewest);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        bridge.serve(listener);
+        let mut client = TcpStream::connect(addr).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        while bridge.client_count() < 1 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        bridge.shutdown();
+        let mut buf = [0; 1];
+        assert_eq!(client.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    pub fn test_bridge_topic_filter() {
+        let bridge = Bridge::new(16, DropPolicy::DropNewest);
+        let (all_writer, all_out) = collecting_writer();
+        let (status_writer, status_out) = collecting_writer();
+        bridge.add_client(all_writer, TopicFilter::All);
+        let mut filter = TopicFilter::All;
+        filter.subscribe(VehicleStatusData::MSG_HASH_CODE);
+        bridge.add_client(status_writer, filter);
+
+        let gps = test_shared::get_vehicle_gps_position();
+        let status = test_shared::get_vehicle_status();
+        let (hdr, msg) = gps.gen_ready_pair(0, 1);
+        bridge.forward(&hdr, &msg).unwrap();
+        let (hdr, msg) = status.gen_ready_pair(0, 2);
+        bridge.forward(&hdr, &msg).unwrap();
+        bridge.shutdown();
+
+        assert_eq!(timestamps(&all_out), vec![1, 2]);
+        let frames = status_out.lock().unwrap().clone();
+        let received: Vec<UorbMessage> = UorbReader::new(frames.as_slice())
+            .map(|res| res.unwrap().1)
+            .collect();
+        assert_eq!(received, vec![status.wrap()]);
+        assert!(!TopicFilter::Only(Default::default()).matches(VehicleGpsPositionData::MSG_HASH_CODE));
+    }
+
+    fn slow_client_drops(policy: DropPolicy) -> (Vec<u64>, Vec<u64>, u64) {
+        let bridge = Bridge::new(2, policy);
+        let (entered_tx, entered_rx) = channel();
+        let (release_tx, release_rx) = channel();
+        let slow_out = Arc::new(Mutex::new(vec![]));
+        let slow_writer = GatedWriter { gate: Some((entered_tx, release_rx)), out: slow_out.clone() };
+        bridge.add_client(slow_writer, TopicFilter::All);
+        let (fast_writer, fast_out) = collecting_writer();
+        bridge.add_client(fast_writer, TopicFilter::All);
+
+        let status = test_shared::get_vehicle_status();
+        // the slow client's writer blocks on the first frame, so the rest pile up in its queue
+        let (hdr, msg) = status.gen_ready_pair(0, 0);
+        bridge.forward(&hdr, &msg).unwrap();
+        entered_rx.recv().unwrap();
+        for timestamp in 1..6 {
+            let (hdr, msg) = status.gen_ready_pair(0, timestamp);
+            bridge.forward(&hdr, &msg).unwrap();
+            // the fast client keeps receiving while the slow one is stalled
+            while timestamps(&fast_out).len() <= timestamp as usize {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        for _ in 0..3 {
+            release_tx.send(()).unwrap();
+        }
+        bridge.shutdown();
+        (timestamps(&slow_out), timestamps(&fast_out), bridge.dropped())
+    }
+
+    #[test]
+    pub fn test_bridge_drop_newest() {
+        let (slow, fast, dropped) = slow_client_drops(DropPolicy::DropNewest);
+        assert_eq!(fast, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(slow, vec![0, 1, 2]);
+        assert_eq!(dropped, 3);
+    }
+
+    #[test]
+    pub fn test_bridge_drop_oldest() {
+        let (slow, fast, dropped) = slow_client_drops(DropPolicy::DropOldest);
+        assert_eq!(fast, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(slow, vec![0, 4, 5]);
+        assert_eq!(dropped, 3);
+    }
+}
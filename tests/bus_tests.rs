@@ -0,0 +1,99 @@
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_bus {
+    use crate::test_shared;
+    use uorb_codec::bus::Bus;
+    use uorb_codec::common::{SensorGyroData, VehicleGpsPositionData, VehicleStatusData};
+    use uorb_codec::UorbMsgMeta;
+
+    #[test]
+    pub fn test_bus_publish_copy() {
+        let bus = Bus::new();
+        let mut sub = bus.subscribe::<VehicleStatusData>(0);
+        assert!(!sub.exists());
+        assert!(!sub.updated());
+        assert_eq!(sub.copy(), None);
+
+        let publisher = bus.advertise::<VehicleStatusData>(0);
+        assert!(sub.exists());
+        assert!(!sub.updated());
+
+        let mut status = test_shared::get_vehicle_status();
+        publisher.publish_at(&status, 10);
+        status.nav_state += 1;
+        publisher.publish_at(&status, 20);
+        assert!(sub.updated());
+        let (hdr, copied) = sub.copy_with_header().unwrap();
+        assert_eq!(hdr.timestamp, 20);
+        assert_eq!(copied, status);
+        assert!(!sub.updated());
+        assert_eq!(sub.update(), None);
+        // the latest value remains available to copy
+        assert_eq!(sub.copy(), Some(status));
+
+        // other instances and topics are independent
+        assert_eq!(bus.subscribe::<VehicleStatusData>(1).copy(), None);
+        assert_eq!(bus.subscribe::<SensorGyroData>(0).copy(), None);
+    }
+
+    #[test]
+    pub fn test_bus_group_count() {
+        let bus = Bus::new();
+        assert_eq!(bus.group_count::<SensorGyroData>(), 0);
+        let first = bus.advertise_next::<SensorGyroData>().unwrap();
+        let second = bus.advertise_next::<SensorGyroData>().unwrap();
+        bus.advertise::<VehicleStatusData>(0);
+        assert_eq!((first.instance(), second.instance()), (0, 1));
+        assert_eq!(bus.group_count::<SensorGyroData>(), 2);
+        assert_eq!(bus.group_count::<VehicleStatusData>(), 1);
+
+        let gyro = test_shared::get_sensor_gyro();
+        second.publish(&gyro);
+        let mut sub = bus.subscribe::<SensorGyroData>(1);
+        assert_eq!(sub.update(), Some(gyro));
+        assert!(!bus.subscribe::<SensorGyroData>(0).updated());
+    }
+
+    #[test]
+    pub fn test_bus_bridge_round_trip() {
+        let vehicle = Bus::new();
+        let companion = Bus::new();
+        let mut outbound = vehicle.subscribe_all();
+
+        let status = test_shared::get_vehicle_status();
+        let gps = test_shared::get_vehicle_gps_position();
+        let status_pub = vehicle.advertise::<VehicleStatusData>(0);
+        let gps_pub = vehicle.advertise::<VehicleGpsPositionData>(2);
+        status_pub.publish_at(&status, 1);
+        gps_pub.publish_at(&gps, 2);
+        // replaces the first publication of the topic
+        let (hdr, msg) = status_pub.publish_at(&status, 3);
+        assert_eq!(msg, status.wrap());
+        assert_eq!(hdr.payload_len, VehicleStatusData::ENCODED_LEN);
+
+        let mut link = vec![];
+        for (hdr, msg) in outbound.poll() {
+            uorb_codec::write_msg(&mut link, &hdr, &msg).unwrap();
+        }
+        assert!(outbound.poll().is_empty());
+
+        let received: Vec<_> = uorb_codec::UorbReader::new(link.as_slice())
+            .map(|res| res.unwrap())
+            .collect();
+        let timestamps: Vec<u64> = received.iter().map(|(hdr, _)| hdr.timestamp).collect();
+        assert_eq!(timestamps, vec![2, 3]);
+        for (hdr, msg) in &received {
+            companion.publish_msg(hdr, msg);
+        }
+
+        let mut gps_sub = companion.subscribe::<VehicleGpsPositionData>(2);
+        let (hdr, copied) = gps_sub.copy_with_header().unwrap();
+        assert_eq!(hdr.instance_id, 2);
+        assert_eq!(copied, gps);
+        assert_eq!(companion.subscribe::<VehicleStatusData>(0).copy(), Some(status));
+        assert!(companion.exists::<VehicleGpsPositionData>(2));
+    }
+}
@@ -0,0 +1,94 @@
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_cdr {
+    use crate::test_shared;
+    use uorb_codec::cdr::CdrEndian;
+    use uorb_codec::common::{SensorBaroData, VehicleAttitudeData};
+    use uorb_codec::UorbMsgMeta;
+
+    fn baro() -> SensorBaroData {
+        SensorBaroData {
+            timestamp: 0x0102030405060708,
+            device_id: 0x0A0B0C0D,
+            error_count: 2,
+            pressure: 1.5,
+            temperature: -2.0,
+        }
+    }
+
+    #[test]
+    pub fn test_cdr_little_endian() {
+        let expected: Vec<u8> = vec![
+            0x00, 0x01, 0x00, 0x00, // encapsulation header, CDR_LE
+            0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, // timestamp
+            0x0D, 0x0C, 0x0B, 0x0A, // device_id
+            0x00, 0x00, 0x00, 0x00, // padding
+            0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // error_count
+            0x00, 0x00, 0xC0, 0x3F, // pressure
+            0x00, 0x00, 0x00, 0xC0, // temperature
+        ];
+        assert_eq!(SensorBaroData::CDR_ENCODED_LEN, 32);
+        assert_eq!(baro().ser_cdr(CdrEndian::Little), expected);
+        assert_eq!(SensorBaroData::deser_cdr(&expected), Some(baro()));
+    }
+
+    #[test]
+    pub fn test_cdr_big_endian() {
+        let expected: Vec<u8> = vec![
+            0x00, 0x00, 0x00, 0x00, // encapsulation header, CDR_BE
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+            0x0A, 0x0B, 0x0C, 0x0D,
+            0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02,
+            0x3F, 0xC0, 0x00, 0x00,
+            0xC0, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(baro().ser_cdr(CdrEndian::Big), expected);
+        assert_eq!(SensorBaroData::deser_cdr(&expected), Some(baro()));
+    }
+
+    #[test]
+    pub fn test_cdr_arrays() {
+        let attitude = VehicleAttitudeData {
+            timestamp: 1,
+            rollspeed: 1.0,
+            pitchspeed: 2.0,
+            yawspeed: 3.0,
+            q: [1.0, 0.0, 0.0, 0.5],
+            delta_q_reset: [0.0; 4],
+            quat_reset_counter: 7,
+        };
+        let mut expected = vec![0x00, 0x00, 0x00, 0x00];
+        expected.extend_from_slice(&1u64.to_be_bytes());
+        for value in &[1.0f32, 2.0, 3.0, 1.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0] {
+            expected.extend_from_slice(&value.to_be_bytes());
+        }
+        expected.push(7);
+        assert_eq!(VehicleAttitudeData::CDR_ENCODED_LEN, 53);
+        assert_eq!(attitude.ser_cdr(CdrEndian::Big), expected);
+        assert_eq!(VehicleAttitudeData::deser_cdr(&expected), Some(attitude));
+    }
+
+    #[test]
+    pub fn test_cdr_round_trip() {
+        let gyro = test_shared::get_sensor_gyro();
+        let status = test_shared::get_vehicle_status();
+        let estimator = test_shared::get_estimator_status();
+        for &endian in &[CdrEndian::Little, CdrEndian::Big] {
+            assert_eq!(UorbMsgMeta::deser_cdr(&gyro.ser_cdr(endian)), Some(gyro));
+            assert_eq!(UorbMsgMeta::deser_cdr(&status.ser_cdr(endian)), Some(status));
+            assert_eq!(UorbMsgMeta::deser_cdr(&estimator.ser_cdr(endian)), Some(estimator));
+        }
+
+        let mut encoded = baro().ser_cdr(CdrEndian::Little);
+        let mut short = [0; 16];
+        assert_eq!(baro().ser_cdr_into_slice(&mut short, CdrEndian::Little), None);
+        assert_eq!(SensorBaroData::deser_cdr(&encoded[..20]), None);
+        // XCDR2 is not supported
+        encoded[1] = 0x07;
+        assert_eq!(SensorBaroData::deser_cdr(&encoded), None);
+    }
+}
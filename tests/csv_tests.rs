@@ -0,0 +1,148 @@
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_csv {
+    use crate::test_shared;
+    use std::io::{Cursor, ErrorKind};
+    use std::path::PathBuf;
+    use uorb_codec::csv::{self, CsvExporter, CsvReader, CsvWriter};
+    use uorb_codec::common::{SensorGyroData, UorbMessage, VehicleAttitudeData, VehicleStatusData};
+    use uorb_codec::recording::Recorder;
+    use uorb_codec::{write_msg, UorbHeader, UorbMsgMeta};
+
+    /// Gyro instances 0 and 1, and vehicle status
+    fn frames() -> Vec<(UorbHeader, UorbMessage)> {
+        let mut frames = Vec::new();
+        for idx in 0..3u64 {
+            for instance in 0..2 {
+                let gyro = SensorGyroData {
+                    timestamp: 1_000_000 + idx * 10_000,
+                    x: idx as f32 + 0.1,
+                    ..test_shared::get_sensor_gyro()
+                };
+                frames.push(gyro.gen_ready_pair(instance, gyro.timestamp));
+            }
+        }
+        let status = test_shared::get_vehicle_status();
+        frames.push(status.gen_ready_pair(0, status.timestamp));
+        frames
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("uorb-csv-test-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    pub fn test_columns_and_rows() {
+        let names = csv::column_names(VehicleAttitudeData::MSG_HASH_CODE).unwrap();
+        assert_eq!(&names[..8], &["timestamp", "rollspeed", "pitchspeed", "yawspeed", "q[0]", "q[1]", "q[2]", "q[3]"]);
+        assert_eq!(names.last().unwrap(), "quat_reset_counter");
+
+        let attitude = VehicleAttitudeData {
+            timestamp: 1234,
+            rollspeed: f32::NAN,
+            q: [1.0, 0.0, -0.25, 1e-7],
+            ..Default::default()
+        };
+        let (header, msg) = attitude.gen_ready_pair(0, 1234);
+        let mut writer = CsvWriter::new(Vec::new(), header.hash).unwrap();
+        writer.write_msg(&header, &msg).unwrap();
+        assert_eq!(writer.rows(), 1);
+        let text = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        let rows: Vec<&str> = text.lines().collect();
+        assert_eq!(rows[0], names.join(","));
+        assert!(rows[1].starts_with("1234,nan,0,0,1,0,-0.25,0.0000001,"), "{}", rows[1]);
+
+        let mut reader = CsvReader::new(text.as_bytes(), header.hash, 0).unwrap();
+        let back: VehicleAttitudeData = reader.read_typed().unwrap().unwrap();
+        assert!(back.rollspeed.is_nan());
+        assert_eq!(back.q, attitude.q);
+        assert!(reader.read_typed::<VehicleAttitudeData>().unwrap().is_none());
+    }
+
+    #[test]
+    pub fn test_export_and_import() {
+        let mut stream = Vec::new();
+        for (header, msg) in frames() {
+            write_msg(&mut stream, &header, &msg).unwrap();
+        }
+        let dir = temp_dir("stream");
+        let mut exporter = CsvExporter::new(&dir, "flight_01").unwrap();
+        assert_eq!(exporter.export_stream(&mut stream.as_slice()).unwrap(), frames().len() as u64);
+        let files = exporter.finish().unwrap();
+        let names: Vec<&str> = files.iter().map(|path| path.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(names, vec!["flight_01_sensor_gyro_0.csv", "flight_01_sensor_gyro_1.csv", "flight_01_vehicle_status_0.csv"]);
+
+        // bools are written as numbers
+        let text = std::fs::read_to_string(&files[2]).unwrap();
+        assert!(text.lines().nth(1).unwrap().contains(",16,0,99,51,49,1,1,1,0,0,0,12,"), "{}", text);
+
+        let mut imported = Vec::new();
+        for path in &files {
+            let reader = CsvReader::open(path).unwrap();
+            imported.extend(reader.map(|frame| frame.unwrap()));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+        let mut expected = frames();
+        expected.sort_by_key(|(header, _)| (header.hash, header.instance_id));
+        imported.sort_by_key(|(header, _)| (header.hash, header.instance_id));
+        assert_eq!(imported, expected);
+
+        let status: VehicleStatusData = CsvReader::new(text.as_bytes(), VehicleStatusData::MSG_HASH_CODE, 0).unwrap()
+            .read_typed().unwrap().unwrap();
+        assert_eq!(status, test_shared::get_vehicle_status());
+    }
+
+    #[test]
+    pub fn test_export_recording() {
+        let mut recorder = Recorder::new(Vec::new()).unwrap();
+        for (header, msg) in frames() {
+            recorder.record(header.timestamp, &header, &msg).unwrap();
+        }
+        let recording = recorder.finish().unwrap();
+
+        let dir = temp_dir("recording");
+        let mut exporter = CsvExporter::new(&dir, "rec").unwrap();
+        assert_eq!(exporter.export_recording(Cursor::new(recording)).unwrap(), frames().len() as u64);
+        assert_eq!(exporter.files().len(), 3);
+        let files = exporter.finish().unwrap();
+        let gyro: Vec<SensorGyroData> = {
+            let mut reader = CsvReader::open(&files[1]).unwrap();
+            assert_eq!(reader.instance_id(), 1);
+            std::iter::from_fn(|| reader.read_typed().unwrap()).collect()
+        };
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(gyro.iter().map(|gyro| gyro.x).collect::<Vec<_>>(), vec![0.1, 1.1, 2.1]);
+    }
+
+    #[test]
+    pub fn test_file_names_and_errors() {
+        let hash = SensorGyroData::MSG_HASH_CODE;
+        assert_eq!(csv::file_name("log_3", "sensor_gyro", 2), "log_3_sensor_gyro_2.csv");
+        assert_eq!(csv::parse_file_name("log_3_sensor_gyro_2.csv"), Some((hash, 2)));
+        assert_eq!(csv::parse_file_name("log_3_sensor_gyro.csv"), None);
+        assert_eq!(csv::parse_file_name("log_3_gyro_0.csv"), None);
+
+        // columns may be reordered, and unknown ones are ignored
+        let names = csv::column_names(hash).unwrap();
+        let mut reversed = names.clone();
+        reversed.reverse();
+        let text = format!("extra,{}\n7,{}\n", reversed.join(","), vec!["3"; names.len()].join(","));
+        let gyro: SensorGyroData = CsvReader::new(text.as_bytes(), hash, 0).unwrap().read_typed().unwrap().unwrap();
+        assert_eq!((gyro.timestamp, gyro.x, gyro.x_raw), (3, 3.0, 3));
+
+        let missing = names[1..].join(",");
+        let err = CsvReader::new(missing.as_bytes(), hash, 0).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("timestamp"), "{}", err);
+
+        let bad = format!("{}\n{}\n1,2\n", names.join(","), vec!["-1"; names.len()].join(","));
+        let mut reader = CsvReader::new(bad.as_bytes(), hash, 0).unwrap();
+        let err = reader.read_msg().unwrap_err();
+        assert_eq!(err.to_string(), "line 2: bad value \"-1\" for timestamp");
+        let err = reader.read_msg().unwrap_err();
+        assert_eq!(err.to_string(), format!("line 3: 2 cells for {} columns", names.len()));
+    }
+}
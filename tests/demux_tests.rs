@@ -0,0 +1,95 @@
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_demux {
+    use crate::test_shared;
+    use uorb_codec::common::{BatteryStatusData, SensorGyroData};
+    use uorb_codec::demux::{DemuxEvent, SlotKind};
+    use uorb_codec::{TopicDemux, UorbMsgMeta, UorbReader};
+
+    const GYRO: u16 = SensorGyroData::MSG_HASH_CODE;
+    const BATTERY: u16 = BatteryStatusData::MSG_HASH_CODE;
+
+    #[test]
+    pub fn test_demux_routes_instances() {
+        let gyro = test_shared::get_sensor_gyro();
+        let battery = test_shared::get_battery_status();
+        let mut v = vec![];
+        for timestamp in 0..4 {
+            for instance in 0..3 {
+                let (hdr, msg) = gyro.gen_ready_pair(instance, timestamp * 10 + instance as u64);
+                uorb_codec::write_msg(&mut v, &hdr, &msg).unwrap();
+            }
+            let (hdr, msg) = battery.gen_ready_pair(1, timestamp * 10);
+            uorb_codec::write_msg(&mut v, &hdr, &msg).unwrap();
+        }
+
+        let mut demux = TopicDemux::new(SlotKind::Latest);
+        demux.set_slot_kind_for::<SensorGyroData>(SlotKind::Queue(3));
+        for res in UorbReader::new(v.as_slice()) {
+            let (hdr, msg) = res.unwrap();
+            demux.push(hdr, msg);
+        }
+
+        assert_eq!(demux.instances(GYRO), vec![0, 1, 2]);
+        assert_eq!(demux.instances(BATTERY), vec![1]);
+        // gyro queues keep the newest three frames of each instance
+        assert_eq!(demux.len(GYRO, 2), 3);
+        assert_eq!(demux.dropped(GYRO, 2), 1);
+        let queued: Vec<u64> = std::iter::from_fn(|| demux.pop(GYRO, 2))
+            .map(|(hdr, _)| hdr.timestamp)
+            .collect();
+        assert_eq!(queued, vec![12, 22, 32]);
+        // the battery slot keeps just the latest
+        assert_eq!(demux.latest(BATTERY, 1).unwrap().0.timestamp, 30);
+        let (hdr, data) = demux.pop_data::<BatteryStatusData>(1).unwrap();
+        assert_eq!(hdr.timestamp, 30);
+        assert_eq!(data, battery);
+        assert!(demux.pop(BATTERY, 1).is_none());
+        assert!(demux.pop(BATTERY, 0).is_none());
+
+        let events: Vec<DemuxEvent> = std::iter::from_fn(|| demux.poll_event()).collect();
+        assert_eq!(events, vec![
+            DemuxEvent::NewInstance { hash: GYRO, instance_id: 0 },
+            DemuxEvent::NewInstance { hash: GYRO, instance_id: 1 },
+            DemuxEvent::NewInstance { hash: GYRO, instance_id: 2 },
+            DemuxEvent::NewInstance { hash: BATTERY, instance_id: 1 },
+        ]);
+    }
+
+    #[test]
+    pub fn test_demux_silent_instances() {
+        let gyro = test_shared::get_sensor_gyro();
+        let mut demux = TopicDemux::default();
+        demux.set_silence_timeout(Some(100_000));
+
+        // gyro 1 fails after 50ms, then comes back at 300ms
+        for timestamp in (0..400_000).step_by(10_000) {
+            let (hdr, msg) = gyro.gen_ready_pair(0, timestamp);
+            demux.push(hdr, msg);
+            if timestamp <= 50_000 || timestamp >= 300_000 {
+                let (hdr, msg) = gyro.gen_ready_pair(1, timestamp);
+                demux.push(hdr, msg);
+            }
+            if timestamp == 200_000 {
+                assert!(demux.is_silent(GYRO, 1));
+                assert_eq!(demux.active_instances(GYRO), vec![0]);
+            }
+        }
+        assert_eq!(demux.active_instances(GYRO), vec![0, 1]);
+
+        let events: Vec<DemuxEvent> = std::iter::from_fn(|| demux.poll_event()).collect();
+        assert_eq!(events, vec![
+            DemuxEvent::NewInstance { hash: GYRO, instance_id: 0 },
+            DemuxEvent::NewInstance { hash: GYRO, instance_id: 1 },
+            DemuxEvent::Silent { hash: GYRO, instance_id: 1, last_timestamp: 50_000 },
+            DemuxEvent::Resumed { hash: GYRO, instance_id: 1 },
+        ]);
+
+        // with no frames arriving at all, a clock can still drive silence checks
+        demux.check_silence(1_000_000);
+        assert!(demux.active_instances(GYRO).is_empty());
+    }
+}
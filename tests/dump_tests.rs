@@ -0,0 +1,83 @@
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_dump {
+    use crate::test_shared;
+    use std::io::Write;
+    use std::process::{Command, Output, Stdio};
+    use uorb_codec::common::{ActuatorControlsData, SensorGyroData};
+    use uorb_codec::{write_msg, UorbMsgMeta};
+
+    /// Gyro instances 0 and 1 at 100 Hz, actuator controls, and a frame of an unknown message
+    fn stream() -> Vec<u8> {
+        let mut buf = Vec::new();
+        for idx in 0..3u64 {
+            for instance in 0..2 {
+                let gyro = SensorGyroData { x: idx as f32, ..test_shared::get_sensor_gyro() };
+                let (header, msg) = gyro.gen_ready_pair(instance, 1_000_000 + idx * 10_000);
+                write_msg(&mut buf, &header, &msg).unwrap();
+            }
+        }
+        let (header, msg) = test_shared::get_actuator_controls().gen_ready_pair(0, 1_020_000);
+        write_msg(&mut buf, &header, &msg).unwrap();
+        let (mut header, msg) = test_shared::get_actuator_controls().gen_ready_pair(0, 1_030_000);
+        header.hash = 0xFFFF;
+        write_msg(&mut buf, &header, &msg).unwrap();
+        buf
+    }
+
+    fn dump(args: &[&str], input: &[u8]) -> Output {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_uorb-dump"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        output
+    }
+
+    #[test]
+    pub fn test_dump_frames() {
+        let path = std::env::temp_dir().join(format!("uorb-dump-test-{}.bin", std::process::id()));
+        std::fs::write(&path, stream()).unwrap();
+        let output = dump(&[path.to_str().unwrap(), "--json", "--name", "sensor_gyro", "--instance", "1"], &[]);
+        std::fs::remove_file(&path).unwrap();
+
+        let text = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        let prefix = format!("{{\"name\":\"sensor_gyro\",\"instance\":1,\"hash\":{},\"timestamp\":1010000,",
+                             SensorGyroData::MSG_HASH_CODE);
+        assert!(lines[1].starts_with(&prefix), "{}", lines[1]);
+        assert!(lines[1].contains("\"x\":1,") && lines[1].contains("\"device_id\":6887"), "{}", lines[1]);
+
+        let hash = format!("0x{:04x}", ActuatorControlsData::MSG_HASH_CODE);
+        let text = String::from_utf8(dump(&["-", "--hash", &hash], &stream()).stdout).unwrap();
+        assert!(text.starts_with(&format!("actuator_controls 0 (hash {}) timestamp 1020000\n", hash)), "{}", text);
+        assert!(text.contains("\n    control: ["));
+        assert!(!text.contains("sensor_gyro"));
+    }
+
+    #[test]
+    pub fn test_dump_stats() {
+        let text = String::from_utf8(dump(&["--stats"], &stream()).stdout).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("topic"));
+        assert!(lines[1].starts_with("actuator_controls") && lines[1].ends_with("0.00"), "{}", lines[1]);
+        let gyro: Vec<&str> = lines[2].split_whitespace().collect();
+        assert_eq!(&gyro[..2], &["sensor_gyro", "0"]);
+        assert_eq!(&gyro[3..], &["3", &(3 * (14 + SensorGyroData::ENCODED_LEN)).to_string(), "100.00"]);
+        assert_eq!(lines[4], "1 frames could not be decoded");
+
+        let text = String::from_utf8(dump(&["--stats", "--json", "--instance", "1"], &stream()).stdout).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("\"frames\":3") && lines[0].ends_with("\"rate_hz\":100.00}"), "{}", lines[0]);
+        assert_eq!(lines[1], "{\"errors\":1}");
+    }
+}
@@ -0,0 +1,166 @@
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_encoding {
+    use crate::test_shared;
+    use uorb_codec::encoding::{self, Cobs, PacketEncoding, Slip};
+    use uorb_codec::common::MAX_ENCODED_LEN;
+    use uorb_codec::{DecodeMode, FrameError, UorbMessage, UorbMsgMeta, UORB_HEADER_LEN, UORB_MAGIC_V1};
+
+    fn roundtrip<E: PacketEncoding>(raw: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0; E::max_encoded_len(raw.len())];
+        let packet_len = E::encode(raw, &mut packet).unwrap();
+        assert_eq!(packet[packet_len - 1], E::DELIMITER);
+        assert!(!packet[..packet_len - 1].contains(&E::DELIMITER));
+        let mut decoded = vec![0; raw.len()];
+        let decoded_len = E::decode(&packet[..packet_len - 1], &mut decoded).unwrap();
+        assert_eq!(&decoded[..decoded_len], raw);
+        packet.truncate(packet_len);
+        packet
+    }
+
+    #[test]
+    pub fn test_cobs_vectors() {
+        assert_eq!(roundtrip::<Cobs>(&[]), vec![0x01, 0x00]);
+        assert_eq!(roundtrip::<Cobs>(&[0x00]), vec![0x01, 0x01, 0x00]);
+        assert_eq!(roundtrip::<Cobs>(&[0x11, 0x22, 0x00, 0x33]),
+                   vec![0x03, 0x11, 0x22, 0x02, 0x33, 0x00]);
+        let long: Vec<u8> = (1..=255).collect();
+        let packet = roundtrip::<Cobs>(&long);
+        assert_eq!(packet[0], 0xFF);
+        assert_eq!(packet[255], 0x02);
+        assert_eq!(packet.len(), Cobs::max_encoded_len(long.len()));
+    }
+
+    #[test]
+    pub fn test_slip_vectors() {
+        assert_eq!(roundtrip::<Slip>(&[0x01, 0xC0, 0xDB, 0x02]),
+                   vec![0x01, 0xDB, 0xDC, 0xDB, 0xDD, 0x02, 0xC0]);
+        let mut out = [0; 4];
+        assert_eq!(Slip::decode(&[0xDB, 0x01], &mut out), Err(FrameError::InvalidEncoding));
+    }
+
+    #[test]
+    pub fn test_decode_short_buffer() {
+        let mut out = [0; 2];
+        assert_eq!(Cobs::decode(&[0x04, 0x01, 0x02, 0x03], &mut out),
+                   Err(FrameError::BufferTooSmall { needed: 3, available: 2 }));
+    }
+
+    /// A frame whose header timestamp contains the magic value and both delimiters
+    fn tricky_status_pair() -> (uorb_codec::UorbHeader, UorbMessage) {
+        let status = test_shared::get_vehicle_status();
+        let timestamp = u64::from_be_bytes([UORB_MAGIC_V1, 0x00, 0xC0, 0xDB, 0, 0, 0, 1]);
+        status.gen_ready_pair(0, timestamp)
+    }
+
+    fn write_read<E: PacketEncoding>() {
+        let (hdr, msg) = tricky_status_pair();
+        let mut v = vec![E::DELIMITER, UORB_MAGIC_V1, 0x55];
+        // the line noise above is terminated by the leading delimiter of the first packet
+        v.push(E::DELIMITER);
+        encoding::write_msg::<E, _>(&mut v, &hdr, &msg).unwrap();
+        encoding::write_msg::<E, _>(&mut v, &hdr, &msg).unwrap();
+
+        let mut c = v.as_slice();
+        assert!(encoding::read_msg::<E, _>(&mut c).is_err());
+        for _ in 0..2 {
+            let (decoded_hdr, decoded_msg) = encoding::read_msg::<E, _>(&mut c).unwrap();
+            assert_eq!(decoded_hdr.timestamp, hdr.timestamp);
+            assert_eq!(decoded_msg, msg);
+        }
+        assert!(c.is_empty());
+    }
+
+    #[test]
+    pub fn test_write_read_cobs() {
+        write_read::<Cobs>();
+    }
+
+    #[test]
+    pub fn test_write_read_slip() {
+        write_read::<Slip>();
+    }
+
+    #[test]
+    pub fn test_resync_after_corrupt_packet() {
+        let (hdr, msg) = tricky_status_pair();
+        let mut v = vec![];
+        encoding::write_msg::<Cobs, _>(&mut v, &hdr, &msg).unwrap();
+        let first_len = v.len();
+        encoding::write_msg::<Cobs, _>(&mut v, &hdr, &msg).unwrap();
+        // drop a byte from the middle of the first packet
+        v.remove(first_len / 2);
+
+        let mut c = v.as_slice();
+        assert!(encoding::read_msg_with_mode::<Cobs, _>(&mut c, DecodeMode::Lenient).is_err());
+        let (_, decoded_msg) = encoding::read_msg::<Cobs, _>(&mut c).unwrap();
+        assert_eq!(decoded_msg, msg);
+    }
+
+    #[test]
+    pub fn test_resync_after_overlong_packet() {
+        let (hdr, msg) = tricky_status_pair();
+        // noise longer than any packet, ended by the delimiter before the next packet
+        let mut v = vec![0x55; encoding::MAX_PACKET_LEN + 1];
+        v.push(Cobs::DELIMITER);
+        encoding::write_msg::<Cobs, _>(&mut v, &hdr, &msg).unwrap();
+
+        let mut c = v.as_slice();
+        let err = encoding::read_msg::<Cobs, _>(&mut c).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.into_inner().unwrap().downcast::<FrameError>().unwrap(), Box::new(FrameError::InvalidEncoding));
+        let (_, decoded_msg) = encoding::read_msg::<Cobs, _>(&mut c).unwrap();
+        assert_eq!(decoded_msg, msg);
+    }
+
+    /// A status frame whose payload is longer than the largest known message
+    fn oversize_status_packet<E: PacketEncoding>() -> Vec<u8> {
+        let status = test_shared::get_vehicle_status();
+        let payload_len = MAX_ENCODED_LEN + 1;
+        let mut raw = vec![0; UORB_HEADER_LEN + payload_len];
+        status.encode_frame_into(0, 1, &mut raw).unwrap();
+        raw[12..14].copy_from_slice(&(payload_len as u16).to_be_bytes());
+        let mut packet = vec![0; E::max_encoded_len(raw.len())];
+        let packet_len = E::encode(&raw, &mut packet).unwrap();
+        packet.truncate(packet_len);
+        packet
+    }
+
+    fn lenient_oversize<E: PacketEncoding>() {
+        let packet = oversize_status_packet::<E>();
+        let status = test_shared::get_vehicle_status().wrap();
+        let (hdr, msg) = encoding::read_msg_with_mode::<E, _>(&mut packet.as_slice(), DecodeMode::Lenient).unwrap();
+        assert_eq!((hdr.timestamp, msg), (1, status.clone()));
+        let body = &packet[..packet.len() - 1];
+        assert_eq!(encoding::decode_packet::<E>(body, DecodeMode::Lenient).unwrap().1, status);
+        assert_eq!(encoding::decode_packet::<E>(body, DecodeMode::Strict).unwrap_err(),
+                   FrameError::Oversize { payload_len: MAX_ENCODED_LEN + 1, max: MAX_ENCODED_LEN });
+    }
+
+    #[test]
+    pub fn test_lenient_oversize_cobs() {
+        lenient_oversize::<Cobs>();
+    }
+
+    #[test]
+    pub fn test_lenient_oversize_slip() {
+        lenient_oversize::<Slip>();
+    }
+
+    #[test]
+    pub fn test_decode_packet() {
+        let (hdr, msg) = tricky_status_pair();
+        let mut packet = [0; encoding::MAX_PACKET_LEN];
+        let packet_len = msg.encode_packet_into::<Slip>(&hdr, &mut packet).unwrap();
+        let (decoded_hdr, decoded_msg) =
+            encoding::decode_packet::<Slip>(&packet[..packet_len - 1], DecodeMode::Strict).unwrap();
+        assert_eq!(decoded_hdr.instance_id, hdr.instance_id);
+        assert_eq!(decoded_msg, msg);
+        // a packet holding only part of a frame
+        assert_eq!(encoding::decode_packet::<Slip>(&packet[..20], DecodeMode::Strict).unwrap_err(),
+                   FrameError::InvalidEncoding);
+    }
+}
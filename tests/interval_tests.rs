@@ -0,0 +1,160 @@
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_interval {
+    use crate::test_shared;
+    use uorb_codec::bus::Bus;
+    use uorb_codec::common::{SensorGyroData, VehicleStatusData};
+    use uorb_codec::interval::{IntervalFilter, IntervalMode};
+    use uorb_codec::{UorbMsgMeta, UorbReader};
+
+    /// Timestamp of the `idx`th gyro sample at 1 kHz, with up to 200us of jitter
+    fn gyro_timestamp(idx: u64) -> u64 {
+        idx * 1000 + (idx % 3) * 100
+    }
+
+    /// One second of gyro instances 0 and 1 at 1 kHz, and vehicle status at 10 Hz
+    fn recorded_stream() -> Vec<u8> {
+        let gyro = test_shared::get_sensor_gyro();
+        let status = test_shared::get_vehicle_status();
+        let mut v = vec![];
+        for idx in 0..1000 {
+            for instance in 0..2 {
+                let (hdr, msg) = gyro.gen_ready_pair(instance, gyro_timestamp(idx));
+                uorb_codec::write_msg(&mut v, &hdr, &msg).unwrap();
+            }
+            if idx % 100 == 0 {
+                let (hdr, msg) = status.gen_ready_pair(0, idx * 1000);
+                uorb_codec::write_msg(&mut v, &hdr, &msg).unwrap();
+            }
+        }
+        v
+    }
+
+    /// Replay the recording at 50 Hz for gyro instance 0, returning delivered
+    /// gyro instance 0 timestamps and counts of the other messages
+    fn replay(mode: IntervalMode) -> (Vec<u64>, usize, usize) {
+        let recording = recorded_stream();
+        let mut filter = IntervalFilter::new();
+        filter.set_interval_for::<SensorGyroData>(0, 20_000, mode);
+
+        let mut gyro_timestamps = vec![];
+        let mut other_gyro = 0;
+        let mut status = 0;
+        for res in filter.throttle(UorbReader::new(recording.as_slice())) {
+            let (hdr, _msg) = res.unwrap();
+            if hdr.hash == VehicleStatusData::MSG_HASH_CODE {
+                status += 1;
+            } else if hdr.instance_id == 0 {
+                gyro_timestamps.push(hdr.timestamp);
+            } else {
+                other_gyro += 1;
+            }
+        }
+        (gyro_timestamps, other_gyro, status)
+    }
+
+    #[test]
+    pub fn test_interval_first_value() {
+        let (delivered, other_gyro, status) = replay(IntervalMode::First);
+        assert_eq!(delivered.len(), 50);
+        assert_eq!(delivered[0], 0);
+        // jitter does not make the rate drift
+        for pair in delivered.windows(2) {
+            assert!(pair[1] - pair[0] >= 19_800, "{:?}", pair);
+        }
+        assert_eq!(other_gyro, 1000);
+        assert_eq!(status, 10);
+    }
+
+    #[test]
+    pub fn test_interval_latest_value() {
+        let (delivered, other_gyro, status) = replay(IntervalMode::Latest);
+        assert_eq!(delivered.len(), 50);
+        // the last sample of the first and final intervals
+        assert_eq!(delivered[0], gyro_timestamp(19));
+        assert_eq!(delivered[49], gyro_timestamp(999));
+        assert_eq!(other_gyro, 1000);
+        assert_eq!(status, 10);
+    }
+
+    #[test]
+    pub fn test_interval_restarts_with_stream() {
+        let gyro = test_shared::get_sensor_gyro();
+        let mut filter = IntervalFilter::new();
+        filter.set_interval_for::<SensorGyroData>(0, 20_000, IntervalMode::First);
+        let delivered: Vec<u64> = [0u64, 1000, 500_000, 0, 1000, 20_000].iter()
+            .filter_map(|&timestamp| {
+                let (hdr, msg) = gyro.gen_ready_pair(0, timestamp);
+                filter.push(hdr, msg)
+            })
+            .map(|(hdr, _)| hdr.timestamp)
+            .collect();
+        // a looped replay starts over at its first message
+        assert_eq!(delivered, vec![0, 500_000, 0, 20_000]);
+
+        filter.clear_interval(SensorGyroData::MSG_HASH_CODE, 0);
+        let (hdr, msg) = gyro.gen_ready_pair(0, 20_001);
+        assert!(filter.push(hdr, msg).is_some());
+    }
+
+    /// Publish the gyro samples of `recorded_stream` on a bus, returning the
+    /// timestamps of the updates received at 50 Hz
+    fn bus_updates(mode: IntervalMode) -> Vec<u64> {
+        let bus = Bus::new();
+        let publisher = bus.advertise::<SensorGyroData>(0);
+        let mut sub = bus.subscribe::<SensorGyroData>(0);
+        sub.set_interval(20_000, mode);
+        let gyro = test_shared::get_sensor_gyro();
+
+        let mut updates = vec![];
+        for idx in 0..1000 {
+            publisher.publish_at(&gyro, gyro_timestamp(idx));
+            if sub.updated() {
+                updates.push(sub.copy_with_header().unwrap().0.timestamp);
+            }
+        }
+        updates
+    }
+
+    #[test]
+    pub fn test_bus_subscriber_interval() {
+        let first = bus_updates(IntervalMode::First);
+        assert_eq!(first.len(), 50);
+        assert_eq!(first[0], 0);
+        for pair in first.windows(2) {
+            assert!(pair[1] - pair[0] >= 19_800, "{:?}", pair);
+        }
+
+        // the final interval is still held back, as no later publication arrived
+        let latest = bus_updates(IntervalMode::Latest);
+        assert_eq!(latest.len(), 49);
+        assert_eq!(latest[0], gyro_timestamp(19));
+        assert_eq!(latest[48], gyro_timestamp(979));
+    }
+
+    #[test]
+    pub fn test_bus_subscriber_interval_clone_and_clear() {
+        let bus = Bus::new();
+        let publisher = bus.advertise::<SensorGyroData>(0);
+        let mut sub = bus.subscribe::<SensorGyroData>(0);
+        sub.set_interval(20_000, IntervalMode::First);
+        let gyro = test_shared::get_sensor_gyro();
+
+        publisher.publish_at(&gyro, 0);
+        let mut cloned = sub.clone();
+        assert!(sub.update().is_some());
+        assert!(!sub.updated());
+        // the clone keeps its own delivery
+        assert!(cloned.update().is_some());
+        publisher.publish_at(&gyro, 1000);
+        assert!(!sub.updated() && !cloned.updated());
+
+        sub.clear_interval();
+        drop(cloned);
+        assert!(sub.update().is_some());
+        assert_eq!(sub.copy_with_header().unwrap().0.timestamp, 1000);
+    }
+}
@@ -0,0 +1,187 @@
+#![cfg(feature = "mavlink")]
+
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_mavlink {
+    use crate::test_shared;
+    use mavlink::dialects::common::{GpsFixType, MavMessage, MavModeFlag, MavState};
+    use uorb_codec::common::{
+        ActuatorOutputsData, BatteryStatusData, UorbMessage, VehicleAttitudeData, VehicleGpsPositionData,
+        VehicleStatusData,
+    };
+    use uorb_codec::mavlink::*;
+    use uorb_codec::UorbMsgMeta;
+
+    fn gps() -> VehicleGpsPositionData {
+        VehicleGpsPositionData {
+            timestamp: 1_234_567,
+            lat: 473_977_418,
+            lon: 85_455_939,
+            alt: 488_120,
+            fix_type: 3,
+            hdop: 0.9,
+            vdop: 1.35,
+            vel_m_s: 4.25,
+            cog_rad: -std::f32::consts::FRAC_PI_2,
+            satellites_used: 11,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    pub fn test_gps_units() {
+        let raw = gps_raw_int(&gps());
+        assert_eq!(raw.time_usec, 1_234_567);
+        assert_eq!(raw.lat, 473_977_418);
+        assert_eq!(raw.alt, 488_120);
+        assert_eq!(raw.eph, 90);
+        assert_eq!(raw.epv, 135);
+        assert_eq!(raw.vel, 425);
+        // west is 270 degrees, not -90
+        assert_eq!(raw.cog, 27000);
+        assert_eq!(raw.fix_type, GpsFixType::GPS_FIX_TYPE_3D_FIX);
+        assert_eq!(raw.satellites_visible, 11);
+
+        let back = from_gps_raw_int(&raw);
+        assert_eq!(back.lat, gps().lat);
+        assert_eq!(back.fix_type, 3);
+        assert!((back.hdop - 0.9).abs() < 1e-6);
+        assert!((back.vel_m_s - 4.25).abs() < 1e-6);
+        assert!((back.cog_rad + std::f32::consts::FRAC_PI_2).abs() < 1e-4);
+
+        // unknown values use the MAVLink marker both ways
+        let unknown = VehicleGpsPositionData { vel_m_s: f32::NAN, cog_rad: f32::NAN, ..gps() };
+        let raw = gps2_raw(&unknown);
+        assert_eq!((raw.vel, raw.cog), (u16::MAX, u16::MAX));
+        let back = from_gps2_raw(&raw);
+        assert!(back.vel_m_s.is_nan() && back.cog_rad.is_nan());
+    }
+
+    #[test]
+    pub fn test_attitude_round_trip() {
+        let attitude = VehicleAttitudeData {
+            timestamp: 9_876_543,
+            rollspeed: 0.01,
+            pitchspeed: -0.02,
+            yawspeed: 0.3,
+            q: [0.9238795, 0.0, 0.0, 0.38268343],
+            ..Default::default()
+        };
+        let att = attitude_quaternion(&attitude);
+        assert_eq!(att.time_boot_ms as u64, attitude.timestamp / 1000);
+        assert_eq!([att.q1, att.q2, att.q3, att.q4], attitude.q);
+
+        let back = from_attitude_quaternion(&att);
+        assert_eq!(back.q, attitude.q);
+        assert_eq!(back.rollspeed, attitude.rollspeed);
+        assert_eq!(back.timestamp, attitude.timestamp / 1000 * 1000);
+    }
+
+    #[test]
+    pub fn test_battery_units() {
+        let battery = BatteryStatusData {
+            voltage_v: 16.4,
+            current_a: 12.5,
+            discharged_mah: 830.0,
+            remaining: 0.62,
+            temperature: f32::NAN,
+            cell_count: 4,
+            connected: true,
+            voltage_cell_v: [4.1, 4.1, 4.1, 4.1],
+            ..Default::default()
+        };
+        let status = battery_status(&battery, 1);
+        assert_eq!(status.id, 1);
+        assert_eq!(&status.voltages[..5], &[4100, 4100, 4100, 4100, u16::MAX]);
+        assert_eq!(status.current_battery, 1250);
+        assert_eq!(status.current_consumed, 830);
+        assert_eq!(status.battery_remaining, 62);
+        assert_eq!(status.temperature, i16::MAX);
+
+        let back = from_battery_status(&status);
+        assert_eq!(back.cell_count, 4);
+        assert!((back.voltage_v - 16.4).abs() < 1e-4);
+        assert!((back.remaining - 0.62).abs() < 1e-6);
+        assert!(back.temperature.is_nan());
+
+        // without a cell count, the total voltage is the first cell
+        let unknown = BatteryStatusData { cell_count: 0, current_a: -1.0, remaining: -1.0, ..battery };
+        let status = battery_status(&unknown, 0);
+        assert_eq!(&status.voltages[..2], &[16400, u16::MAX]);
+        assert_eq!((status.current_battery, status.battery_remaining), (-1, -1));
+        let back = from_battery_status(&status);
+        assert_eq!(back.cell_count, 0);
+        assert_eq!((back.current_a, back.remaining), (-1.0, -1.0));
+    }
+
+    #[test]
+    pub fn test_heartbeat_modes() {
+        let status = VehicleStatusData {
+            nav_state: VehicleStatusData::NAVIGATION_STATE_AUTO_MISSION,
+            arming_state: VehicleStatusData::ARMING_STATE_ARMED,
+            system_type: 2,
+            onboard_control_sensors_present: 0x3f,
+            onboard_control_sensors_health: 0x1f,
+            ..Default::default()
+        };
+        let beat = heartbeat(&status);
+        assert_eq!(beat.custom_mode, (4 << 24) | (4 << 16));
+        assert!(beat.base_mode.contains(
+            MavModeFlag::MAV_MODE_FLAG_CUSTOM_MODE_ENABLED | MavModeFlag::MAV_MODE_FLAG_SAFETY_ARMED));
+        assert_eq!(beat.system_status, MavState::MAV_STATE_ACTIVE);
+
+        let mut back = from_heartbeat(&beat);
+        assert_eq!(back.nav_state, status.nav_state);
+        assert_eq!(back.arming_state, status.arming_state);
+        assert_eq!(back.system_type, 2);
+
+        let sys = sys_status(&status, None);
+        assert_eq!(sys.voltage_battery, u16::MAX);
+        merge_sys_status(&mut back, &sys);
+        assert_eq!(back.onboard_control_sensors_present, 0x3f);
+        assert_eq!(back.onboard_control_sensors_health, 0x1f);
+
+        let terminated = VehicleStatusData { nav_state: VehicleStatusData::NAVIGATION_STATE_TERMINATION, ..status };
+        let beat = heartbeat(&terminated);
+        assert_eq!(beat.system_status, MavState::MAV_STATE_FLIGHT_TERMINATION);
+        assert_eq!(from_heartbeat(&beat).nav_state, VehicleStatusData::NAVIGATION_STATE_TERMINATION);
+    }
+
+    #[test]
+    pub fn test_servo_ports() {
+        let mut outputs = ActuatorOutputsData { timestamp: 5_000_000, noutputs: 10, ..Default::default() };
+        for (idx, output) in outputs.output.iter_mut().take(10).enumerate() {
+            *output = 1000.0 + 100.0 * idx as f32;
+        }
+        let ports = servo_output_raw(&outputs);
+        assert_eq!(ports.len(), 2);
+        assert_eq!((ports[0].port, ports[0].servo1_raw, ports[0].servo8_raw), (0, 1000, 1700));
+        assert_eq!((ports[1].port, ports[1].servo2_raw, ports[1].servo3_raw), (1, 1900, 0));
+
+        let back = from_servo_output_raw(&ports);
+        assert_eq!(back.noutputs, 10);
+        assert_eq!(&back.output[..10], &outputs.output[..10]);
+        assert_eq!(back.timestamp, 5_000_000);
+    }
+
+    #[test]
+    pub fn test_to_from_mavlink() {
+        let (header, msg) = gps().gen_ready_pair(1, 1_234_567);
+        let converted = to_mavlink(&header, &msg);
+        assert!(matches!(converted.as_slice(), [MavMessage::GPS2_RAW(_)]));
+
+        let (header, msg) = from_mavlink(&converted[0]).unwrap();
+        assert_eq!(header.instance_id, 1);
+        assert_eq!(header.hash, VehicleGpsPositionData::MSG_HASH_CODE);
+        match msg {
+            UorbMessage::VehicleGpsPosition(gps) => assert_eq!(gps.lat, 473_977_418),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let (header, msg) = test_shared::get_sensor_gyro().gen_ready_pair(0, 0);
+        assert!(to_mavlink(&header, &msg).is_empty());
+    }
+}
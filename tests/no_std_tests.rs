@@ -0,0 +1,37 @@
+//! Verify that the crate builds for an embedded target without the `std` feature
+
+use std::process::Command;
+
+const EMBEDDED_TARGET: &str = "thumbv7em-none-eabihf";
+
+fn target_installed(target: &str) -> bool {
+    Command::new("rustc")
+        .args(["--print", "target-libdir", "--target", target])
+        .output()
+        .map(|out| out.status.success() && std::path::Path::new(
+            String::from_utf8_lossy(&out.stdout).trim()).exists())
+        .unwrap_or(false)
+}
+
+/// Set to skip the build where the embedded target cannot be installed
+const SKIP_VAR: &str = "UORB_SKIP_NO_STD_BUILD";
+
+#[test]
+pub fn test_build_no_std_embedded() {
+    if std::env::var_os(SKIP_VAR).is_some() {
+        println!("skipping: {} is set", SKIP_VAR);
+        return;
+    }
+    assert!(target_installed(EMBEDDED_TARGET),
+            "rustup target add {} to run this test, or set {} to skip it", EMBEDDED_TARGET, SKIP_VAR);
+
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--lib", "--no-default-features", "--target", EMBEDDED_TARGET])
+        // separate target dir, to avoid contending for the lock held by the outer build
+        .env("CARGO_TARGET_DIR", format!("{}/target/no_std", manifest_dir))
+        .current_dir(manifest_dir)
+        .status()
+        .expect("failed to run cargo");
+    assert!(status.success());
+}
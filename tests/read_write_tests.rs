@@ -5,8 +5,8 @@ pub mod test_shared;
 
 mod test_read_write {
     use crate::test_shared;
-    use uorb_codec::common::{UorbMessage, VehicleStatusData };
-    use uorb_codec::UorbMsgMeta;
+    use uorb_codec::common::{EstimatorStatusData, UorbMessage, VehicleGpsPositionData, VehicleStatusData };
+    use uorb_codec::{DecodeMode, PayloadFit, UorbMsgMeta};
 
     #[test]
     pub fn test_verify_msg_hashcodes() {
@@ -23,6 +23,7 @@ mod test_read_write {
     }
 
     #[test]
+    #[allow(clippy::clone_on_copy)]
     pub fn test_write_read_vehicle_status() {
         let mut v = vec![];
         let msg_data = test_shared::get_vehicle_status();
@@ -51,4 +52,240 @@ mod test_read_write {
             panic!("Read wrong message type");
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_frame_decoder() {
+        let gps = test_shared::get_vehicle_gps_position();
+        let status = test_shared::get_vehicle_status();
+        let mut buf = [0u8; 2 * uorb_codec::MAX_FRAME_LEN + 3];
+        // leading garbage before the first frame
+        buf[0] = 0x01;
+        buf[1] = 0x02;
+        let mut len = 2;
+        len += gps.encode_frame_into(1, 100, &mut buf[len..]).unwrap();
+        len += status.encode_frame_into(0, 200, &mut buf[len..]).unwrap();
+
+        // everything but the last byte of the second frame
+        let mut decoder = uorb_codec::FrameDecoder::new(&buf[..len - 1]);
+        let (hdr, msg) = decoder.next().unwrap().unwrap();
+        assert_eq!(hdr.instance_id, 1);
+        assert_eq!(hdr.timestamp, 100);
+        assert_eq!(msg, gps.wrap());
+        assert!(decoder.next().is_none());
+        assert_eq!(decoder.remaining().len(),
+                   uorb_codec::UORB_HEADER_LEN + VehicleStatusData::ENCODED_LEN - 1);
+
+        let decoded: Vec<_> = uorb_codec::FrameDecoder::new(&buf[..len])
+            .map(|res| res.unwrap().1)
+            .collect();
+        assert_eq!(decoded, vec![gps.wrap(), status.wrap()]);
+    }
+
+    #[test]
+    pub fn test_frame_decoder_invalid_payload() {
+        let status = test_shared::get_vehicle_status();
+        let mut buf = [0u8; uorb_codec::MAX_FRAME_LEN];
+        let mut hdr = status.header_for_instance(0, 200);
+        hdr.hash = 0xFFFF;
+        let len = status.wrap().encode_frame_into(&hdr, &mut buf).unwrap();
+
+        let mut decoder = uorb_codec::FrameDecoder::new(&buf[..len]);
+        assert_eq!(decoder.next().unwrap().unwrap_err(),
+                   uorb_codec::FrameError::InvalidPayload { hash: 0xFFFF });
+        assert!(decoder.next().is_none());
+        assert!(decoder.remaining().is_empty());
+    }
+
+    #[test]
+    pub fn test_write_read_large_payload() {
+        let msg_data = test_shared::get_estimator_status();
+
+        let mut v = vec![];
+        let (header, msg) = msg_data.gen_ready_pair(1, 999);
+        uorb_codec::write_msg(&mut v, &header, &msg).expect("Failed to write message");
+
+        let (decoded_header, decoded_msg) = uorb_codec::read_msg(&mut v.as_slice()).expect("Failed to read");
+        assert_eq!(decoded_header.payload_len, EstimatorStatusData::ENCODED_LEN);
+        assert!(decoded_header.payload_len > 255);
+        assert_eq!(decoded_msg, msg);
+    }
+
+    #[test]
+    pub fn test_read_oversize_frame() {
+        let oversize_len = uorb_codec::common::MAX_ENCODED_LEN + 1;
+        let status = test_shared::get_vehicle_status();
+        let mut frame = [0u8; uorb_codec::MAX_FRAME_LEN];
+        status.encode_frame_into(0, 5, &mut frame).unwrap();
+
+        // a bogus frame whose payload is full of magic bytes, followed by a valid frame
+        let mut v = frame[..uorb_codec::UORB_HEADER_LEN].to_vec();
+        v[12..14].copy_from_slice(&(oversize_len as u16).to_be_bytes());
+        v.extend(vec![uorb_codec::UORB_MAGIC_V1; oversize_len]);
+        let (hdr, msg) = status.gen_ready_pair(0, 6);
+        uorb_codec::write_msg(&mut v, &hdr, &msg).unwrap();
+
+        let mut c = v.as_slice();
+        let err = uorb_codec::read_msg(&mut c).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let (decoded_header, decoded_msg) = uorb_codec::read_msg(&mut c).expect("Failed to resync");
+        assert_eq!(decoded_header.timestamp, 6);
+        assert_eq!(decoded_msg, msg);
+
+        let mut decoder = uorb_codec::FrameDecoder::new(&v);
+        assert_eq!(decoder.next().unwrap().unwrap_err(),
+                   uorb_codec::FrameError::Oversize { payload_len: oversize_len,
+                       max: uorb_codec::common::MAX_ENCODED_LEN });
+        assert_eq!(decoder.next().unwrap().unwrap().1, msg);
+    }
+
+    /// Frame a vehicle_status payload as if its definition had `delta` more (or fewer) bytes
+    fn vehicle_status_frame_with_len_delta(status: &VehicleStatusData, delta: isize) -> Vec<u8> {
+        let mut frame = [0u8; uorb_codec::MAX_FRAME_LEN];
+        let frame_len = status.encode_frame_into(0, 5, &mut frame).unwrap();
+        let mut v = frame[..frame_len].to_vec();
+        let payload_len = (VehicleStatusData::ENCODED_LEN as isize + delta) as usize;
+        v.resize(uorb_codec::UORB_HEADER_LEN + payload_len, 0x55);
+        v[12..14].copy_from_slice(&(payload_len as u16).to_be_bytes());
+        v
+    }
+
+    #[test]
+    pub fn test_strict_payload_len() {
+        for delta in [-3, 4].iter() {
+            let v = vehicle_status_frame_with_len_delta(&test_shared::get_vehicle_status(), *delta);
+            let err = uorb_codec::read_msg(&mut v.as_slice()).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+            let mut decoder = uorb_codec::FrameDecoder::new(&v);
+            match decoder.next().unwrap() {
+                Err(uorb_codec::FrameError::LengthMismatch { payload_len, expected, .. }) => {
+                    assert_eq!(expected, VehicleStatusData::ENCODED_LEN);
+                    assert_eq!(payload_len as isize, expected as isize + delta);
+                },
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_lenient_trailing_bytes() {
+        let v = vehicle_status_frame_with_len_delta(&test_shared::get_vehicle_status(), 4);
+        let (_hdr, msg, fit) = uorb_codec::read_msg_with_mode(
+            &mut v.as_slice(), DecodeMode::Lenient).unwrap();
+        assert_eq!(fit, PayloadFit::Trailing { extra: 4 });
+        assert_eq!(msg, test_shared::get_vehicle_status().wrap());
+    }
+
+    #[test]
+    pub fn test_lenient_oversize_trailing_bytes() {
+        // a newer definition may push the payload past the largest known message
+        let extra = uorb_codec::common::MAX_ENCODED_LEN + 1 - VehicleStatusData::ENCODED_LEN;
+        let mut v = vehicle_status_frame_with_len_delta(&test_shared::get_vehicle_status(), extra as isize);
+        let (hdr, msg) = test_shared::get_vehicle_status().gen_ready_pair(0, 6);
+        uorb_codec::write_msg(&mut v, &hdr, &msg).unwrap();
+
+        let mut c = v.as_slice();
+        let (_hdr, msg, fit) = uorb_codec::read_msg_with_mode(&mut c, DecodeMode::Lenient).unwrap();
+        assert_eq!(fit, PayloadFit::Trailing { extra });
+        assert_eq!(msg, test_shared::get_vehicle_status().wrap());
+        let (hdr, _msg, fit) = uorb_codec::read_msg_with_mode(&mut c, DecodeMode::Lenient).unwrap();
+        assert_eq!((hdr.timestamp, fit), (6, PayloadFit::Exact));
+
+        let mut decoder = uorb_codec::FrameDecoder::with_mode(&v, DecodeMode::Lenient);
+        assert_eq!(decoder.next().unwrap().unwrap().1, msg);
+        assert_eq!(decoder.next().unwrap().unwrap().0.timestamp, 6);
+        assert!(uorb_codec::read_msg(&mut v.as_slice()).is_err());
+    }
+
+    #[test]
+    pub fn test_lenient_short_payload() {
+        let mut status = test_shared::get_vehicle_status();
+        status.mission_failure = true;
+        status.failure_detector_status = VehicleStatusData::FAILURE_ALT;
+        let v = vehicle_status_frame_with_len_delta(&status, -2);
+        let (_hdr, msg, fit) = uorb_codec::read_msg_with_mode(
+            &mut v.as_slice(), DecodeMode::Lenient).unwrap();
+        assert_eq!(fit, PayloadFit::Padded { missing: 2 });
+
+        // the last two single-byte fields are missing, so take their defaults
+        let mut expected = status;
+        expected.mission_failure = false;
+        expected.failure_detector_status = 0;
+        assert_eq!(msg, expected.wrap());
+
+        let mut decoder = uorb_codec::FrameDecoder::with_mode(&v, DecodeMode::Lenient);
+        assert_eq!(decoder.next().unwrap().unwrap().1, expected.wrap());
+    }
+
+    #[test]
+    pub fn test_write_header_len_mismatch() {
+        let msg_data = test_shared::get_vehicle_status();
+        let mut header = msg_data.header_for_instance(0, 5);
+        header.payload_len += 1;
+        let mut v = vec![];
+        assert!(uorb_codec::write_msg(&mut v, &header, &msg_data.wrap()).is_err());
+        assert!(v.is_empty());
+    }
+
+    fn gps_and_status_stream() -> Vec<u8> {
+        let gps = test_shared::get_vehicle_gps_position();
+        let status = test_shared::get_vehicle_status();
+        let mut v = vec![];
+        for (idx, timestamp) in [100u64, 200, 300].iter().enumerate() {
+            let (hdr, msg) = gps.gen_ready_pair(idx as u8, *timestamp);
+            uorb_codec::write_msg(&mut v, &hdr, &msg).unwrap();
+            let (hdr, msg) = status.gen_ready_pair(0, *timestamp + 1);
+            uorb_codec::write_msg(&mut v, &hdr, &msg).unwrap();
+        }
+        v
+    }
+
+    #[test]
+    pub fn test_reader_iterator() {
+        let v = gps_and_status_stream();
+        // a partial frame at the end of the stream is not reported
+        let reader = uorb_codec::UorbReader::new(&v[..v.len() - 3]);
+        let timestamps: Vec<u64> = reader.map(|res| res.unwrap().0.timestamp).collect();
+        assert_eq!(timestamps, vec![100, 101, 200, 201, 300]);
+    }
+
+    #[test]
+    pub fn test_reader_continues_after_bad_frame() {
+        let status = test_shared::get_vehicle_status();
+        let mut v = vehicle_status_frame_with_len_delta(&status, 2);
+        let (hdr, msg) = status.gen_ready_pair(0, 777);
+        uorb_codec::write_msg(&mut v, &hdr, &msg).unwrap();
+
+        let mut reader = uorb_codec::UorbReader::new(v.as_slice());
+        assert!(reader.next().unwrap().is_err());
+        let (hdr, msg) = reader.next().unwrap().unwrap();
+        assert_eq!(hdr.timestamp, 777);
+        assert_eq!(msg, status.wrap());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    pub fn test_read_typed() {
+        let v = gps_and_status_stream();
+        let mut reader = uorb_codec::UorbReader::new(v.as_slice());
+        for instance_id in 0..3 {
+            let (hdr, gps) = reader.read_typed::<VehicleGpsPositionData>().unwrap();
+            assert_eq!(hdr.instance_id, instance_id);
+            assert_eq!(gps, test_shared::get_vehicle_gps_position());
+        }
+        let err = reader.read_typed::<VehicleGpsPositionData>().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    fn roundtrip<T: UorbMsgMeta>(data: &T) -> Option<T> {
+        T::deser(&data.ser())
+    }
+
+    #[test]
+    pub fn test_generic_deser() {
+        let status = test_shared::get_vehicle_status();
+        assert_eq!(roundtrip(&status), Some(status));
+        let gps = test_shared::get_vehicle_gps_position();
+        assert_eq!(roundtrip(&gps), Some(gps));
+    }
+}
@@ -0,0 +1,152 @@
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_recording {
+    use crate::test_shared;
+    use std::io::{Cursor, ErrorKind};
+    use uorb_codec::bus::Bus;
+    use uorb_codec::clock::{Clock, VirtualClock};
+    use uorb_codec::common::{SensorGyroData, UorbMessage};
+    use uorb_codec::recording::{Player, Recorder};
+    use uorb_codec::{read_msg, write_msg, UorbHeader, UorbMsgMeta};
+
+    /// Receive times of the recorded frames
+    const TIMES: [u64; 5] = [1_000_000, 1_010_000, 1_030_000, 1_030_000, 1_100_000];
+
+    fn frames() -> Vec<(UorbHeader, UorbMessage)> {
+        TIMES.iter().enumerate()
+            .map(|(idx, &time)| {
+                let gyro = SensorGyroData { x: idx as f32, ..test_shared::get_sensor_gyro() };
+                gyro.gen_ready_pair(0, time - 500)
+            })
+            .collect()
+    }
+
+    fn recording(finish: bool) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut recorder = Recorder::new(&mut buf).unwrap();
+        recorder.set_index_interval(20_000);
+        for (&time, (header, msg)) in TIMES.iter().zip(frames()) {
+            recorder.record(time, &header, &msg).unwrap();
+        }
+        assert_eq!(recorder.frames(), TIMES.len() as u64);
+        if finish {
+            recorder.finish().unwrap();
+        }
+        buf
+    }
+
+    fn gyro_x(msg: &UorbMessage) -> f32 {
+        match msg {
+            UorbMessage::SensorGyro(gyro) => gyro.x,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    pub fn test_record_and_play_timing() {
+        let clock = VirtualClock::new(500);
+        let mut player = Player::open(Cursor::new(recording(true)), clock.clone()).unwrap();
+        assert_eq!(player.start_time(), Some(TIMES[0]));
+
+        for (idx, (header, msg)) in frames().into_iter().enumerate() {
+            let (played_header, played) = player.next_frame().unwrap().unwrap();
+            assert_eq!(clock.now(), 500 + TIMES[idx] - TIMES[0]);
+            assert_eq!(played_header, header);
+            assert_eq!(played, msg);
+        }
+        assert!(player.next_frame().unwrap().is_none());
+
+        // a receive time going backwards is refused
+        let mut recorder = Recorder::new(Vec::new()).unwrap();
+        let (header, msg) = &frames()[0];
+        recorder.record(10, header, msg).unwrap();
+        assert_eq!(recorder.record(9, header, msg).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    pub fn test_play_to_writer_and_bus() {
+        let clock = VirtualClock::new(0);
+        let mut player = Player::open(Cursor::new(recording(true)), clock.clone()).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(player.play_to(&mut out).unwrap(), TIMES.len() as u64);
+        assert_eq!(clock.now(), TIMES[4] - TIMES[0]);
+        let mut out = out.as_slice();
+        for (header, msg) in frames() {
+            assert_eq!(read_msg(&mut out).unwrap(), (header, msg));
+        }
+
+        // recording what was played gives back the same frames
+        let mut wire = Vec::new();
+        for (header, msg) in frames() {
+            write_msg(&mut wire, &header, &msg).unwrap();
+        }
+        let mut recorder = Recorder::new(Vec::new()).unwrap();
+        assert_eq!(recorder.record_stream(&mut wire.as_slice(), &clock).unwrap(), TIMES.len() as u64);
+        let rerecorded = recorder.finish().unwrap();
+
+        let bus = Bus::new();
+        let mut sub = bus.subscribe::<SensorGyroData>(0);
+        let mut player = Player::open(Cursor::new(rerecorded), VirtualClock::new(0)).unwrap();
+        assert_eq!(player.play_to_bus(&bus).unwrap(), TIMES.len() as u64);
+        assert_eq!(sub.update().unwrap().x, 4.0);
+    }
+
+    #[test]
+    pub fn test_speed_and_pause() {
+        let clock = VirtualClock::new(0);
+        let mut player = Player::open(Cursor::new(recording(true)), clock.clone()).unwrap();
+        player.set_speed(2.0);
+        assert_eq!(player.poll().unwrap().map(|(_, msg)| gyro_x(&msg)), Some(0.0));
+        assert_eq!(player.next_due().unwrap(), Some(5_000));
+        assert!(player.poll().unwrap().is_none());
+
+        clock.advance(5_000);
+        assert_eq!(player.poll().unwrap().map(|(_, msg)| gyro_x(&msg)), Some(1.0));
+
+        // paused for 50 ms, 2 ms into the 10 ms wait for the next frame
+        clock.advance(2_000);
+        player.pause();
+        clock.advance(50_000);
+        assert_eq!(player.next_due().unwrap(), None);
+        assert!(player.poll().unwrap().is_none());
+        assert_eq!(player.next_frame().unwrap_err().kind(), ErrorKind::WouldBlock);
+        player.resume();
+        assert_eq!(player.next_due().unwrap(), Some(clock.now() + 8_000));
+
+        // back to the original rate for the rest
+        player.set_speed(1.0);
+        assert_eq!(player.next_due().unwrap(), Some(clock.now() + 16_000));
+    }
+
+    #[test]
+    pub fn test_seek_and_loop() {
+        for finish in [true, false] {
+            let mut bytes = recording(finish);
+            if !finish {
+                // cut off part way through another record
+                bytes.extend_from_slice(&[1, 2, 3]);
+            }
+            let clock = VirtualClock::new(0);
+            let mut player = Player::open(Cursor::new(bytes), clock.clone()).unwrap();
+
+            player.seek(1_020_000).unwrap();
+            assert_eq!(player.position().unwrap(), Some(1_030_000));
+            let (_, msg) = player.poll().unwrap().unwrap();
+            assert_eq!(gyro_x(&msg), 2.0);
+            player.seek(1_000_001).unwrap();
+            assert_eq!(player.position().unwrap(), Some(1_010_000));
+            player.seek(2_000_000).unwrap();
+            assert_eq!(player.position().unwrap(), None);
+
+            player.set_loop(true);
+            player.seek(1_100_000).unwrap();
+            let played: Vec<f32> = (0..3).map(|_| gyro_x(&player.next_frame().unwrap().unwrap().1)).collect();
+            assert_eq!(played, vec![4.0, 0.0, 1.0]);
+            // each pass starts as soon as the last one ends
+            assert_eq!(clock.now(), 10_000);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_rtps {
+    use crate::test_shared;
+    use std::io::{Cursor, ErrorKind};
+    use uorb_codec::common::{SensorBaroData, UorbMessage};
+    use uorb_codec::rtps::{crc16, RtpsCodec, TopicTable};
+    use uorb_codec::UorbMsgMeta;
+
+    const MESSAGE_IDS: &str = "\
+rtps:
+  # topic IDs as in uorb_rtps_message_ids.yaml
+  - msg: sensor_baro
+    id: 14
+    send: true
+  - msg: sensor_gyro
+    id: 17
+  - msg: vehicle_odometry
+    id: 80
+    receive: true
+  - msg: sensor_gyro_fifo  # an alias of a known type
+    id: 81
+    alias: sensor_gyro
+";
+
+    fn baro() -> SensorBaroData {
+        SensorBaroData {
+            timestamp: 1,
+            error_count: 2,
+            device_id: 3,
+            pressure: 1.5,
+            temperature: 20.0,
+        }
+    }
+
+    /// A hand-built frame for `baro()`, with fields in declaration order
+    fn baro_frame(seq: u8) -> Vec<u8> {
+        let mut payload = vec![];
+        payload.extend_from_slice(&1u64.to_le_bytes());
+        payload.extend_from_slice(&3u32.to_le_bytes());
+        // error_count is aligned to 8 bytes
+        payload.extend_from_slice(&[0; 4]);
+        payload.extend_from_slice(&2u64.to_le_bytes());
+        payload.extend_from_slice(&1.5f32.to_le_bytes());
+        payload.extend_from_slice(&20.0f32.to_le_bytes());
+        let crc = crc16(&payload);
+        let mut frame = vec![b'>', b'>', b'>', 14, seq, 0, payload.len() as u8, (crc >> 8) as u8, crc as u8];
+        frame.extend_from_slice(&payload);
+        frame
+    }
+
+    #[test]
+    pub fn test_rtps_crc16() {
+        assert_eq!(crc16(b"123456789"), 0xBB3D);
+    }
+
+    #[test]
+    pub fn test_rtps_topic_table() {
+        let table = TopicTable::parse(MESSAGE_IDS).unwrap();
+        assert_eq!(table.topics().count(), 4);
+        let baro = table.get(14).unwrap();
+        assert_eq!(baro.name, "sensor_baro");
+        assert_eq!(baro.hash, Some(SensorBaroData::MSG_HASH_CODE));
+        assert!(baro.send && !baro.receive);
+        // not a message this crate knows
+        assert_eq!(table.get(80).unwrap().hash, None);
+        let fifo = table.get(81).unwrap();
+        assert!(fifo.is_alias);
+        assert_eq!(fifo.hash, table.get(17).unwrap().hash);
+        assert_eq!(table.id_for_hash(fifo.hash.unwrap()), Some(17));
+
+        assert!(TopicTable::parse("rtps:\n  - msg: sensor_baro\n").is_err());
+    }
+
+    #[test]
+    pub fn test_rtps_encode_decode() {
+        let mut codec = RtpsCodec::new(TopicTable::parse(MESSAGE_IDS).unwrap());
+        let (header, msg) = baro().gen_ready_pair(0, 1);
+        assert_eq!(codec.encode_frame(&header, &msg).unwrap(), baro_frame(0));
+
+        let mut stream = vec![];
+        codec.write_msg(&mut stream, &header, &msg).unwrap();
+        let gyro = test_shared::get_sensor_gyro();
+        let (gyro_header, gyro_msg) = gyro.gen_ready_pair(2, gyro.timestamp);
+        codec.write_msg(&mut stream, &gyro_header, &gyro_msg).unwrap();
+
+        let mut input = b"noise>>".to_vec();
+        // a topic not in the table, then a corrupted frame
+        let mut unknown = baro_frame(7);
+        unknown[3] = 99;
+        input.extend_from_slice(&unknown);
+        let mut corrupted = baro_frame(8);
+        corrupted[12] ^= 0xFF;
+        input.extend_from_slice(&corrupted);
+        input.extend_from_slice(&stream);
+
+        let mut cursor = Cursor::new(input);
+        assert_eq!(codec.read_msg(&mut cursor).unwrap(), (header, msg));
+        assert_eq!(codec.crc_errors(), 1);
+        let (header, msg) = codec.read_msg(&mut cursor).unwrap();
+        assert_eq!(header.instance_id, 0);
+        assert_eq!(header.timestamp, gyro.timestamp);
+        assert_eq!(msg, UorbMessage::SensorGyro(gyro));
+        assert_eq!(codec.read_msg(&mut cursor).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+}
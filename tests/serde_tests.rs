@@ -0,0 +1,60 @@
+#![cfg(feature = "serde")]
+
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_serde {
+    use crate::test_shared;
+    use uorb_codec::common::{EstimatorStatusData, SensorGyroData, UorbMessage};
+    use uorb_codec::{UorbHeader, UorbMsgMeta};
+
+    fn messages() -> Vec<UorbMessage> {
+        vec![
+            UorbMessage::VehicleGpsPosition(test_shared::get_vehicle_gps_position()),
+            UorbMessage::VehicleStatus(test_shared::get_vehicle_status()),
+            UorbMessage::ActuatorControls(test_shared::get_actuator_controls()),
+            UorbMessage::BatteryStatus(test_shared::get_battery_status()),
+            UorbMessage::EstimatorStatus(test_shared::get_estimator_status()),
+            UorbMessage::SensorGyro(test_shared::get_sensor_gyro()),
+        ]
+    }
+
+    #[test]
+    pub fn test_message_json_round_trip() {
+        for msg in messages() {
+            let json = serde_json::to_string(&msg).unwrap();
+            let back: UorbMessage = serde_json::from_str(&json).unwrap();
+            assert_eq!(back, msg);
+        }
+    }
+
+    #[test]
+    pub fn test_message_tagged_by_raw_name() {
+        let gyro = test_shared::get_sensor_gyro();
+        let value = serde_json::to_value(UorbMessage::SensorGyro(gyro)).unwrap();
+        let fields = &value[SensorGyroData::MSG_RAW_NAME];
+        assert_eq!(fields["device_id"], 6887);
+        assert_eq!(fields["x"], 1111.0);
+
+        // the data types serialize as plain structs
+        let status = test_shared::get_estimator_status();
+        let value = serde_json::to_value(status).unwrap();
+        assert_eq!(value["states"].as_array().unwrap().len(), 24);
+        let back: EstimatorStatusData = serde_json::from_value(value).unwrap();
+        assert_eq!(back, status);
+
+        let unknown = r#"{"sensor_gyroscope":{"timestamp":0}}"#;
+        assert!(serde_json::from_str::<UorbMessage>(unknown).is_err());
+    }
+
+    #[test]
+    pub fn test_header_round_trip() {
+        let (header, msg) = test_shared::get_battery_status().gen_ready_pair(2, 123_456);
+        let json = serde_json::to_string(&(&header, &msg)).unwrap();
+        let (back_header, back_msg): (UorbHeader, UorbMessage) = serde_json::from_str(&json).unwrap();
+        assert_eq!(back_header, header);
+        assert_eq!(back_msg, msg);
+    }
+}
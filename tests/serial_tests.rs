@@ -0,0 +1,133 @@
+#![cfg(all(feature = "serial", unix))]
+extern crate libc;
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_serial {
+    use crate::test_shared;
+    use std::ffi::CStr;
+    use std::fs::File;
+    use std::io::{ErrorKind, Write};
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use uorb_codec::serial::{FlowControl, Parity, SerialConfig, SerialTransport};
+    use uorb_codec::UorbMsgMeta;
+
+    /// Open a pseudo-terminal, returning the master side and the path of the slave tty
+    fn open_pty() -> (File, PathBuf) {
+        unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(fd >= 0);
+            assert_eq!(libc::grantpt(fd), 0);
+            assert_eq!(libc::unlockpt(fd), 0);
+            let name = CStr::from_ptr(libc::ptsname(fd)).to_str().unwrap().to_owned();
+            (File::from_raw_fd(fd), PathBuf::from(name))
+        }
+    }
+
+    fn termios_of(port: &File) -> libc::termios {
+        unsafe {
+            let mut tio: libc::termios = std::mem::zeroed();
+            assert_eq!(libc::tcgetattr(port.as_raw_fd(), &mut tio), 0);
+            tio
+        }
+    }
+
+    #[test]
+    pub fn test_serial_configure() {
+        let (_master, path) = open_pty();
+        let config = SerialConfig {
+            baud: 115_200,
+            parity: Parity::Even,
+            flow_control: FlowControl::Hardware,
+        };
+        let _transport = SerialTransport::open(&path, config).unwrap();
+
+        // settings made through one descriptor apply to the tty itself
+        let tio = termios_of(&File::open(&path).unwrap());
+        assert_eq!(unsafe { libc::cfgetospeed(&tio) }, libc::B115200);
+        // a pseudo-terminal has no line hardware, so it does not keep parity or RTS/CTS;
+        // those flags are checked on the termios settings in serial.rs
+        assert_eq!(tio.c_lflag & (libc::ECHO | libc::ICANON), 0);
+
+        assert!(SerialTransport::open(&path, SerialConfig::new(12345)).is_err());
+    }
+
+    #[test]
+    pub fn test_serial_read_write() {
+        let (mut master, path) = open_pty();
+        let mut transport = SerialTransport::open(&path, SerialConfig::default()).unwrap();
+        let status = test_shared::get_vehicle_status();
+
+        // line noise before each frame
+        let mut frame = [0; uorb_codec::MAX_FRAME_LEN];
+        for timestamp in 42..44 {
+            let frame_len = status.encode_frame_into(0, timestamp, &mut frame).unwrap();
+            master.write_all(&[0x13, 0x55, 0x00]).unwrap();
+            master.write_all(&frame[..frame_len]).unwrap();
+        }
+        for timestamp in 42..44 {
+            let (hdr, msg) = transport.read_msg().unwrap();
+            assert_eq!(hdr.timestamp, timestamp);
+            assert_eq!(msg, status.wrap());
+        }
+
+        let (hdr, msg) = status.gen_ready_pair(1, 43);
+        transport.write_msg(&hdr, &msg).unwrap();
+        let (decoded_hdr, decoded_msg) = uorb_codec::read_msg(&mut master).unwrap();
+        assert_eq!(decoded_hdr.instance_id, 1);
+        assert_eq!(decoded_msg, msg);
+    }
+
+    #[test]
+    pub fn test_serial_reconnect() {
+        // a stable device path that is moved to a new tty, as udev does for a replugged adapter
+        let link = std::env::temp_dir().join(format!("uorb-serial-test-{}", std::process::id()));
+        let _ = std::fs::remove_file(&link);
+        let (master, path) = open_pty();
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        let mut transport = SerialTransport::open(&link, SerialConfig::default()).unwrap();
+        transport.set_reconnect_interval(Some(Duration::from_millis(10)));
+
+        let status = test_shared::get_vehicle_status();
+        let received = Arc::new(AtomicBool::new(false));
+        let replug_received = received.clone();
+        let replug = thread::spawn(move || {
+            // unplug
+            drop(master);
+            let (mut master, path) = open_pty();
+            let moved = link.with_extension("new");
+            std::os::unix::fs::symlink(&path, &moved).unwrap();
+            std::fs::rename(&moved, &link).unwrap();
+            // the transport flushes the tty when it reopens it, so keep sending until a frame gets through
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while !replug_received.load(Ordering::SeqCst) {
+                assert!(Instant::now() < deadline, "transport never reopened the tty");
+                let (hdr, msg) = status.gen_ready_pair(0, 7);
+                uorb_codec::write_msg(&mut master, &hdr, &msg).unwrap();
+                thread::sleep(Duration::from_millis(10));
+            }
+            (master, link)
+        });
+
+        // a frame cut short by the flush fails to decode
+        let (hdr, msg) = loop {
+            match transport.read_msg() {
+                Ok(frame) => break frame,
+                Err(err) => assert_eq!(err.kind(), ErrorKind::InvalidData),
+            }
+        };
+        received.store(true, Ordering::SeqCst);
+        assert!(transport.is_connected());
+        assert_eq!(hdr.timestamp, 7);
+        assert_eq!(msg, status.wrap());
+        let (_master, link) = replug.join().unwrap();
+        std::fs::remove_file(link).unwrap();
+    }
+}
@@ -1,4 +1,5 @@
 
+extern crate bytes;
 extern crate uorb_codec;
 
 pub mod test_shared;
@@ -6,6 +7,7 @@ pub mod test_shared;
 
 mod test_serialization {
     use crate::test_shared;
+    use bytes::IntoBuf;
     use uorb_codec::common::*;
     use uorb_codec::UorbMsgMeta;
 
@@ -25,6 +27,69 @@ mod test_serialization {
         assert_eq!(msg_data.onboard_control_sensors_health, decoded.onboard_control_sensors_health);
     }
 
+    #[test]
+    pub fn test_deserialize_actuator_controls_array() {
+        let msg_data = test_shared::get_actuator_controls();
+        let encoded:Vec<u8> = msg_data.ser();
+        assert_eq!(encoded.len(), ActuatorControlsData::ENCODED_LEN);
+        let decoded = ActuatorControlsData::deser(encoded.as_slice()).unwrap();
+        assert_eq!(msg_data.control, decoded.control);
+    }
+
+    #[test]
+    pub fn test_ser_into_matches_ser() {
+        let msg_data = test_shared::get_vehicle_gps_position();
+        let mut buf = [0u8; VehicleGpsPositionData::ENCODED_LEN];
+        msg_data.ser_into(&mut (&mut buf[..]).into_buf());
+        assert_eq!(&buf[..], msg_data.ser().as_slice());
+
+        let msg = msg_data.wrap();
+        let mut msg_buf = vec![];
+        msg.ser_into(&mut msg_buf);
+        assert_eq!(msg_buf.len(), msg.encoded_len());
+        assert_eq!(msg_buf, msg.ser());
+    }
+
+    #[test]
+    pub fn test_encode_frame_into() {
+        let msg_data = test_shared::get_sensor_gyro();
+        let mut buf = [0u8; uorb_codec::MAX_FRAME_LEN];
+        let frame_len = msg_data.encode_frame_into(3, 777, &mut buf).unwrap();
+        assert_eq!(frame_len, uorb_codec::UORB_HEADER_LEN + SensorGyroData::ENCODED_LEN);
+
+        let (hdr, msg) = msg_data.gen_ready_pair(3, 777);
+        let mut msg_buf = [0u8; uorb_codec::MAX_FRAME_LEN];
+        assert_eq!(msg.encode_frame_into(&hdr, &mut msg_buf).unwrap(), frame_len);
+        assert_eq!(&buf[..frame_len], &msg_buf[..frame_len]);
+
+        let mut written = vec![];
+        uorb_codec::write_msg(&mut written, &hdr, &msg).unwrap();
+        assert_eq!(written.as_slice(), &buf[..frame_len]);
+
+        let (decoded_hdr, decoded_msg) = uorb_codec::read_msg(&mut &buf[..frame_len]).unwrap();
+        assert_eq!(decoded_hdr.instance_id, 3);
+        assert_eq!(decoded_hdr.timestamp, 777);
+        assert_eq!(decoded_msg, msg);
+    }
+
+    #[test]
+    pub fn test_encode_frame_into_short_buffer() {
+        let msg_data = test_shared::get_sensor_gyro();
+        let mut buf = [0u8; SensorGyroData::ENCODED_LEN];
+        assert!(msg_data.encode_frame_into(0, 0, &mut buf).is_err());
+    }
+
+    #[test]
+    pub fn test_ser_into_slice() {
+        let msg_data = test_shared::get_battery_status();
+        let mut buf = [0u8; BatteryStatusData::ENCODED_LEN + 1];
+        assert_eq!(msg_data.ser_into_slice(&mut buf), Some(BatteryStatusData::ENCODED_LEN));
+        assert_eq!(&buf[..BatteryStatusData::ENCODED_LEN], msg_data.ser().as_slice());
+        assert_eq!(msg_data.wrap().ser_into_slice(&mut buf), Some(BatteryStatusData::ENCODED_LEN));
+        assert!(msg_data.ser_into_slice(&mut buf[..BatteryStatusData::ENCODED_LEN - 1]).is_none());
+        assert_eq!(BatteryStatusData::deser(&buf).unwrap(), msg_data);
+    }
+
     #[test]
     pub fn test_deserialize_sensor_gyro() {
         let msg_data = test_shared::get_sensor_gyro();
@@ -33,6 +98,45 @@ mod test_serialization {
         assert_eq!(msg_data.timestamp, decoded.timestamp);
     }
 
+    #[test]
+    pub fn test_view_sensor_gyro() {
+        let msg_data = test_shared::get_sensor_gyro();
+        let encoded:Vec<u8> = msg_data.ser();
+        let view = SensorGyroView::new(encoded.as_slice()).unwrap();
+        assert_eq!(view.timestamp(), msg_data.timestamp);
+        assert_eq!(view.device_id(), msg_data.device_id);
+        assert_eq!(view.z_raw(), msg_data.z_raw);
+        assert_eq!(view.to_owned(), msg_data);
+    }
 
-}
+    #[test]
+    pub fn test_view_array_fields() {
+        let msg_data = test_shared::get_actuator_controls();
+        let encoded:Vec<u8> = msg_data.ser();
+        let view = ActuatorControlsView::new(encoded.as_slice()).unwrap();
+        for idx in 0..msg_data.control.len() {
+            assert_eq!(view.control(idx), msg_data.control[idx]);
+        }
+        assert_eq!(view.to_owned(), msg_data);
+    }
+
+    #[test]
+    pub fn test_view_short_payload() {
+        let encoded:Vec<u8> = test_shared::get_vehicle_status().ser();
+        assert!(VehicleStatusView::new(&encoded[..encoded.len() - 1]).is_none());
+        assert!(uorb_codec::UorbMessageView::parse(VehicleStatusData::MSG_HASH_CODE, &encoded[..1]).is_none());
+    }
 
+    #[test]
+    pub fn test_message_view_dispatch() {
+        let msg_data = test_shared::get_vehicle_gps_position();
+        let encoded:Vec<u8> = msg_data.ser();
+        let view = uorb_codec::UorbMessageView::parse(msg_data.get_hash_code(), encoded.as_slice()).unwrap();
+        if let uorb_codec::UorbMessageView::VehicleGpsPosition(gps) = view {
+            assert_eq!(gps.lat(), msg_data.lat);
+        } else {
+            panic!("Parsed wrong message type");
+        }
+        assert_eq!(view.to_owned(), msg_data.wrap());
+    }
+}
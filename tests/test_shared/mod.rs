@@ -69,6 +69,73 @@ pub fn get_actuator_controls() -> ActuatorControlsData {
     }
 }
 
+pub fn get_battery_status() -> BatteryStatusData {
+    BatteryStatusData {
+        timestamp: 83838333,
+        voltage_v: 16.4,
+        voltage_filtered_v: 16.3,
+        current_a: 12.5,
+        current_filtered_a: 12.1,
+        average_current_a: 11.9,
+        discharged_mah: 850.0,
+        remaining: 0.71,
+        scale: 1.0,
+        temperature: 31.5,
+        cell_count: 4,
+        connected: true,
+        system_source: true,
+        priority: 0,
+        capacity: 5000,
+        cycle_count: 42,
+        run_time_to_empty: 17,
+        average_time_to_empty: 18,
+        serial_number: 1234,
+        voltage_cell_v: [4.1, 4.1, 4.1, 4.1],
+        max_cell_voltage_delta: 0.02,
+        is_powering_off: false,
+        warning: BatteryStatusData::BATTERY_WARNING_NONE,
+    }
+}
+
+pub fn get_estimator_status() -> EstimatorStatusData {
+    let mut states = [0.0; 24];
+    let mut covariances = [0.0; 24];
+    for idx in 0..24 {
+        states[idx] = idx as f32 * 0.5;
+        covariances[idx] = 1.0 / (idx as f32 + 1.0);
+    }
+
+    EstimatorStatusData {
+        timestamp: 83838333,
+        states,
+        n_states: 24.0,
+        vibe: [0.001, 0.002, 0.03],
+        covariances,
+        gps_check_fail_flags: 1 << EstimatorStatusData::GPS_CHECK_FAIL_MIN_SAT_COUNT,
+        control_mode_flags: 0x1234,
+        filter_fault_flags: 0,
+        pos_horiz_accuracy: 0.8,
+        pos_vert_accuracy: 1.2,
+        innovation_check_flags: 0,
+        mag_test_ratio: 0.1,
+        vel_test_ratio: 0.2,
+        pos_test_ratio: 0.3,
+        hgt_test_ratio: 0.4,
+        tas_test_ratio: 0.0,
+        hagl_test_ratio: 0.0,
+        beta_test_ratio: 0.0,
+        solution_status_flags: 0xFF,
+        health_flags: 0,
+        timeout_flags: 0,
+        time_slip: 0.0,
+        pre_flt_fail_innov_heading: false,
+        pre_flt_fail_innov_vel_horiz: false,
+        pre_flt_fail_innov_vel_vert: false,
+        pre_flt_fail_innov_height: false,
+        pre_flt_fail_mag_field_disturbed: true,
+    }
+}
+
 const GYRO_REBASE_FACTOR:f32 =  1E3;
 
 pub fn get_sensor_gyro() -> SensorGyroData {
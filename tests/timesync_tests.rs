@@ -0,0 +1,122 @@
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_timesync {
+    use crate::test_shared;
+    use uorb_codec::common::{TimesyncData, UorbMessage};
+    use uorb_codec::timesync::{
+        Timesync, TimesyncEvent, CONVERGENCE_WINDOW, MAX_CONSECUTIVE_HIGH_DEVIATION, MAX_CONSECUTIVE_HIGH_RTT,
+    };
+    use uorb_codec::{read_msg, UorbMsgMeta};
+
+    /// The remote clock runs this far ahead of the local one
+    const REMOTE_AHEAD: u64 = 5_000_000;
+    /// One-way latency of the link
+    const LATENCY: u64 = 1_000;
+
+    /// One request from `local` answered by `remote`, returning what the response caused
+    fn exchange(local: &mut Timesync, remote: &mut Timesync, now: u64, latency: u64, remote_ahead: u64)
+        -> Option<TimesyncEvent> {
+        let mut link = Vec::new();
+        local.send_request(&mut link, now).unwrap();
+
+        let (_header, request) = read_msg(&mut link.as_slice()).unwrap();
+        let mut back = Vec::new();
+        let status = remote.process(&mut back, &request, now + latency + remote_ahead).unwrap();
+        assert!(status.is_none());
+
+        let (_header, response) = read_msg(&mut back.as_slice()).unwrap();
+        local.handle_msg(&response, now + 2 * latency)
+    }
+
+    #[test]
+    pub fn test_timesync_offset() {
+        let mut local = Timesync::new();
+        let mut remote = Timesync::new();
+        assert_eq!(local.to_local(7_000_000), 7_000_000);
+
+        let mut now = 1_000_000;
+        for _ in 0..20 {
+            match exchange(&mut local, &mut remote, now, LATENCY, REMOTE_AHEAD) {
+                Some(TimesyncEvent::Updated(status)) => {
+                    assert_eq!(status.round_trip_time, 2 * LATENCY as u32);
+                    assert_eq!(status.observed_offset, -(REMOTE_AHEAD as i64));
+                    assert_eq!(status.remote_timestamp, now + LATENCY + REMOTE_AHEAD);
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+            now += 100_000;
+        }
+        assert!(local.synced() && !local.converged());
+        assert_eq!(local.estimated_offset(), -(REMOTE_AHEAD as i64));
+        assert_eq!(local.status().unwrap().estimated_offset, -(REMOTE_AHEAD as i64));
+
+        // remote timestamps move onto the local clock
+        assert_eq!(local.to_local(now + REMOTE_AHEAD), now);
+        let (mut header, _msg) = test_shared::get_sensor_gyro().gen_ready_pair(0, 9_000_000 + REMOTE_AHEAD);
+        local.to_local_header(&mut header);
+        assert_eq!(header.timestamp, 9_000_000);
+    }
+
+    #[test]
+    pub fn test_timesync_rtt_gating() {
+        let mut local = Timesync::new();
+        let mut remote = Timesync::new();
+        let mut now = 1_000_000;
+        exchange(&mut local, &mut remote, now, LATENCY, REMOTE_AHEAD).unwrap();
+        assert!(local.synced());
+
+        for _ in 0..MAX_CONSECUTIVE_HIGH_RTT {
+            now += 100_000;
+            match exchange(&mut local, &mut remote, now, 20_000, REMOTE_AHEAD) {
+                Some(TimesyncEvent::Rejected(status)) => assert_eq!(status.round_trip_time, 40_000),
+                other => panic!("unexpected {:?}", other),
+            }
+            assert_eq!(local.estimated_offset(), -(REMOTE_AHEAD as i64));
+        }
+        // one more slow response restarts the filter
+        now += 100_000;
+        exchange(&mut local, &mut remote, now, 20_000, REMOTE_AHEAD).unwrap();
+        assert!(!local.synced());
+    }
+
+    #[test]
+    pub fn test_timesync_deviation_gating() {
+        let mut local = Timesync::new();
+        let mut remote = Timesync::new();
+        let mut now = 1_000_000;
+        for _ in 0..CONVERGENCE_WINDOW {
+            exchange(&mut local, &mut remote, now, LATENCY, REMOTE_AHEAD).unwrap();
+            now += 10_000;
+        }
+        assert!(local.converged());
+
+        // the remote clock jumps, as when it reboots
+        let jumped = REMOTE_AHEAD + 2_000_000;
+        for _ in 0..MAX_CONSECUTIVE_HIGH_DEVIATION {
+            let event = exchange(&mut local, &mut remote, now, LATENCY, jumped);
+            assert!(matches!(event, Some(TimesyncEvent::Rejected(_))));
+            now += 10_000;
+        }
+        exchange(&mut local, &mut remote, now, LATENCY, jumped).unwrap();
+        assert!(!local.synced());
+        now += 10_000;
+        let event = exchange(&mut local, &mut remote, now, LATENCY, jumped);
+        assert!(matches!(event, Some(TimesyncEvent::Updated(_))));
+        assert_eq!(local.estimated_offset(), -(jumped as i64));
+    }
+
+    #[test]
+    pub fn test_timesync_ignored() {
+        let mut local = Timesync::new();
+        let gyro = UorbMessage::SensorGyro(test_shared::get_sensor_gyro());
+        assert_eq!(local.handle_msg(&gyro, 1_000_000), None);
+
+        // a response to a request sent in the future
+        let response = TimesyncData { timestamp: 0, seq: 0, tc1: 5_000_000_000, ts1: 2_000_000_000 };
+        assert_eq!(local.handle(&response, 1_000_000), None);
+        assert!(local.status().is_none());
+    }
+}
@@ -0,0 +1,152 @@
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_udp {
+    use crate::test_shared;
+    use std::io::ErrorKind;
+    use std::net::UdpSocket;
+    use std::time::Duration;
+    use uorb_codec::common::VehicleStatusData;
+    use uorb_codec::{UdpTransport, UorbMsgMeta, UORB_HEADER_LEN};
+
+    fn loopback_socket() -> UdpSocket {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        socket
+    }
+
+    const FRAME_LEN: usize = UORB_HEADER_LEN + VehicleStatusData::ENCODED_LEN;
+
+    #[test]
+    pub fn test_udp_ordering() {
+        let mut sender = UdpTransport::new(loopback_socket());
+        let mut receiver = UdpTransport::new(loopback_socket());
+        let dest = receiver.local_addr().unwrap();
+        let status = test_shared::get_vehicle_status();
+        for timestamp in 0..10 {
+            let (hdr, msg) = status.gen_ready_pair(0, timestamp);
+            sender.send_msg_to(dest, &hdr, &msg).unwrap();
+        }
+
+        for timestamp in 0..10 {
+            let (from, hdr, msg) = receiver.recv_msg().unwrap();
+            assert_eq!(from, sender.local_addr().unwrap());
+            assert_eq!(hdr.timestamp, timestamp);
+            assert_eq!(msg, status.wrap());
+        }
+        let stats = receiver.peer(&sender.local_addr().unwrap()).unwrap();
+        assert_eq!(stats.datagrams, 10);
+        assert_eq!(stats.frames, 10);
+        assert_eq!(stats.last_timestamp, 9);
+        assert_eq!(receiver.peers().count(), 1);
+    }
+
+    #[test]
+    pub fn test_udp_batching() {
+        // room for two frames per datagram
+        let mut sender = UdpTransport::with_batching(loopback_socket(), 2 * FRAME_LEN + 1);
+        let raw = loopback_socket();
+        let status = test_shared::get_vehicle_status();
+        for timestamp in 0..5 {
+            let (hdr, msg) = status.gen_ready_pair(0, timestamp);
+            sender.send_msg_to(raw.local_addr().unwrap(), &hdr, &msg).unwrap();
+        }
+        sender.flush().unwrap();
+
+        let mut buf = [0; 4 * FRAME_LEN];
+        let sizes: Vec<usize> = (0..3).map(|_| raw.recv(&mut buf).unwrap()).collect();
+        assert_eq!(sizes, vec![2 * FRAME_LEN, 2 * FRAME_LEN, FRAME_LEN]);
+    }
+
+    #[test]
+    pub fn test_udp_batching_oversize_frame_and_drop() {
+        // smaller than one frame
+        let mut sender = UdpTransport::with_batching(loopback_socket(), FRAME_LEN - 1);
+        let raw = loopback_socket();
+        let dest = raw.local_addr().unwrap();
+        let status = test_shared::get_vehicle_status();
+        let (hdr, msg) = status.gen_ready_pair(0, 1);
+        sender.send_msg_to(dest, &hdr, &msg).unwrap();
+
+        // sent whole, without waiting for a flush
+        let mut buf = [0; 4 * FRAME_LEN];
+        assert_eq!(raw.recv(&mut buf).unwrap(), FRAME_LEN);
+
+        // batched frames go out when the transport is dropped
+        let mut sender = UdpTransport::with_batching(loopback_socket(), 4 * FRAME_LEN);
+        for timestamp in 0..2 {
+            let (hdr, msg) = status.gen_ready_pair(0, timestamp);
+            sender.send_msg_to(dest, &hdr, &msg).unwrap();
+        }
+        drop(sender);
+        assert_eq!(raw.recv(&mut buf).unwrap(), 2 * FRAME_LEN);
+    }
+
+    #[test]
+    pub fn test_udp_batched_receive() {
+        let mut sender = UdpTransport::with_batching(loopback_socket(), 3 * FRAME_LEN);
+        let mut receiver = UdpTransport::new(loopback_socket());
+        let dest = receiver.local_addr().unwrap();
+        let status = test_shared::get_vehicle_status();
+        for timestamp in 0..5 {
+            let (hdr, msg) = status.gen_ready_pair(0, timestamp);
+            sender.send_msg_to(dest, &hdr, &msg).unwrap();
+        }
+        sender.flush().unwrap();
+
+        let timestamps: Vec<u64> = (0..5).map(|_| receiver.recv_msg().unwrap().1.timestamp).collect();
+        assert_eq!(timestamps, vec![0, 1, 2, 3, 4]);
+        let stats = receiver.peer(&sender.local_addr().unwrap()).unwrap();
+        assert_eq!(stats.datagrams, 2);
+        assert_eq!(stats.frames, 5);
+    }
+
+    #[test]
+    pub fn test_udp_truncated_datagram() {
+        let raw = loopback_socket();
+        let mut receiver = UdpTransport::new(loopback_socket());
+        let dest = receiver.local_addr().unwrap();
+        let status = test_shared::get_vehicle_status();
+
+        let mut frames = [0; 2 * FRAME_LEN];
+        status.encode_frame_into(0, 1, &mut frames).unwrap();
+        status.encode_frame_into(0, 2, &mut frames[FRAME_LEN..]).unwrap();
+        // a whole frame followed by part of another
+        raw.send_to(&frames[..FRAME_LEN + 20], dest).unwrap();
+        raw.send_to(&frames[FRAME_LEN..], dest).unwrap();
+
+        assert_eq!(receiver.recv_msg().unwrap().1.timestamp, 1);
+        assert_eq!(receiver.recv_msg().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(receiver.recv_msg().unwrap().1.timestamp, 2);
+        let stats = receiver.peer(&raw.local_addr().unwrap()).unwrap();
+        assert_eq!(stats.datagrams, 2);
+        assert_eq!(stats.frames, 2);
+        assert_eq!(stats.truncated, 1);
+        assert_eq!(stats.malformed, 0);
+    }
+
+    #[test]
+    pub fn test_udp_empty_and_trailing_datagrams() {
+        let raw = loopback_socket();
+        let mut receiver = UdpTransport::new(loopback_socket());
+        let dest = receiver.local_addr().unwrap();
+        let status = test_shared::get_vehicle_status();
+
+        let mut frame = [0; FRAME_LEN + 3];
+        status.encode_frame_into(0, 1, &mut frame).unwrap();
+        frame[FRAME_LEN..].copy_from_slice(&[0x01, 0x02, 0x03]);
+        raw.send_to(&[], dest).unwrap();
+        raw.send_to(&frame, dest).unwrap();
+
+        assert_eq!(receiver.recv_msg().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(receiver.recv_msg().unwrap().1.timestamp, 1);
+        assert_eq!(receiver.recv_msg().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        let stats = receiver.peer(&raw.local_addr().unwrap()).unwrap();
+        assert_eq!(stats.datagrams, 2);
+        assert_eq!(stats.frames, 1);
+        assert_eq!(stats.truncated, 0);
+        assert_eq!(stats.malformed, 2);
+    }
+}
@@ -0,0 +1,235 @@
+extern crate byteorder;
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_ulog {
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use std::io::Write;
+    use crate::test_shared;
+    use uorb_codec::common::{SensorGyroData, UorbMessage};
+    use uorb_codec::ulog::{ULogInfo, ULogReader, ULogRecord, ULogWriter, ULOG_MAGIC};
+    use uorb_codec::UorbMsgMeta;
+
+    fn record(buf: &mut Vec<u8>, kind: u8, body: &[u8]) {
+        buf.write_u16::<LittleEndian>(body.len() as u16).unwrap();
+        buf.push(kind);
+        buf.extend_from_slice(body);
+    }
+
+    fn key_value(key: &str, value: &[u8]) -> Vec<u8> {
+        let mut body = vec![key.len() as u8];
+        body.extend_from_slice(key.as_bytes());
+        body.extend_from_slice(value);
+        body
+    }
+
+    /// A log with a PX4-style sensor_gyro layout, which orders fields differently
+    /// from the generated type, omits some and adds padding
+    fn sample_log() -> Vec<u8> {
+        let mut log = ULOG_MAGIC.to_vec();
+        log.push(1);
+        log.write_u64::<LittleEndian>(1000).unwrap();
+        record(&mut log, b'B', &[0; 40]);
+        record(&mut log, b'F', b"sensor_gyro:uint64_t timestamp;float x;float y;float z;\
+                                 uint32_t device_id;int16_t x_raw;uint8_t[2] _padding0;");
+        record(&mut log, b'F', b"inner_t:float a;float b;");
+        record(&mut log, b'F', b"custom_topic:uint64_t timestamp;inner_t[2] inner;");
+        record(&mut log, b'I', &key_value("char[4] ver_hw", b"PX4X"));
+        record(&mut log, b'P', &key_value("int32_t SYS_AUTOSTART", &4001i32.to_le_bytes()));
+        record(&mut log, b'P', &key_value("float MC_ROLL_P", &6.5f32.to_le_bytes()));
+        record(&mut log, b'A', b"\x01\x00\x00sensor_gyro");
+        record(&mut log, b'A', b"\x00\x01\x00custom_topic");
+
+        let mut gyro = vec![0, 0];
+        gyro.write_u64::<LittleEndian>(123_456).unwrap();
+        for value in &[0.5f32, -0.25, 2.0] {
+            gyro.write_f32::<LittleEndian>(*value).unwrap();
+        }
+        gyro.write_u32::<LittleEndian>(6887).unwrap();
+        gyro.write_i16::<LittleEndian>(-12).unwrap();
+        gyro.write_all(&[0, 0]).unwrap();
+        record(&mut log, b'D', &gyro);
+
+        let mut custom = vec![1, 0];
+        custom.write_u64::<LittleEndian>(123_500).unwrap();
+        custom.write_all(&[0; 16]).unwrap();
+        record(&mut log, b'D', &custom);
+
+        record(&mut log, b'O', &100u16.to_le_bytes());
+        let mut logging = vec![6];
+        logging.write_u64::<LittleEndian>(123_600).unwrap();
+        logging.write_all(b"takeoff").unwrap();
+        record(&mut log, b'L', &logging);
+        record(&mut log, b'S', &[0x2F, 0x73, 0x13, 0x20, 0x25, 0x0C, 0xBB, 0x12]);
+        record(&mut log, b'X', b"??");
+        // cut off part way through a record
+        log.extend_from_slice(&[20, 0, b'D', 0]);
+        log
+    }
+
+    #[test]
+    pub fn test_ulog_records() {
+        let log = sample_log();
+        let reader = ULogReader::new(log.as_slice()).unwrap();
+        assert_eq!(reader.header().version, 1);
+        assert_eq!(reader.header().timestamp, 1000);
+        let records: Vec<ULogRecord> = reader.map(|res| res.unwrap()).collect();
+        assert_eq!(records.len(), 15);
+
+        match &records[4] {
+            ULogRecord::Info(info) => {
+                assert_eq!(info.key, "ver_hw");
+                assert_eq!(info.as_str(), Some("PX4X"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match (&records[5], &records[6]) {
+            (ULogRecord::Parameter(autostart), ULogRecord::Parameter(roll_p)) => {
+                assert_eq!(autostart.as_i32(), Some(4001));
+                assert_eq!(roll_p.key, "MC_ROLL_P");
+                assert_eq!(roll_p.as_f32(), Some(6.5));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match &records[10] {
+            ULogRecord::RawData { subscription, payload } => {
+                assert_eq!(subscription.name, "custom_topic");
+                assert_eq!(payload.len(), 24);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(records[11], ULogRecord::Dropout { duration_ms: 100 });
+        assert_eq!(records[12], ULogRecord::Logging {
+            level: 6, tag: None, timestamp: 123_600, message: "takeoff".to_string() });
+        assert_eq!(records[13], ULogRecord::Sync);
+        assert_eq!(records[14], ULogRecord::Unknown { record_type: b'X', body: b"??".to_vec() });
+    }
+
+    #[test]
+    pub fn test_ulog_messages() {
+        let log = sample_log();
+        let msgs: Vec<_> = ULogReader::new(log.as_slice()).unwrap()
+            .messages()
+            .map(|res| res.unwrap())
+            .collect();
+        assert_eq!(msgs.len(), 1);
+        let (hdr, msg) = &msgs[0];
+        assert_eq!(hdr.hash, SensorGyroData::MSG_HASH_CODE);
+        assert_eq!(hdr.instance_id, 1);
+        assert_eq!(hdr.timestamp, 123_456);
+        let expected = SensorGyroData {
+            timestamp: 123_456,
+            x: 0.5,
+            y: -0.25,
+            z: 2.0,
+            device_id: 6887,
+            x_raw: -12,
+            ..Default::default()
+        };
+        assert_eq!(*msg, UorbMessage::SensorGyro(expected));
+    }
+
+    #[test]
+    pub fn test_ulog_mismatched_format_is_raw() {
+        let mut log = ULOG_MAGIC.to_vec();
+        log.push(1);
+        log.write_u64::<LittleEndian>(0).unwrap();
+        // x is logged with a different type than the generated definition
+        record(&mut log, b'F', b"sensor_gyro:uint64_t timestamp;double x;");
+        record(&mut log, b'A', b"\x00\x00\x00sensor_gyro");
+        record(&mut log, b'D', &[0; 18]);
+        let records: Vec<ULogRecord> = ULogReader::new(log.as_slice()).unwrap()
+            .map(|res| res.unwrap())
+            .collect();
+        assert!(matches!(records[2], ULogRecord::RawData { .. }));
+
+        assert!(ULogReader::new(&b"not a ulog file"[..]).is_err());
+    }
+
+    fn sample_stream() -> Vec<(uorb_codec::UorbHeader, UorbMessage)> {
+        let gyro = test_shared::get_sensor_gyro();
+        let estimator = test_shared::get_estimator_status();
+        vec![
+            gyro.gen_ready_pair(0, gyro.timestamp),
+            gyro.gen_ready_pair(1, gyro.timestamp),
+            estimator.gen_ready_pair(0, estimator.timestamp),
+            gyro.gen_ready_pair(1, gyro.timestamp),
+        ]
+    }
+
+    #[test]
+    pub fn test_ulog_round_trip() {
+        let mut writer = ULogWriter::new(Vec::new(), 5000).unwrap();
+        writer.write_info(&ULogInfo::string("sys_name", "PX4")).unwrap();
+        writer.write_parameter(&ULogInfo::int32("SYS_AUTOSTART", 4001)).unwrap();
+        for (header, msg) in sample_stream() {
+            writer.write_msg(&header, &msg).unwrap();
+        }
+        // a parameter change in the data section
+        writer.write_parameter(&ULogInfo::float("MC_ROLL_P", 6.5)).unwrap();
+        let log = writer.into_inner().unwrap();
+
+        let reader = ULogReader::new(log.as_slice()).unwrap();
+        assert_eq!(reader.header().timestamp, 5000);
+        let records: Vec<ULogRecord> = reader.map(|res| res.unwrap()).collect();
+        let formats = records.iter().filter(|record| matches!(record, ULogRecord::Format(_))).count();
+        assert_eq!(formats, UorbMessage::KNOWN_HASHES.len());
+        let subscriptions: Vec<_> = records.iter()
+            .filter_map(|record| match record {
+                ULogRecord::AddLogged(subscription) => Some((subscription.name.as_str(), subscription.multi_id)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(subscriptions, vec![("sensor_gyro", 0), ("sensor_gyro", 1), ("estimator_status", 0)]);
+        let params: Vec<ULogInfo> = records.iter()
+            .filter_map(|record| match record {
+                ULogRecord::Parameter(param) => Some(param.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(params, vec![ULogInfo::int32("SYS_AUTOSTART", 4001), ULogInfo::float("MC_ROLL_P", 6.5)]);
+        assert_eq!(records[1], ULogRecord::Info(ULogInfo::string("sys_name", "PX4")));
+
+        let msgs: Vec<_> = ULogReader::new(log.as_slice()).unwrap()
+            .messages()
+            .map(|res| res.unwrap())
+            .collect();
+        assert_eq!(msgs, sample_stream());
+    }
+
+    #[test]
+    pub fn test_ulog_incremental_flush() {
+        let gyro = test_shared::get_sensor_gyro();
+        let (header, msg) = gyro.gen_ready_pair(0, gyro.timestamp);
+        let mut writer = ULogWriter::new(Vec::new(), 0).unwrap();
+        writer.write_msg(&header, &msg).unwrap();
+        assert!(writer.get_ref().is_empty());
+
+        writer.flush().unwrap();
+        let count_msgs = |log: &[u8]| ULogReader::new(log).unwrap().messages().count();
+        assert_eq!(count_msgs(writer.get_ref()), 1);
+
+        writer.set_flush_threshold(0);
+        writer.write_msg(&header, &msg).unwrap();
+        assert_eq!(count_msgs(writer.get_ref()), 2);
+
+        let mut wrong_header = header.clone();
+        wrong_header.hash = test_shared::get_vehicle_status().get_hash_code();
+        assert!(writer.write_msg(&wrong_header, &msg).is_err());
+    }
+
+    #[test]
+    pub fn test_ulog_flush_on_drop() {
+        let gyro = test_shared::get_sensor_gyro();
+        let (header, msg) = gyro.gen_ready_pair(0, gyro.timestamp);
+        let mut log = Vec::new();
+        {
+            let mut writer = ULogWriter::new(&mut log, 0).unwrap();
+            writer.write_msg(&header, &msg).unwrap();
+        }
+        assert!(log.starts_with(&ULOG_MAGIC));
+        assert_eq!(ULogReader::new(log.as_slice()).unwrap().messages().count(), 1);
+    }
+}
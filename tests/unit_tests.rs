@@ -0,0 +1,70 @@
+extern crate uorb_codec;
+
+pub mod test_shared;
+
+
+mod test_units {
+    use crate::test_shared;
+    use uorb_codec::common::*;
+    use uorb_codec::{FieldInfo, FieldUnit, UorbMsgMeta};
+
+    fn field(fields: &'static [FieldInfo], name: &str) -> FieldInfo {
+        *fields.iter().find(|field| field.name == name).unwrap()
+    }
+
+    fn unit(fields: &'static [FieldInfo], name: &str) -> Option<(&'static str, f64)> {
+        field(fields, name).unit.map(|unit| (unit.name, unit.scale))
+    }
+
+    #[test]
+    pub fn test_units_from_comments() {
+        let gps = VehicleGpsPositionData::FIELDS;
+        assert_eq!(unit(gps, "timestamp"), Some(("us", 1.0)));
+        assert_eq!(unit(gps, "lat"), Some(("deg", 1e-7)));
+        assert_eq!(unit(gps, "alt"), Some(("m", 1e-3)));
+        assert_eq!(unit(gps, "vel_n_m_s"), Some(("m/s", 1.0)));
+        assert_eq!(unit(gps, "heading"), Some(("rad", 1.0)));
+        assert_eq!(unit(gps, "hdop"), None);
+        // "per millisecond" is not the field's unit
+        assert_eq!(unit(gps, "noise_per_ms"), None);
+
+        assert_eq!(unit(SensorAccelData::FIELDS, "x"), Some(("m/s^2", 1.0)));
+        assert_eq!(unit(SensorAccelData::FIELDS, "x_integral"), Some(("m/s", 1.0)));
+        assert_eq!(unit(SensorAccelData::FIELDS, "temperature"), Some(("degC", 1.0)));
+        assert_eq!(unit(SensorBaroData::FIELDS, "temperature"), Some(("degC", 1.0)));
+        assert_eq!(unit(SensorGyroData::FIELDS, "x"), Some(("rad/s", 1.0)));
+        assert_eq!(unit(SensorGyroData::FIELDS, "scaling"), None);
+        assert_eq!(unit(BatteryStatusData::FIELDS, "voltage_v"), Some(("V", 1.0)));
+        assert_eq!(unit(BatteryStatusData::FIELDS, "run_time_to_empty"), Some(("min", 1.0)));
+        assert_eq!(unit(TimesyncData::FIELDS, "tc1"), Some(("ns", 1.0)));
+        assert_eq!(unit(VehicleAttitudeData::FIELDS, "q"), None);
+    }
+
+    #[test]
+    pub fn test_unit_overrides() {
+        assert_eq!(unit(DifferentialPressureData::FIELDS, "differential_pressure_raw_pa"), Some(("Pa", 1.0)));
+        assert_eq!(unit(SensorGyroData::FIELDS, "x_integral"), Some(("rad", 1.0)));
+        assert_eq!(unit(BatteryStatusData::FIELDS, "voltage_cell_v"), Some(("V", 1.0)));
+        assert_eq!(unit(ActuatorControlsData::FIELDS, "timestamp_sample"), Some(("us", 1.0)));
+    }
+
+    #[test]
+    pub fn test_scaled_accessors() {
+        let gps = test_shared::get_vehicle_gps_position();
+        assert_eq!(gps.lat_deg(), 1005.0 * 1e-7);
+        assert_eq!(gps.lon_deg(), 3355.0 * 1e-7);
+        assert_eq!(gps.alt_m(), 0.5);
+        assert_eq!(gps.alt_ellipsoid_m(), 0.5);
+
+        let encoded = gps.ser();
+        let view = VehicleGpsPositionView::new(&encoded).unwrap();
+        assert_eq!(view.lat_deg(), gps.lat_deg());
+        assert_eq!(view.alt_m(), gps.alt_m());
+
+        let lat = field(VehicleGpsPositionData::FIELDS, "lat");
+        assert_eq!(lat.unit, Some(FieldUnit { name: "deg", scale: 1e-7 }));
+        assert_eq!(lat.scaled_values(&encoded).collect::<Vec<f64>>(), vec![gps.lat_deg()]);
+        let hdop = field(VehicleGpsPositionData::FIELDS, "hdop");
+        assert_eq!(hdop.scaled_values(&encoded).collect::<Vec<f64>>(), vec![2.0]);
+    }
+}